@@ -0,0 +1,595 @@
+use crate::app::App;
+use crate::columns::Column;
+use crate::control::{self, AuditEntry, Control};
+use crate::environ::{self, EnvVar};
+use crate::limits::{self, ResourceLimit};
+use crate::metrics::{self, MetricsOptions};
+use crate::process::{self, ProcessInfo};
+use crate::security::{self, SecurityInfo};
+use serde::Serialize;
+use serde_json::{Value, json};
+use crate::web_tui;
+use crossterm::event::KeyEvent;
+use std::io;
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+
+// Single page dashboard, built into the binary so it works without network access
+const DASHBOARD: &str = include_str!("dashboard.html");
+const TERMINAL_VIEW: &str = include_str!("web_tui.html");
+
+// Transport independent response, turned into a tiny_http one by the webserver
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl ApiResponse {
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => ApiResponse {
+                status,
+                content_type: "application/json",
+                headers: vec![],
+                body,
+            },
+            Err(e) => Self::error(500, &e.to_string()),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        ApiResponse {
+            status,
+            content_type: "application/json",
+            headers: vec![],
+            body: json!({ "error": message }).to_string(),
+        }
+    }
+
+    fn from_io_error(pid: u32, e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Self::error(404, &format!("process {} not found", pid)),
+            io::ErrorKind::PermissionDenied => Self::error(403, &format!("process {}: {}", pid, e)),
+            _ => Self::error(500, &format!("process {}: {}", pid, e)),
+        }
+    }
+}
+
+// What the router needs from an HTTP request
+#[derive(Debug, Clone, Default)]
+pub struct ApiRequest {
+    pub method: String,
+    pub url: String,
+    pub body: String,
+    pub authorization: Option<String>,
+    pub user_agent: Option<String>,
+    pub client: String, // remote address
+}
+
+// GET /processes/{pid}, everything the detail tabs show that is cheap to read
+#[derive(Debug, Serialize)]
+pub struct ProcessDetail {
+    #[serde(flatten)]
+    pub process: ProcessInfo,
+    pub cmdline: Option<Vec<String>>,
+    pub exe: Option<String>,
+    pub cwd: Option<String>,
+    pub security: Option<SecurityInfo>,
+    pub limits: Option<Vec<ResourceLimit>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListQuery {
+    pub filter: String,
+    pub sort: Column,
+    pub descending: bool,
+    pub limit: Option<usize>,
+    pub fields: Option<Vec<String>>,
+}
+
+// Keys a process serializes to, the only valid values for fields=
+const PROCESS_FIELDS: [&str; 19] = [
+    "pid",
+    "name",
+    "stat",
+    "cpu_time",
+    "memory",
+    "cpu_percent",
+    "io",
+    "read_rate",
+    "write_rate",
+    "cgroup",
+    "namespaces",
+    "isolated_namespaces",
+    "ns_pids",
+    "cap_eff",
+    "flags",
+    "oom_score",
+    "oom_score_adj",
+    "uid",
+    "threads",
+];
+
+pub fn parse_list_query(query: &str) -> Result<ListQuery, String> {
+    let mut list = ListQuery {
+        filter: String::new(),
+        sort: Column::Pid,
+        descending: false,
+        limit: None,
+        fields: None,
+    };
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "filter" => list.filter = value.to_lowercase(),
+            "sort" => list.sort = Column::from_key(&value).ok_or_else(|| format!("unknown sort key '{}'", value))?,
+            "order" => {
+                list.descending = match value.as_ref() {
+                    "asc" => false,
+                    "desc" => true,
+                    _ => return Err(format!("order must be asc or desc, not '{}'", value)),
+                }
+            }
+            "limit" => list.limit = Some(value.parse().map_err(|_| format!("invalid limit '{}'", value))?),
+            "fields" => {
+                let fields: Vec<String> = value.split(',').filter(|f| !f.is_empty()).map(str::to_owned).collect();
+                if let Some(unknown) = fields.iter().find(|f| !PROCESS_FIELDS.contains(&f.as_str())) {
+                    return Err(format!("unknown field '{}'", unknown));
+                }
+                list.fields = Some(fields);
+            }
+            _ => return Err(format!("unknown query parameter '{}'", key)),
+        }
+    }
+    Ok(list)
+}
+
+// Filter, sort and limit, shared with batch output
+pub fn select_processes<'a>(processes: &'a [ProcessInfo], query: &ListQuery) -> Vec<&'a ProcessInfo> {
+    let mut matching: Vec<&ProcessInfo> = processes
+        .iter()
+        .filter(|p| p.name.to_lowercase().contains(&query.filter))
+        .collect();
+    if query.descending {
+        matching.sort_by(|a, b| query.sort.compare(b, a));
+    } else {
+        matching.sort_by(|a, b| query.sort.compare(a, b));
+    }
+    matching.truncate(query.limit.unwrap_or(usize::MAX));
+    matching
+}
+
+pub fn list_processes(processes: &[ProcessInfo], query: &ListQuery) -> Vec<Value> {
+    select_processes(processes, query)
+        .into_iter()
+        .map(|p| {
+            let mut value = serde_json::to_value(p).unwrap_or(Value::Null);
+            if let (Some(fields), Value::Object(map)) = (&query.fields, &mut value) {
+                map.retain(|key, _| fields.contains(key));
+            }
+            value
+        })
+        .collect()
+}
+
+fn find_process(app: &Mutex<App>, pid: u32) -> Result<ProcessInfo, ApiResponse> {
+    let app = app.lock().unwrap();
+    app.all_processes
+        .iter()
+        .find(|p| p.pid == pid)
+        .cloned()
+        .ok_or_else(|| ApiResponse::error(404, &format!("process {} not found", pid)))
+}
+
+fn process_detail(process: ProcessInfo) -> ProcessDetail {
+    let pid = process.pid;
+    ProcessDetail {
+        process,
+        cmdline: process::read_cmdline(pid).ok(),
+        exe: process::read_link(pid, "exe"),
+        cwd: process::read_link(pid, "cwd"),
+        security: security::read_security_info(pid).ok(),
+        limits: limits::read_limits(pid).ok(),
+    }
+}
+
+fn read_json<T: Serialize>(pid: u32, read: impl FnOnce(u32) -> io::Result<T>) -> ApiResponse {
+    match read(pid) {
+        Ok(value) => ApiResponse::json(200, &value),
+        Err(e) => ApiResponse::from_io_error(pid, &e),
+    }
+}
+
+fn process_route(app: &Mutex<App>, pid: u32, sub: Option<&str>) -> ApiResponse {
+    let process = match find_process(app, pid) {
+        Ok(process) => process,
+        Err(response) => return response,
+    };
+    match sub {
+        None => ApiResponse::json(200, &process_detail(process)),
+        Some("children") => {
+            let app = app.lock().unwrap();
+            let children: Vec<&ProcessInfo> = app.all_processes.iter().filter(|p| p.stat.ppid == pid).collect();
+            ApiResponse::json(200, &children)
+        }
+        Some("fds") => read_json(pid, process::read_open_files),
+        Some("maps") => read_json(pid, process::read_maps),
+        Some("environ") => read_json::<Vec<EnvVar>>(pid, environ::read_environ),
+        Some(_) => ApiResponse::error(404, "not found"),
+    }
+}
+
+// The method each endpoint accepts, None if there is no such endpoint
+fn allowed_method(segments: &[&str]) -> Option<&'static str> {
+    match segments {
+        [] | ["processes"] | ["system"] | ["processes", _] | ["processes", _, "children" | "fds" | "maps" | "environ"] => {
+            Some("GET")
+        }
+        ["processes", _, "signal" | "renice" | "affinity"] => Some("POST"),
+        ["events"] | ["tui", "events"] => Some("GET"), // streamed by the web server itself
+        ["tui"] | ["metrics"] => Some("GET"),
+        ["tui", "keys"] => Some("POST"),
+        _ => None,
+    }
+}
+
+// Every attempt is audited, including ones refused for a missing token or bad input.
+// The body is only parsed once the token checks out.
+fn control_route(request: &ApiRequest, control: Option<&Control>, pid: &str, action: &str) -> ApiResponse {
+    let Some(control) = control else {
+        return ApiResponse::error(403, "process control is disabled, start with --control-token-file");
+    };
+    let authorized = control.is_authorized(request.authorization.as_deref());
+    let pid_number = pid.parse::<u32>().ok();
+    let parsed = authorized.then(|| control::parse_action(action, &request.body));
+    let (starttime, result) = match (pid_number, &parsed) {
+        (_, None) => (None, Err((401, "missing or invalid bearer token".to_string()))),
+        (None, _) => (None, Err((400, format!("invalid pid '{}'", pid)))),
+        (_, Some(Err(e))) => (None, Err((400, e.clone()))),
+        (Some(pid), Some(Ok((action, expected)))) => {
+            let (starttime, result) = control::perform_checked(pid, *expected, action);
+            (starttime, result.map_err(|e| (e.status(), e.message())))
+        }
+    };
+
+    let params = match &parsed {
+        Some(Ok((action, _))) => action.params(),
+        _ => String::new(),
+    };
+    control.audit(&AuditEntry {
+        time: control::now_utc(),
+        client: &request.client,
+        user_agent: request.user_agent.as_deref(),
+        authorized,
+        action,
+        pid: pid_number,
+        starttime,
+        params: &params,
+        result: result.clone().map_err(|(_, message)| message),
+    });
+    control_response(result.map(|_| json!({ "pid": pid_number, "starttime": starttime, "action": action, "params": params })))
+}
+
+// 403 when control is disabled, 401 without a valid bearer token
+fn require_token(request: &ApiRequest, control: Option<&Control>) -> Result<(), ApiResponse> {
+    let Some(control) = control else {
+        return Err(ApiResponse::error(403, "this endpoint needs --control-token-file"));
+    };
+    if control.is_authorized(request.authorization.as_deref()) {
+        Ok(())
+    } else {
+        Err(control_response(Err((401, "missing or invalid bearer token".to_string()))))
+    }
+}
+
+fn control_response(result: Result<Value, (u16, String)>) -> ApiResponse {
+    match result {
+        Ok(value) => ApiResponse::json(200, &value),
+        Err((status, message)) => {
+            let mut response = ApiResponse::error(status, &message);
+            if status == 401 {
+                response.headers.push(("WWW-Authenticate".into(), "Bearer".into()));
+            }
+            response
+        }
+    }
+}
+
+// A forwarded key can do anything the terminal user can, so it needs the control token too
+fn key_route(request: &ApiRequest, control: Option<&Control>, keys: Option<&Sender<KeyEvent>>) -> ApiResponse {
+    let Some(control) = control else {
+        return ApiResponse::error(403, "keyboard input is disabled, start with --control-token-file");
+    };
+    let authorized = control.is_authorized(request.authorization.as_deref());
+    let key = authorized.then(|| web_tui::parse_key(&request.body));
+    let result = match (&key, keys) {
+        (None, _) => Err((401, "missing or invalid bearer token".to_string())),
+        (Some(Err(e)), _) => Err((400, e.clone())),
+        (Some(Ok(_)), None) => Err((503, "no terminal UI is running".to_string())),
+        (Some(Ok(key)), Some(keys)) => keys
+            .send(*key)
+            .map_err(|_| (503, "the terminal UI has exited".to_string())),
+    };
+    let params = match &key {
+        Some(Ok(key)) => web_tui::describe_key(key),
+        _ => String::new(),
+    };
+    control.audit(&AuditEntry {
+        time: control::now_utc(),
+        client: &request.client,
+        user_agent: request.user_agent.as_deref(),
+        authorized,
+        action: "key",
+        pid: None,
+        starttime: None,
+        params: &params,
+        result: result.clone().map_err(|(_, message)| message),
+    });
+    control_response(result.map(|_| json!({ "key": params })))
+}
+
+pub fn handle(
+    request: &ApiRequest,
+    app: &Mutex<App>,
+    control: Option<&Control>,
+    keys: Option<&Sender<KeyEvent>>,
+    metrics: &MetricsOptions,
+) -> ApiResponse {
+    let url = request.url.as_str();
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let Some(allowed) = allowed_method(&segments) else {
+        return ApiResponse::error(404, &format!("no such endpoint {}", path));
+    };
+    if request.method != allowed {
+        let mut response = ApiResponse::error(405, &format!("method {} not allowed", request.method));
+        response.headers.push(("Allow".into(), allowed.into()));
+        return response;
+    }
+
+    // Open files, mappings and above all the environment can hold secrets, so they need the token too
+    if let ["processes", _, "fds" | "maps" | "environ"] = segments.as_slice()
+        && let Err(response) = require_token(request, control)
+    {
+        return response;
+    }
+
+    match segments.as_slice() {
+        [] => ApiResponse {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            headers: vec![],
+            body: DASHBOARD.to_owned(),
+        },
+        ["tui"] => ApiResponse {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            headers: vec![],
+            body: TERMINAL_VIEW.to_owned(),
+        },
+        ["tui", "keys"] => key_route(request, control, keys),
+        ["processes"] => match parse_list_query(query) {
+            Ok(query) => ApiResponse::json(200, &list_processes(&app.lock().unwrap().all_processes, &query)),
+            Err(e) => ApiResponse::error(400, &e),
+        },
+        ["system"] => ApiResponse::json(200, &app.lock().unwrap().system),
+        ["metrics"] => ApiResponse {
+            status: 200,
+            content_type: metrics::CONTENT_TYPE,
+            headers: vec![],
+            body: metrics::collect(app, metrics),
+        },
+        ["processes", pid, action @ ("signal" | "renice" | "affinity")] => control_route(request, control, pid, action),
+        ["processes", pid, rest @ ..] => match pid.parse() {
+            Ok(pid) => process_route(app, pid, rest.first().copied()),
+            Err(_) => ApiResponse::error(400, &format!("invalid pid '{}'", pid)),
+        },
+        _ => ApiResponse::error(404, &format!("no such endpoint {}", path)),
+    }
+}
+
+#[cfg(test)]
+fn test_app() -> Mutex<App> {
+    let mut init = ProcessInfo::new(1, "init".into(), 0, 300);
+    init.cpu_percent = 1.0;
+    let mut bash = ProcessInfo::new(100, "bash".into(), 0, 200);
+    bash.stat.ppid = 1;
+    bash.cpu_percent = 5.0;
+    let mut own = ProcessInfo::new(std::process::id(), "task-manager".into(), 0, 100);
+    own.stat.ppid = 100;
+    Mutex::new(App {
+        all_processes: vec![init, bash, own],
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+fn get(url: &str, app: &Mutex<App>) -> ApiResponse {
+    let request = ApiRequest {
+        method: "GET".into(),
+        url: url.into(),
+        ..Default::default()
+    };
+    handle(&request, app, None, None, &MetricsOptions::default())
+}
+
+#[cfg(test)]
+fn body(response: &ApiResponse) -> Value {
+    serde_json::from_str(&response.body).unwrap()
+}
+
+#[test]
+fn test_list_filter_sort_limit_fields() {
+    let app = test_app();
+    let response = get("/processes?sort=cpu_percent&order=desc&limit=2&fields=pid,name", &app);
+    assert_eq!(response.status, 200);
+    assert_eq!(body(&response), json!([{"pid": 100, "name": "bash"}, {"pid": 1, "name": "init"}]));
+
+    let response = get("/processes?filter=BA", &app);
+    assert_eq!(body(&response).as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_list_rejects_bad_query() {
+    let app = test_app();
+    for url in [
+        "/processes?sort=bogus",
+        "/processes?order=up",
+        "/processes?limit=-1",
+        "/processes?fields=pid,secret",
+        "/processes?page=2",
+    ] {
+        let response = get(url, &app);
+        assert_eq!(response.status, 400, "{}", url);
+        assert!(body(&response)["error"].is_string());
+    }
+}
+
+#[test]
+fn test_status_codes() {
+    let app = test_app();
+    assert_eq!(get("/nope", &app).status, 404);
+    assert_eq!(get("/processes/4000000000", &app).status, 404);
+    assert_eq!(get("/processes/abc", &app).status, 400);
+    assert_eq!(get("/processes/1/bogus", &app).status, 404);
+    let delete = ApiRequest {
+        method: "DELETE".into(),
+        url: "/processes/1".into(),
+        ..Default::default()
+    };
+    let response = handle(&delete, &app, None, None, &MetricsOptions::default());
+    assert_eq!(response.status, 405);
+    assert_eq!(response.headers, vec![("Allow".to_string(), "GET".to_string())]);
+    assert_eq!(get("/processes/1/signal", &app).status, 405);
+}
+
+#[test]
+fn test_process_endpoints() {
+    let app = test_app();
+    let children = body(&get("/processes/1/children", &app));
+    assert_eq!(children[0]["pid"], 100);
+
+    let own = std::process::id();
+    let detail = body(&get(&format!("/processes/{}", own), &app));
+    assert_eq!(detail["name"], "task-manager");
+    assert!(detail["exe"].is_string());
+    assert!(!detail["cmdline"].as_array().unwrap().is_empty());
+
+    let audit_path = control::temp_path("read-audit");
+    let control = Control::new("0123456789abcdef".into(), std::fs::File::create(&audit_path).unwrap());
+    let read = |sub: &str, authorization: Option<&str>, control: Option<&Control>| {
+        let request = ApiRequest {
+            method: "GET".into(),
+            url: format!("/processes/{}/{}", own, sub),
+            authorization: authorization.map(str::to_owned),
+            ..Default::default()
+        };
+        handle(&request, &app, control, None, &MetricsOptions::default())
+    };
+    for sub in ["fds", "maps", "environ"] {
+        assert_eq!(read(sub, None, None).status, 403);
+        assert_eq!(read(sub, Some("Bearer wrong"), Some(&control)).status, 401);
+    }
+    let token = Some("Bearer 0123456789abcdef");
+    let fds = body(&read("fds", token, Some(&control)));
+    assert!(fds.as_array().unwrap().iter().any(|f| f["fd"] == 0));
+    let maps = body(&read("maps", token, Some(&control)));
+    assert!(!maps.as_array().unwrap().is_empty());
+    assert_eq!(read("environ", token, Some(&control)).status, 200);
+    std::fs::remove_file(&audit_path).unwrap();
+}
+
+#[test]
+fn test_control_requires_token_and_audits() {
+    let app = test_app();
+    let audit_path = control::temp_path("audit");
+    let control = Control::new("0123456789abcdef".into(), std::fs::File::create(&audit_path).unwrap());
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let post = |body: &str, authorization: Option<&str>| ApiRequest {
+        method: "POST".into(),
+        url: format!("/processes/{}/signal", child.id()),
+        body: body.into(),
+        authorization: authorization.map(str::to_owned),
+        client: "127.0.0.1:5000".into(),
+        ..Default::default()
+    };
+
+    let disabled = handle(&post(r#"{"signal": "TERM"}"#, None), &app, None, None, &MetricsOptions::default());
+    assert_eq!(disabled.status, 403);
+    let unauthorized = handle(&post(r#"{"signal": "TERM"}"#, Some("Bearer wrong")), &app, Some(&control), None, &MetricsOptions::default());
+    assert_eq!(unauthorized.status, 401);
+    // Without a token the body is never parsed, however large the CPU range it asks for
+    let mut affinity = post(r#"{"cpus": "0-18446744073709551615"}"#, None);
+    affinity.url = format!("/processes/{}/affinity", child.id());
+    assert_eq!(handle(&affinity, &app, Some(&control), None, &MetricsOptions::default()).status, 401);
+    let bad = handle(&post(r#"{"signal": "NOPE"}"#, Some("Bearer 0123456789abcdef")), &app, Some(&control), None, &MetricsOptions::default());
+    assert_eq!(bad.status, 400);
+    let ok = handle(&post(r#"{"signal": "TERM"}"#, Some("Bearer 0123456789abcdef")), &app, Some(&control), None, &MetricsOptions::default());
+    assert_eq!(ok.status, 200, "{}", ok.body);
+    child.wait().unwrap();
+
+    let log = std::fs::read_to_string(&audit_path).unwrap();
+    let entries: Vec<Value> = log.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0]["authorized"], false);
+    assert_eq!(entries[0]["client"], "127.0.0.1:5000");
+    assert_eq!(entries[1]["params"], "");
+    assert!(entries[2]["result"]["Err"].is_string());
+    assert_eq!(entries[3]["result"]["Ok"], Value::Null);
+    assert_eq!(entries[3]["params"], "SIGTERM (15)");
+    assert!(entries[3]["starttime"].is_u64());
+    std::fs::remove_file(&audit_path).unwrap();
+}
+
+#[test]
+fn test_dashboard_is_self_contained() {
+    let response = get("/", &test_app());
+    assert_eq!(response.status, 200);
+    assert!(response.content_type.starts_with("text/html"));
+    assert!(response.body.contains("new EventSource(\"/events\")"));
+    // Must work offline, so nothing may be pulled from another host
+    assert!(!response.body.contains("http://") && !response.body.contains("https://"));
+}
+
+#[test]
+fn test_keys_need_token_and_reach_the_terminal_ui() {
+    let app = test_app();
+    let control = Control::new("0123456789abcdef".into(), std::fs::File::create("/dev/null").unwrap());
+    let (tx, rx) = std::sync::mpsc::channel();
+    let post = |authorization: &str| ApiRequest {
+        method: "POST".into(),
+        url: "/tui/keys".into(),
+        body: r#"{"key": "ArrowDown"}"#.into(),
+        authorization: Some(authorization.into()),
+        ..Default::default()
+    };
+    assert_eq!(handle(&post("Bearer 0123456789abcdef"), &app, None, Some(&tx), &MetricsOptions::default()).status, 403);
+    assert_eq!(handle(&post("Bearer nope"), &app, Some(&control), Some(&tx), &MetricsOptions::default()).status, 401);
+    assert_eq!(handle(&post("Bearer 0123456789abcdef"), &app, Some(&control), None, &MetricsOptions::default()).status, 503);
+    assert_eq!(handle(&post("Bearer 0123456789abcdef"), &app, Some(&control), Some(&tx), &MetricsOptions::default()).status, 200);
+    assert_eq!(rx.try_recv().unwrap().code, crossterm::event::KeyCode::Down);
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_metrics_endpoint() {
+    let app = test_app();
+    let response = get("/metrics", &app);
+    assert_eq!((response.status, response.content_type), (200, metrics::CONTENT_TYPE));
+    assert!(response.body.contains("task_manager_processes 3\n"));
+    assert!(response.body.contains(r#"task_manager_process_resident_memory_bytes{pid="100",name="bash""#));
+
+    let request = ApiRequest {
+        method: "GET".into(),
+        url: "/metrics".into(),
+        ..Default::default()
+    };
+    let top = MetricsOptions {
+        top: Some(1),
+        ..Default::default()
+    };
+    let body = handle(&request, &app, None, None, &top).body;
+    assert_eq!(body.matches("task_manager_process_resident_memory_bytes{").count(), 1);
+}
@@ -1,11 +1,37 @@
-use crate::process::{ProcessInfo, Processes};
-use std::cmp::Reverse;
+use crate::cgroup::{self, CgroupGroup};
+use crate::columns::Column;
+use crate::details::ProcessDetails;
+use crate::exe_hash::SharedHashCache;
+use crate::foreground::{self, Foreground};
+use crate::kill::{self, KillPreview, SignalOutcome, TreeOrder};
+use crate::launcher::{self, RunningCommand};
+use crate::namespaces::{self, NamespaceGroup, Namespaces};
+use crate::oom;
+use crate::process::{self, ProcessInfo, Processes};
+use crate::psi::Pressure;
+use crate::suspend::{self, PausedProcess};
+use crate::suspicious::SuspicionReport;
+use crate::system_stats::SystemStats;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
 #[derive(PartialEq, Debug)]
 pub enum AppState {
     Normal,      // Default, navigating
     Filtering,   // Filterting Processes
     ProcessMenu, // When selecting a process with Enter
+    Details,     // Detail tabs of the selected process
+    Cgroups,     // Processes aggregated per cgroup
+    Namespaces,  // Processes grouped by shared namespace set
+    Report,      // Suspicious process report
+    OomAdjust,   // Entering a new oom_score_adj for the selected process
+    OomVictims,  // Predicted OOM killer victim order
+    KillConfirm, // Preview of the processes about to be signalled
+    KillResults, // Per-process outcome of the last signal
+    ForegroundConfirm, // Asking before handing the terminal to a job
+    RunPrompt,   // Entering a command to launch
+    Output,      // Captured stdout/stderr of launched commands
 }
 
 #[derive(PartialEq)]
@@ -18,8 +44,77 @@ pub struct App {
     pub processes: Vec<ProcessInfo>,
     pub selected_proc: usize,
     pub process_order: ProcessOrder,
+    pub sort_column: Column,
+    pub columns: Vec<Column>,
+    pub io_only: bool, // iotop mode, hides processes without disk I/O
+    pub elevated_only: bool, // audit preset, only processes with effective capabilities
     pub filter_string: String,
     pub state: AppState,
+    pub marked_pid: Option<u32>, // Second process for comparisons, e.g. environment diff
+    pub details: Option<ProcessDetails>,
+    pub system: SystemStats,
+    pub show_system_panel: bool,
+    pub cgroup_pressure: Option<(String, Pressure)>, // PSI of the selected process's cgroup
+    pub cgroup_groups: Vec<CgroupGroup>,
+    pub selected_group: usize, // Selection in the cgroup and namespace views
+    pub cgroup_filter: Option<String>, // Only show processes of this cgroup path
+    pub namespace_groups: Vec<NamespaceGroup>,
+    pub namespace_filter: Option<Namespaces>, // Only show processes sharing these namespaces
+    pub report: Option<SuspicionReport>,
+    pub report_pending: Option<Receiver<SuspicionReport>>, // Report still being built in the background
+    pub hash_cache: SharedHashCache,
+    pub oom_adj_input: String,
+    pub status_message: Option<Result<String, String>>, // Outcome of the last action, shown in the mode line
+    pub paused: Vec<PausedProcess>, // Stopped or frozen from here, resumed on exit
+    pub kill_order: TreeOrder,
+    pub kill_preview: Option<KillPreview>,
+    pub kill_results: Vec<SignalOutcome>,
+    pub foreground_target: Option<(u32, i32)>, // pid and process group waiting for confirmation
+    pub run_input: String,
+    pub launched: Vec<RunningCommand>,
+    pub followed_pid: Option<u32>, // Keeps this process selected across refreshes
+    pub output_scroll: Option<usize>, // None sticks to the newest output line
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            all_processes: vec![],
+            processes: vec![],
+            selected_proc: 0,
+            process_order: ProcessOrder::Ascending,
+            sort_column: Column::Pid,
+            columns: Column::ALL.to_vec(),
+            io_only: false,
+            elevated_only: false,
+            filter_string: String::new(),
+            state: AppState::Normal,
+            marked_pid: None,
+            details: None,
+            system: SystemStats::default(),
+            show_system_panel: false,
+            cgroup_pressure: None,
+            cgroup_groups: vec![],
+            selected_group: 0,
+            cgroup_filter: None,
+            namespace_groups: vec![],
+            namespace_filter: None,
+            report: None,
+            report_pending: None,
+            hash_cache: SharedHashCache::default(),
+            oom_adj_input: String::new(),
+            status_message: None,
+            paused: vec![],
+            kill_order: TreeOrder::ChildrenFirst,
+            kill_preview: None,
+            kill_results: vec![],
+            foreground_target: None,
+            run_input: String::new(),
+            launched: vec![],
+            followed_pid: None,
+            output_scroll: None,
+        }
+    }
 }
 
 impl App {
@@ -29,53 +124,461 @@ impl App {
         Self {
             all_processes,
             processes,
-            selected_proc: 0,
-            process_order: ProcessOrder::Ascending,
-            filter_string: String::new(),
-            state: AppState::Normal,
+            ..Default::default()
+        }
+    }
+
+    pub fn selected_process(&self) -> Option<&ProcessInfo> {
+        self.processes.get(self.selected_proc)
+    }
+
+    pub fn refresh_cgroup_pressure(&mut self) {
+        self.cgroup_pressure = self.selected_process().and_then(|proc| {
+            let path = cgroup::read_cgroup_path(proc.pid)?;
+            let pressure = Pressure::read_cgroup(&cgroup::cgroup_dir(&path))?;
+            Some((path, pressure))
+        });
+    }
+
+    pub fn toggle_mark_selected(&mut self) {
+        let pid = self.selected_process().map(|p| p.pid);
+        self.marked_pid = if self.marked_pid == pid { None } else { pid };
+    }
+
+    pub fn open_details(&mut self) {
+        if let Some(proc) = self.selected_process() {
+            self.details = Some(ProcessDetails::load(proc.pid, proc.name.clone()));
+            self.state = AppState::Details;
         }
     }
 
+    pub fn close_details(&mut self) {
+        self.details = None;
+        self.state = AppState::Normal;
+    }
+
     pub fn sort_ascending(&mut self) {
         self.process_order = ProcessOrder::Ascending;
-        self.processes.sort_by_key(|p| p.pid);
+        self.sort_processes();
     }
 
     pub fn sort_descending(&mut self) {
         self.process_order = ProcessOrder::Descending;
-        self.processes.sort_by_key(|p| Reverse(p.pid));
+        self.sort_processes();
     }
-    pub fn apply_filter(&mut self) {
-        self.processes = if self.filter_string.is_empty() {
-            self.all_processes.clone()
+
+    pub fn cycle_sort_column(&mut self) {
+        self.sort_column = self.sort_column.next();
+        self.sort_processes();
+    }
+
+    pub fn toggle_io_only(&mut self) {
+        self.io_only = !self.io_only;
+        self.apply_filter();
+    }
+
+    pub fn toggle_elevated_only(&mut self) {
+        self.elevated_only = !self.elevated_only;
+        self.apply_filter();
+    }
+
+    fn sort_processes(&mut self) {
+        let column = self.sort_column;
+        if self.process_order == ProcessOrder::Ascending {
+            self.processes.sort_by(|a, b| column.compare(a, b));
         } else {
-            let proc: Vec<ProcessInfo> = self.all_processes
-                .iter()
-                .filter(|p| p.name.to_lowercase().contains(&self.filter_string))
-                .cloned()
-                .collect();
-
-            if self.selected_proc >= proc.len() {
-                self.selected_proc = 0;
-            };
-            proc
+            self.processes.sort_by(|a, b| column.compare(b, a));
+        }
+    }
 
-        };
+    pub fn apply_filter(&mut self) {
+        self.processes = self
+            .all_processes
+            .iter()
+            .filter(|p| p.name.to_lowercase().contains(&self.filter_string))
+            .filter(|p| !self.io_only || p.has_disk_io())
+            .filter(|p| !self.elevated_only || p.has_elevated_capabilities())
+            .filter(|p| self.cgroup_filter.is_none() || p.cgroup == self.cgroup_filter)
+            .filter(|p| self.namespace_filter.is_none() || p.namespaces == self.namespace_filter)
+            .cloned()
+            .collect();
+
+        if self.selected_proc >= self.processes.len() {
+            self.selected_proc = 0;
+        }
+        self.sort_processes();
+        if let Some(pid) = self.followed_pid
+            && let Some(index) = self.processes.iter().position(|p| p.pid == pid)
+        {
+            self.selected_proc = index;
+        }
     }
 
+    // Shows the effect of an action right away. CPU usage, I/O rates and suspicion flags need
+    // two samples, so they are kept from the collector's last snapshot until the next one arrives.
     pub fn reload_processes(&mut self) {
-        let all_processes = Processes::fetch_process_list().unwrap_or_default();
-        if self.selected_proc > all_processes.len(){
-            self.selected_proc = all_processes.len() - 1;
+        let mut all_processes = Processes::fetch_process_list().unwrap_or_default();
+        let previous: HashMap<u32, &ProcessInfo> = self.all_processes.iter().map(|p| (p.pid, p)).collect();
+        for process in &mut all_processes {
+            if let Some(old) = previous.get(&process.pid).filter(|old| **old == process) {
+                process.cpu_percent = old.cpu_percent;
+                process.read_rate = old.read_rate;
+                process.write_rate = old.write_rate;
+                process.flags = old.flags.clone();
+            }
         }
+        self.set_processes(all_processes);
+    }
+
+    pub fn set_processes(&mut self, all_processes: Vec<ProcessInfo>) {
+        self.poll_launched();
         self.all_processes = all_processes;
         self.apply_filter();
-        if self.process_order == ProcessOrder::Ascending {
-            self.sort_ascending();
-        } else {
-            self.sort_descending();
+        match self.state {
+            AppState::Cgroups => self.refresh_cgroup_groups(),
+            AppState::Namespaces => self.refresh_namespace_groups(),
+            _ => {}
         }
     }
+
+    pub fn open_cgroup_view(&mut self) {
+        self.cgroup_filter = None;
+        self.apply_filter();
+        self.selected_group = 0;
+        self.refresh_cgroup_groups();
+        self.state = AppState::Cgroups;
+    }
+
+    pub fn open_namespace_view(&mut self) {
+        self.namespace_filter = None;
+        self.apply_filter();
+        self.selected_group = 0;
+        self.refresh_namespace_groups();
+        self.state = AppState::Namespaces;
+    }
+
+    // Probing every pid takes a while, so the report is built on its own thread
+    pub fn open_report(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let processes = self.all_processes.clone();
+        thread::spawn(move || {
+            let _ = tx.send(SuspicionReport::build(&processes));
+        });
+        self.report = None;
+        self.report_pending = Some(rx);
+        self.selected_group = 0;
+        self.state = AppState::Report;
+    }
+
+    pub fn start_oom_adjust(&mut self) {
+        if let Some(proc) = self.selected_process() {
+            self.oom_adj_input = proc.oom_score_adj.map(|adj| adj.to_string()).unwrap_or_default();
+            self.state = AppState::OomAdjust;
+        }
+    }
+
+    pub fn apply_oom_adjust(&mut self) {
+        self.state = AppState::Normal;
+        let Some(pid) = self.selected_process().map(|p| p.pid) else {
+            return;
+        };
+        let result = oom::parse_oom_score_adj(&self.oom_adj_input).and_then(|value| {
+            oom::set_oom_score_adj(pid, value)
+                .map(|_| format!("PID {}: oom_score_adj set to {}", pid, value))
+                .map_err(|e| format!("PID {}: {}", pid, e))
+        });
+        if result.is_ok() {
+            self.reload_processes();
+        }
+        self.status_message = Some(result);
+    }
+
+    pub fn is_paused(&self, pid: u32) -> bool {
+        self.paused.iter().any(|p| p.pid == pid)
+    }
+
+    pub fn stop_selected(&mut self, tree: bool) {
+        let Some(pid) = self.selected_process().map(|p| p.pid) else {
+            return;
+        };
+        let (paused, errors) = suspend::stop(&self.all_processes, pid, tree);
+        let stopped = paused.len();
+        for entry in paused {
+            if !self.is_paused(entry.pid) {
+                self.paused.push(entry);
+            }
+        }
+        self.finish_action(format!("stopped {} process(es)", stopped), errors);
+    }
+
+    pub fn freeze_selected(&mut self) {
+        let Some(pid) = self.selected_process().map(|p| p.pid) else {
+            return;
+        };
+        // A second entry would thaw the cgroup or SIGCONT the process twice on resume
+        if self.is_paused(pid) {
+            self.status_message = Some(Err(format!("PID {} is already paused, resume it first", pid)));
+            self.state = AppState::Normal;
+            return;
+        }
+        let result = suspend::freeze(&self.all_processes, pid);
+        self.status_message = Some(result.map(|entry| {
+            self.paused.push(entry);
+            format!("froze the cgroup of PID {}", pid)
+        }));
+        self.state = AppState::Normal;
+    }
+
+    // Also continues processes stopped by someone else, e.g. with Ctrl+Z
+    pub fn resume_selected(&mut self, tree: bool) {
+        let Some(pid) = self.selected_process().map(|p| p.pid) else {
+            return;
+        };
+        let mut pids = vec![pid];
+        if tree {
+            pids.extend(process::descendants(&self.all_processes, pid));
+        }
+        let mut resumed = 0;
+        let mut errors = vec![];
+        for pid in pids {
+            let result = match self.paused.iter().position(|p| p.pid == pid) {
+                Some(index) => suspend::resume(&self.paused.remove(index)),
+                None if self.all_processes.iter().any(|p| p.pid == pid && p.is_stopped()) => {
+                    suspend::send_signal(pid, libc::SIGCONT)
+                }
+                None => continue,
+            };
+            match result {
+                Ok(()) => resumed += 1,
+                Err(e) => errors.push(format!("PID {}: {}", pid, e)),
+            }
+        }
+        self.finish_action(format!("resumed {} process(es)", resumed), errors);
+    }
+
+    pub fn resume_all_paused(&mut self) -> Vec<String> {
+        let errors: Vec<String> = self
+            .paused
+            .drain(..)
+            .filter_map(|p| suspend::resume(&p).err().map(|e| format!("PID {} ({}): {}", p.pid, p.name, e)))
+            .collect();
+        errors
+    }
+
+    pub fn resume_all(&mut self) {
+        let count = self.paused.len();
+        let errors = self.resume_all_paused();
+        self.finish_action(format!("resumed {} paused process(es)", count - errors.len()), errors);
+    }
+
+    fn finish_action(&mut self, done: String, errors: Vec<String>) {
+        self.status_message = Some(match errors.as_slice() {
+            [] => Ok(done),
+            [first, ..] => Err(format!("{}, {} failed: {}", done, errors.len(), first)),
+        });
+        self.state = AppState::Normal;
+        self.reload_processes();
+    }
+
+    // SIGKILL by default, as the menu's k always sent; s in the preview picks another
+    pub fn preview_kill_selected(&mut self) {
+        let Some(proc) = self.selected_process() else {
+            return;
+        };
+        self.kill_preview = Some(KillPreview {
+            description: format!("PID {}", proc.pid),
+            root: None,
+            targets: vec![kill::KillTarget::of(proc)],
+            signal: libc::SIGKILL,
+        });
+        self.selected_group = 0;
+        self.state = AppState::KillConfirm;
+    }
+
+    pub fn preview_kill_tree(&mut self) {
+        let Some(pid) = self.selected_process().map(|p| p.pid) else {
+            return;
+        };
+        self.kill_preview = Some(KillPreview {
+            description: format!("PID {} and its descendants", pid),
+            root: Some(pid),
+            targets: kill::tree_targets(&self.all_processes, pid, self.kill_order),
+            signal: libc::SIGTERM,
+        });
+        self.selected_group = 0;
+        self.state = AppState::KillConfirm;
+    }
+
+    // pkill: everything the current filter shows
+    pub fn preview_kill_matching(&mut self) {
+        if self.filter_string.is_empty() {
+            self.status_message = Some(Err("set a filter with / first, it selects the processes to kill".into()));
+            return;
+        }
+        self.kill_preview = Some(KillPreview {
+            description: format!("processes matching '{}'", self.filter_string),
+            root: None,
+            targets: kill::matching_targets(&self.processes),
+            signal: libc::SIGTERM,
+        });
+        self.selected_group = 0;
+        self.state = AppState::KillConfirm;
+    }
+
+    pub fn toggle_kill_order(&mut self) {
+        self.kill_order = self.kill_order.toggle();
+        if let Some(preview) = self.kill_preview.as_mut()
+            && let Some(root) = preview.root
+        {
+            preview.targets = kill::tree_targets(&self.all_processes, root, self.kill_order);
+        }
+    }
+
+    pub fn cycle_kill_signal(&mut self) {
+        if let Some(preview) = self.kill_preview.as_mut() {
+            preview.signal = kill::next_signal(preview.signal);
+        }
+    }
+
+    pub fn confirm_kill(&mut self) {
+        let Some(preview) = self.kill_preview.take() else {
+            return;
+        };
+        self.kill_results = kill::signal_all(&preview.targets, preview.signal);
+        let failed = self.kill_results.iter().filter(|o| o.result.is_err()).count();
+        let message = format!(
+            "sent {} to {} process(es), {} failed",
+            kill::signal_name(preview.signal),
+            self.kill_results.len() - failed,
+            failed
+        );
+        self.status_message = Some(if failed == 0 { Ok(message) } else { Err(message) });
+        self.selected_group = 0;
+        self.state = AppState::KillResults;
+        self.reload_processes();
+    }
+
+    pub fn request_foreground(&mut self) {
+        let Some(proc) = self.selected_process() else {
+            return;
+        };
+        let (pid, stat, stopped) = (proc.pid, proc.stat.clone(), proc.is_stopped());
+        let own = process::read_stat(std::process::id()).unwrap_or_default();
+        self.state = AppState::Normal;
+        self.status_message = Some(match foreground::plan(&stat, &own) {
+            Foreground::NoTty => Err(format!("PID {} has no controlling terminal", pid)),
+            Foreground::SameTerminal => {
+                self.foreground_target = Some((pid, stat.pgrp));
+                self.state = AppState::ForegroundConfirm;
+                return;
+            }
+            Foreground::OtherTerminal { tty, is_foreground } => {
+                let continued = if stopped { foreground::continue_group(stat.pgrp).map(|_| true) } else { Ok(false) };
+                match continued {
+                    Err(e) => Err(format!("PID {}: cannot continue job: {}", pid, e)),
+                    Ok(_) if is_foreground => Ok(format!("PID {} is the foreground job of {}", pid, tty)),
+                    Ok(continued) => Err(format!(
+                        "PID {}{} runs on {}, not this terminal: only the shell there can make it the foreground job (fg)",
+                        pid,
+                        if continued { " was continued, but it" } else { "" },
+                        tty
+                    )),
+                }
+            }
+        });
+    }
+
+    pub fn start_run_prompt(&mut self) {
+        self.run_input.clear();
+        self.state = AppState::RunPrompt;
+    }
+
+    // Filters are cleared so the new process is visible and can be selected
+    pub fn launch(&mut self) {
+        self.state = AppState::Normal;
+        let input = std::mem::take(&mut self.run_input);
+        let result = launcher::parse_command_line(&input)
+            .and_then(|spec| launcher::spawn(&spec, &input).map_err(|e| format!("{}: {}", spec.program, e)));
+        match result {
+            Ok(running) => {
+                self.status_message = Some(Ok(format!("started PID {} (w: show output)", running.pid)));
+                self.followed_pid = Some(running.pid);
+                self.launched.push(running);
+                self.filter_string.clear();
+                self.io_only = false;
+                self.elevated_only = false;
+                self.cgroup_filter = None;
+                self.namespace_filter = None;
+                self.reload_processes();
+            }
+            Err(e) => self.status_message = Some(Err(e)),
+        }
+    }
+
+    pub fn poll_launched(&mut self) {
+        for running in self.launched.iter_mut() {
+            running.poll();
+            if running.status.is_some() && self.followed_pid == Some(running.pid) {
+                self.followed_pid = None;
+            }
+        }
+    }
+
+    pub fn open_output_view(&mut self) {
+        if self.launched.is_empty() {
+            self.status_message = Some(Err("nothing launched yet, start a command with c".into()));
+            return;
+        }
+        self.output_scroll = None;
+        self.state = AppState::Output;
+    }
+
+    pub fn open_oom_view(&mut self) {
+        self.selected_group = 0;
+        self.state = AppState::OomVictims;
+    }
+
+    pub fn poll_report(&mut self) {
+        if let Some(rx) = &self.report_pending
+            && let Ok(report) = rx.try_recv()
+        {
+            self.report = Some(report);
+            self.report_pending = None;
+        }
+    }
+
+    pub fn refresh_namespace_groups(&mut self) {
+        let reference = namespaces::reference_namespaces();
+        self.namespace_groups = namespaces::group_by_namespaces(&self.all_processes, &reference);
+        if self.selected_group >= self.namespace_groups.len() {
+            self.selected_group = self.namespace_groups.len().saturating_sub(1);
+        }
+    }
+
+    pub fn filter_selected_namespaces(&mut self) {
+        self.namespace_filter = self
+            .namespace_groups
+            .get(self.selected_group)
+            .map(|g| g.namespaces.clone());
+        self.selected_proc = 0;
+        self.apply_filter();
+        self.state = AppState::Normal;
+    }
+
+    pub fn refresh_cgroup_groups(&mut self) {
+        self.cgroup_groups = cgroup::group_by_cgroup(&self.all_processes, cgroup::read_cgroup_stats);
+        if self.selected_group >= self.cgroup_groups.len() {
+            self.selected_group = self.cgroup_groups.len().saturating_sub(1);
+        }
+    }
+
+    // Back to the process list, showing only the members of the selected cgroup
+    pub fn filter_selected_cgroup(&mut self) {
+        self.cgroup_filter = self.cgroup_groups.get(self.selected_group).map(|g| g.path.clone());
+        self.selected_proc = 0;
+        self.apply_filter();
+        self.state = AppState::Normal;
+    }
 }
 
 #[test]
@@ -96,9 +599,7 @@ fn test_sort_ascending_by_pid() {
             ProcessInfo::new(1, "a".into(), 0, 0),
             ProcessInfo::new(2, "b".into(), 0, 0),
         ],
-        selected_proc: 0,
-        filter_string: String::new(),
-        state: AppState::Normal,
+        ..Default::default()
     };
 
     app.sort_ascending();
@@ -116,9 +617,7 @@ fn test_sort_descending_by_pid() {
             ProcessInfo::new(3, "c".into(), 0, 0),
             ProcessInfo::new(2, "b".into(), 0, 0),
         ],
-        selected_proc: 0,
-        filter_string: String::new(),
-        state: AppState::Normal,
+        ..Default::default()
     };
 
     app.sort_descending();
@@ -138,9 +637,9 @@ fn test_apply_filter_matches_lowercase() {
     let mut app = App {
         all_processes: all.clone(),
         processes: all.clone(),
-        selected_proc: 0,
         filter_string: "fire".into(),
         state: AppState::Filtering,
+        ..Default::default()
     };
 
     app.apply_filter();
@@ -149,3 +648,282 @@ fn test_apply_filter_matches_lowercase() {
     assert_eq!(app.processes[0].name, "firefox");
 }
 
+#[test]
+fn test_toggle_mark_selected() {
+    let mut app = App {
+        processes: vec![ProcessInfo::new(7, "a".into(), 0, 0)],
+        ..Default::default()
+    };
+
+    app.toggle_mark_selected();
+    assert_eq!(app.marked_pid, Some(7));
+    app.toggle_mark_selected();
+    assert_eq!(app.marked_pid, None);
+}
+
+#[test]
+fn test_io_only_hides_idle_and_unknown_processes() {
+    let mut busy = ProcessInfo::new(1, "dd".into(), 0, 0);
+    busy.write_rate = Some(4096.0);
+    busy.read_rate = Some(0.0);
+    let mut idle = ProcessInfo::new(2, "sleep".into(), 0, 0);
+    idle.read_rate = Some(0.0);
+    idle.write_rate = Some(0.0);
+    let unknown = ProcessInfo::new(3, "sshd".into(), 0, 0);
+
+    let mut app = App {
+        all_processes: vec![busy, idle, unknown],
+        ..Default::default()
+    };
+    app.toggle_io_only();
+
+    let pids: Vec<u32> = app.processes.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec![1]);
+}
+
+#[test]
+fn test_sort_descending_by_write_rate() {
+    let mut fast = ProcessInfo::new(1, "fast".into(), 0, 0);
+    fast.write_rate = Some(100.0);
+    let mut slow = ProcessInfo::new(2, "slow".into(), 0, 0);
+    slow.write_rate = Some(1.0);
+    let unknown = ProcessInfo::new(3, "unknown".into(), 0, 0);
+
+    let mut app = App {
+        processes: vec![slow, unknown, fast],
+        sort_column: Column::WriteRate,
+        ..Default::default()
+    };
+    app.sort_descending();
+
+    let pids: Vec<u32> = app.processes.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_filter_selected_cgroup() {
+    let mut a = ProcessInfo::new(1, "nginx".into(), 0, 0);
+    a.cgroup = Some("/system.slice/nginx.service".into());
+    let mut b = ProcessInfo::new(2, "sshd".into(), 0, 0);
+    b.cgroup = Some("/system.slice/sshd.service".into());
+
+    let mut app = App {
+        all_processes: vec![a, b],
+        ..Default::default()
+    };
+    app.open_cgroup_view();
+    assert_eq!(app.state, AppState::Cgroups);
+    app.selected_group = app
+        .cgroup_groups
+        .iter()
+        .position(|g| g.label == "sshd.service")
+        .unwrap();
+    app.filter_selected_cgroup();
+
+    assert_eq!(app.state, AppState::Normal);
+    let pids: Vec<u32> = app.processes.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec![2]);
+}
+
+#[test]
+fn test_filter_selected_namespaces() {
+    let container = Namespaces {
+        pid: Some(2),
+        net: Some(2),
+        ..Default::default()
+    };
+    let mut a = ProcessInfo::new(1, "init".into(), 0, 0);
+    a.namespaces = Some(Namespaces {
+        pid: Some(1),
+        net: Some(1),
+        ..Default::default()
+    });
+    let mut b = ProcessInfo::new(2, "nginx".into(), 0, 0);
+    b.namespaces = Some(container.clone());
+
+    let mut app = App {
+        all_processes: vec![a, b],
+        ..Default::default()
+    };
+    app.open_namespace_view();
+    app.selected_group = app
+        .namespace_groups
+        .iter()
+        .position(|g| g.namespaces == container)
+        .unwrap();
+    app.filter_selected_namespaces();
+
+    let pids: Vec<u32> = app.processes.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec![2]);
+}
+
+#[test]
+fn test_elevated_only_filter() {
+    let mut privileged = ProcessInfo::new(1, "nginx".into(), 0, 0);
+    privileged.cap_eff = Some(0x400);
+    let mut unprivileged = ProcessInfo::new(2, "bash".into(), 0, 0);
+    unprivileged.cap_eff = Some(0);
+    let unknown = ProcessInfo::new(3, "gone".into(), 0, 0);
+
+    let mut app = App {
+        all_processes: vec![privileged, unprivileged, unknown],
+        ..Default::default()
+    };
+    app.toggle_elevated_only();
+
+    let pids: Vec<u32> = app.processes.iter().map(|p| p.pid).collect();
+    assert_eq!(pids, vec![1]);
+}
+
+#[test]
+fn test_oom_adjust_validates_range() {
+    let mut proc = ProcessInfo::new(1, "init".into(), 0, 0);
+    proc.oom_score_adj = Some(0);
+    let mut app = App {
+        processes: vec![proc],
+        ..Default::default()
+    };
+    app.start_oom_adjust();
+    assert_eq!(app.state, AppState::OomAdjust);
+    assert_eq!(app.oom_adj_input, "0");
+
+    app.oom_adj_input = "2000".into();
+    app.apply_oom_adjust();
+    assert_eq!(app.state, AppState::Normal);
+    assert!(matches!(&app.status_message, Some(Err(e)) if e.contains("-1000")));
+}
+
+#[test]
+fn test_stop_and_resume_selected_tree() {
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let pid = child.id();
+    let mut proc = ProcessInfo::new(pid, "sleep".into(), 0, 0);
+    proc.stat = process::read_stat(pid).unwrap();
+    let mut app = App {
+        all_processes: vec![proc.clone()],
+        processes: vec![proc.clone()],
+        ..Default::default()
+    };
+
+    app.stop_selected(true);
+    assert!(app.is_paused(pid));
+    assert!(matches!(&app.status_message, Some(Ok(m)) if m == "stopped 1 process(es)"));
+
+    (app.processes, app.all_processes, app.selected_proc) = (vec![proc.clone()], vec![proc], 0);
+    app.freeze_selected();
+    assert!(matches!(&app.status_message, Some(Err(e)) if e.contains("already paused")));
+    assert_eq!(app.paused.len(), 1);
+
+    assert!(app.resume_all_paused().is_empty());
+    assert!(app.paused.is_empty());
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn test_kill_matching_needs_filter() {
+    let mut app = App {
+        processes: vec![ProcessInfo::new(1, "init".into(), 0, 0)],
+        ..Default::default()
+    };
+    app.preview_kill_matching();
+    assert_eq!(app.state, AppState::Normal);
+    assert!(app.kill_preview.is_none());
+
+    app.filter_string = "init".into();
+    app.preview_kill_matching();
+    assert_eq!(app.state, AppState::KillConfirm);
+    assert_eq!(app.kill_preview.as_ref().unwrap().targets[0].name, "init");
+}
+
+#[test]
+fn test_kill_selected_needs_a_process() {
+    let mut app = App::default();
+    app.preview_kill_selected();
+    assert_eq!(app.state, AppState::Normal);
+    assert!(app.kill_preview.is_none());
+
+    app.processes = vec![ProcessInfo::new(7, "worker".into(), 0, 0)];
+    app.preview_kill_selected();
+    let preview = app.kill_preview.as_ref().unwrap();
+    assert_eq!((preview.targets.len(), preview.targets[0].pid, preview.signal), (1, 7, libc::SIGKILL));
+    assert_eq!(app.state, AppState::KillConfirm);
+}
+
+#[test]
+fn test_kill_tree_order_toggles_preview() {
+    let mut child = ProcessInfo::new(2, "worker".into(), 0, 0);
+    child.stat.ppid = 1;
+    let parent = ProcessInfo::new(1, "master".into(), 0, 0);
+    let mut app = App {
+        all_processes: vec![parent.clone(), child],
+        processes: vec![parent],
+        ..Default::default()
+    };
+    app.preview_kill_tree();
+    let pids = |app: &App| -> Vec<u32> { app.kill_preview.as_ref().unwrap().targets.iter().map(|t| t.pid).collect() };
+    assert_eq!(pids(&app), vec![2, 1]);
+    app.toggle_kill_order();
+    assert_eq!(pids(&app), vec![1, 2]);
+}
+
+#[test]
+fn test_foreground_needs_tty() {
+    let mut app = App {
+        processes: vec![ProcessInfo::new(2, "kthreadd".into(), 0, 0)],
+        state: AppState::ProcessMenu,
+        ..Default::default()
+    };
+    app.request_foreground();
+    assert_eq!(app.state, AppState::Normal);
+    assert!(matches!(&app.status_message, Some(Err(e)) if e.contains("no controlling terminal")));
+}
+
+#[test]
+fn test_launch_selects_and_follows() {
+    let mut app = App {
+        filter_string: "nothing matches this".into(),
+        ..Default::default()
+    };
+    app.start_run_prompt();
+    app.run_input = "sleep 5".into();
+    app.launch();
+    let pid = app.followed_pid.unwrap();
+    assert!(app.filter_string.is_empty());
+    assert_eq!(app.selected_process().map(|p| p.pid), Some(pid));
+
+    crate::suspend::send_signal(pid, libc::SIGKILL).unwrap();
+    while app.launched[0].status.is_none() {
+        app.poll_launched();
+    }
+    assert_eq!(app.followed_pid, None);
+}
+
+#[test]
+fn test_launch_reports_errors() {
+    let mut app = App {
+        run_input: "/nonexistent/program".into(),
+        ..Default::default()
+    };
+    app.launch();
+    assert!(matches!(&app.status_message, Some(Err(e)) if e.starts_with("/nonexistent/program")));
+    assert!(app.launched.is_empty());
+}
+
+#[test]
+fn test_reload_keeps_collector_fields() {
+    use crate::suspicious::SuspicionFlag;
+    let mut app = App::new();
+    let mut processes = Processes::fetch_process_list().unwrap();
+    let own = processes.iter_mut().find(|p| p.pid == std::process::id()).unwrap();
+    own.cpu_percent = 12.5;
+    own.read_rate = Some(4096.0);
+    own.flags = vec![SuspicionFlag::LdPreload];
+    app.set_processes(processes);
+
+    app.reload_processes();
+    let own = app.all_processes.iter().find(|p| p.pid == std::process::id()).unwrap();
+    assert_eq!(own.cpu_percent, 12.5);
+    assert_eq!(own.read_rate, Some(4096.0));
+    assert_eq!(own.flags, vec![SuspicionFlag::LdPreload]);
+}
@@ -0,0 +1,296 @@
+use crate::api::{self, ListQuery};
+use crate::columns::Column;
+use crate::control;
+use crate::cpu_tracker::CpuTracker;
+use crate::io_tracker::IoTracker;
+use crate::process::{ProcessInfo, Processes};
+#[cfg(test)]
+use crate::profiler::strings;
+use crate::suspicious::SuspicionScanner;
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "usage: task-manager batch [--format table|csv|json|ndjson] [--columns KEY[,KEY...]]
+                          [--sort KEY [--desc]] [--filter TEXT] [--limit N] [-n ITERATIONS] [-d SECONDS]";
+
+const DEFAULT_COLUMNS: [Column; 8] = [
+    Column::Pid,
+    Column::Name,
+    Column::State,
+    Column::CpuPercent,
+    Column::Memory,
+    Column::ReadRate,
+    Column::WriteRate,
+    Column::Cgroup,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,   // one document per iteration
+    Ndjson, // one line per process
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOptions {
+    pub format: Format,
+    pub columns: Vec<Column>,
+    pub query: ListQuery, // filter, sort and limit as in GET /processes
+    pub iterations: Option<u64>, // None repeats until interrupted
+    pub delay: Duration,
+}
+
+pub fn parse_batch_args(args: &[String]) -> Result<BatchOptions, String> {
+    let mut options = BatchOptions {
+        format: Format::Table,
+        columns: DEFAULT_COLUMNS.to_vec(),
+        query: ListQuery {
+            filter: String::new(),
+            sort: Column::Pid,
+            descending: false,
+            limit: None,
+            fields: None,
+        },
+        iterations: Some(1),
+        delay: Duration::from_secs(1),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-f" | "--format" => {
+                options.format = match value()?.as_str() {
+                    "table" => Format::Table,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    "ndjson" => Format::Ndjson,
+                    other => return Err(format!("unknown format '{}'", other)),
+                }
+            }
+            "-o" | "--columns" => {
+                options.columns = value()?
+                    .split(',')
+                    .filter(|key| !key.is_empty())
+                    .map(|key| Column::from_key(key).ok_or_else(|| format!("unknown column '{}'", key)))
+                    .collect::<Result<_, _>>()?;
+                if options.columns.is_empty() {
+                    return Err("--columns needs at least one column".into());
+                }
+            }
+            "-s" | "--sort" => {
+                let key = value()?;
+                options.query.sort = Column::from_key(&key).ok_or_else(|| format!("unknown sort key '{}'", key))?;
+            }
+            "--desc" => options.query.descending = true,
+            "--filter" => options.query.filter = value()?.to_lowercase(),
+            "--limit" => {
+                let limit = value()?;
+                options.query.limit = Some(limit.parse().map_err(|_| format!("invalid limit '{}'", limit))?);
+            }
+            // 0 keeps going until interrupted, like top without -n
+            "-n" => {
+                let count = value()?;
+                let count: u64 = count.parse().map_err(|_| format!("invalid iteration count '{}'", count))?;
+                options.iterations = (count > 0).then_some(count);
+            }
+            "-d" => {
+                let delay = value()?;
+                options.delay = delay
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| *secs > 0.0 && secs.is_finite())
+                    .map(Duration::from_secs_f64)
+                    .ok_or_else(|| format!("invalid delay '{}', expected a positive number of seconds", delay))?;
+            }
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+    }
+    Ok(options)
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(csv_field).collect::<Vec<_>>().join(" "),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn process_object(time: Option<&str>, process: &ProcessInfo, columns: &[Column]) -> Value {
+    let mut object = Map::new();
+    if let Some(time) = time {
+        object.insert("time".into(), time.into());
+    }
+    for column in columns {
+        object.insert(column.key().into(), column.value(process));
+    }
+    Value::Object(object)
+}
+
+// Columns are as wide as their widest cell, the last one is not padded
+fn format_table(processes: &[&ProcessInfo], columns: &[Column]) -> String {
+    let rows: Vec<Vec<String>> = std::iter::once(columns.iter().map(|c| c.header().to_owned()).collect())
+        .chain(processes.iter().map(|p| columns.iter().map(|c| c.cell(p)).collect()))
+        .collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| rows.iter().map(|row| row[i].chars().count()).max().unwrap_or(0))
+        .collect();
+    let mut text = String::new();
+    for row in rows {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell)).collect();
+        text.push_str(cells.join(" ").trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+// One iteration's output; the CSV header is only written before the first
+pub fn render(format: Format, columns: &[Column], time: &str, processes: &[&ProcessInfo], first: bool) -> String {
+    match format {
+        Format::Table if first => format_table(processes, columns),
+        Format::Table => format!("\n{}", format_table(processes, columns)),
+        Format::Csv => {
+            let mut text = String::new();
+            if first {
+                let keys: Vec<&str> = std::iter::once("time").chain(columns.iter().map(|c| c.key())).collect();
+                text.push_str(&keys.join(","));
+                text.push('\n');
+            }
+            for process in processes {
+                let fields: Vec<String> = std::iter::once(csv_field(&time.into()))
+                    .chain(columns.iter().map(|c| csv_field(&c.value(process))))
+                    .collect();
+                text.push_str(&fields.join(","));
+                text.push('\n');
+            }
+            text
+        }
+        Format::Json => {
+            let processes: Vec<Value> = processes.iter().map(|p| process_object(None, p, columns)).collect();
+            let document = serde_json::json!({ "time": time, "processes": processes });
+            format!("{}\n", serde_json::to_string_pretty(&document).unwrap_or_default())
+        }
+        Format::Ndjson => processes
+            .iter()
+            .map(|p| format!("{}\n", process_object(Some(time), p, columns)))
+            .collect(),
+    }
+}
+
+// CPU% and I/O rates of every sample cover the delay before it, so even the first waits once
+pub fn run(options: &BatchOptions) -> io::Result<()> {
+    let mut cpu_tracker = CpuTracker::new();
+    let mut io_tracker = IoTracker::new();
+    let mut suspicion_scanner = SuspicionScanner::new();
+    let mut baseline = Processes::fetch_process_list()?;
+    cpu_tracker.update_process_cpu(&mut baseline);
+    io_tracker.update_process_io(&mut baseline);
+
+    let mut stdout = io::stdout();
+    let mut iteration = 0;
+    while options.iterations.is_none_or(|n| iteration < n) {
+        thread::sleep(options.delay);
+        let mut processes = Processes::fetch_process_list()?;
+        cpu_tracker.update_process_cpu(&mut processes);
+        io_tracker.update_process_io(&mut processes);
+        suspicion_scanner.update_process_flags(&mut processes);
+        let selected = api::select_processes(&processes, &options.query);
+        let text = render(options.format, &options.columns, &control::now_utc(), &selected, iteration == 0);
+        // A closed pipe (e.g. piped into head) ends the output quietly
+        if stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+        iteration += 1;
+    }
+    Ok(())
+}
+
+pub fn run_cli(args: &[String]) -> io::Result<()> {
+    match parse_batch_args(args) {
+        Ok(options) => run(&options),
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+fn sample_processes() -> Vec<ProcessInfo> {
+    let mut init = ProcessInfo::new(1, "init".into(), 10, 4096);
+    init.stat.state = 'S';
+    init.cgroup = Some("/init.scope".into());
+    let mut odd = ProcessInfo::new(42, "a,\"b\"".into(), 20, 2048);
+    odd.stat.state = 'R';
+    odd.cpu_percent = 12.345;
+    odd.read_rate = Some(1536.4);
+    vec![init, odd]
+}
+
+#[test]
+fn test_parse_batch_args() {
+    let options = parse_batch_args(&strings(&[
+        "--format", "csv", "--columns", "pid,name,cpu_percent", "--sort", "memory", "--desc", "--filter", "SSH",
+        "--limit", "5", "-n", "3", "-d", "0.5",
+    ]))
+    .unwrap();
+    assert_eq!(options.format, Format::Csv);
+    assert_eq!(options.columns, vec![Column::Pid, Column::Name, Column::CpuPercent]);
+    assert_eq!((options.query.sort, options.query.descending), (Column::Memory, true));
+    assert_eq!(options.query.filter, "ssh");
+    assert_eq!(options.query.limit, Some(5));
+    assert_eq!(options.iterations, Some(3));
+    assert_eq!(options.delay, Duration::from_millis(500));
+
+    let defaults = parse_batch_args(&[]).unwrap();
+    assert_eq!((defaults.format, defaults.iterations), (Format::Table, Some(1)));
+    assert_eq!(parse_batch_args(&strings(&["-n", "0"])).unwrap().iterations, None);
+    for bad in [&["--format", "xml"][..], &["--columns", "pid,bogus"], &["--columns", ","], &["-d", "0"], &["-n"], &["-x"]] {
+        assert!(parse_batch_args(&strings(bad)).is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn test_render_formats() {
+    let processes = sample_processes();
+    let selected: Vec<&ProcessInfo> = processes.iter().collect();
+    let columns = [Column::Pid, Column::Name, Column::CpuPercent, Column::ReadRate, Column::Cgroup];
+    let time = "2026-10-19T08:00:00Z";
+
+    let table = render(Format::Table, &columns, time, &selected, true);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines[0], "PID Name  CPU% Read/s Cgroup");
+    assert_eq!(lines[1], "1   init  0.0  -      init.scope");
+    assert_eq!(lines[2], "42  a,\"b\" 12.3 1.5K/s -");
+    assert!(render(Format::Table, &columns, time, &selected, false).starts_with("\nPID"));
+
+    let csv = render(Format::Csv, &columns, time, &selected, true);
+    assert_eq!(
+        csv,
+        "time,pid,name,cpu_percent,read_rate,cgroup\n\
+         2026-10-19T08:00:00Z,1,init,0.0,,/init.scope\n\
+         2026-10-19T08:00:00Z,42,\"a,\"\"b\"\"\",12.3,1536.0,\n"
+    );
+    assert!(!render(Format::Csv, &columns, time, &selected, false).starts_with("time"));
+
+    let json: Value = serde_json::from_str(&render(Format::Json, &columns, time, &selected, true)).unwrap();
+    assert_eq!(json["time"], time);
+    assert_eq!(json["processes"][1]["name"], "a,\"b\"");
+    assert_eq!(json["processes"][0]["read_rate"], Value::Null);
+
+    let ndjson = render(Format::Ndjson, &columns, time, &selected, true);
+    let records: Vec<Value> = ndjson.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1]["pid"], 42);
+    assert_eq!(records[1]["cpu_percent"], 12.3);
+    assert_eq!(records[1]["time"], time);
+}
@@ -0,0 +1,219 @@
+use crate::process::ProcessInfo;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+// Prefixes of the scope units created by container runtimes, followed by the container id
+const RUNTIME_PREFIXES: [(&str, &str); 5] = [
+    ("docker-", "docker"),
+    ("cri-containerd-", "containerd"),
+    ("crio-", "crio"),
+    ("libpod-", "podman"),
+    ("containerd-", "containerd"),
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CgroupStats {
+    pub memory_current: Option<u64>,
+    pub memory_max: Option<u64>, // None if unlimited or unknown
+    pub cpu_usage_usec: Option<u64>,
+    pub pids_current: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CgroupGroup {
+    pub path: String,
+    pub label: String,
+    pub process_count: usize,
+    pub cpu_percent: f64,
+    pub memory: u64, // summed RSS of the member processes
+    pub stats: CgroupStats,
+}
+
+// The unified (v2) hierarchy is the "0::" entry of /proc/[pid]/cgroup
+pub fn parse_cgroup_path(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim().to_owned())
+}
+
+pub fn read_cgroup_path(pid: u32) -> Option<String> {
+    let content = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    parse_cgroup_path(&content)
+}
+
+pub fn cgroup_dir(path: &str) -> PathBuf {
+    PathBuf::from(CGROUP_ROOT).join(path.trim_start_matches('/'))
+}
+
+fn container_id(component: &str) -> Option<(&'static str, &str)> {
+    let unit = component.strip_suffix(".scope").unwrap_or(component);
+    RUNTIME_PREFIXES.iter().find_map(|(prefix, runtime)| {
+        let id = unit.strip_prefix(prefix)?;
+        let is_id = id.len() >= 12 && id.chars().all(|c| c.is_ascii_hexdigit());
+        is_id.then_some((*runtime, id))
+    })
+}
+
+// docker-<id>.scope -> docker:<short id>, systemd units keep their unit name
+pub fn short_name(path: &str) -> String {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+
+    for (i, component) in components.iter().enumerate().rev() {
+        if let Some((runtime, id)) = container_id(component) {
+            return format!("{}:{}", runtime, &id[..12]);
+        }
+        // cgroupfs driver layout: /docker/<id>, /kubepods/burstable/pod<uid>/<id>
+        if component.len() == 64 && component.chars().all(|c| c.is_ascii_hexdigit()) {
+            let parents = &components[..i];
+            let runtime = if parents.contains(&"docker") {
+                "docker"
+            } else if parents.contains(&"libpod_parent") {
+                "podman"
+            } else if parents.iter().any(|p| p.starts_with("kubepods")) {
+                "k8s"
+            } else {
+                continue;
+            };
+            return format!("{}:{}", runtime, &component[..12]);
+        }
+    }
+
+    components
+        .iter()
+        .rev()
+        .find(|c| c.ends_with(".service") || c.ends_with(".scope") || c.ends_with(".slice"))
+        .or(components.last())
+        .map(|c| c.to_string())
+        .unwrap_or_else(|| "/".into())
+}
+
+fn read_u64(path: PathBuf) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+pub fn parse_cpu_stat_usage(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+pub fn read_cgroup_stats(path: &str) -> CgroupStats {
+    let dir = cgroup_dir(path);
+    CgroupStats {
+        memory_current: read_u64(dir.join("memory.current")),
+        // "max" does not parse and means unlimited
+        memory_max: read_u64(dir.join("memory.max")),
+        cpu_usage_usec: fs::read_to_string(dir.join("cpu.stat"))
+            .ok()
+            .and_then(|c| parse_cpu_stat_usage(&c)),
+        pids_current: read_u64(dir.join("pids.current")),
+    }
+}
+
+pub fn group_by_cgroup(
+    processes: &[ProcessInfo],
+    read_stats: impl Fn(&str) -> CgroupStats,
+) -> Vec<CgroupGroup> {
+    let mut groups: BTreeMap<&str, CgroupGroup> = BTreeMap::new();
+    for proc in processes {
+        let Some(path) = proc.cgroup.as_deref() else {
+            continue;
+        };
+        let group = groups.entry(path).or_insert_with(|| CgroupGroup {
+            path: path.to_owned(),
+            label: short_name(path),
+            process_count: 0,
+            cpu_percent: 0.0,
+            memory: 0,
+            stats: CgroupStats::default(),
+        });
+        group.process_count += 1;
+        group.cpu_percent += proc.cpu_percent;
+        group.memory += proc.memory;
+    }
+
+    let mut groups: Vec<CgroupGroup> = groups
+        .into_values()
+        .map(|mut group| {
+            group.stats = read_stats(&group.path);
+            group
+        })
+        .collect();
+    groups.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent).then(b.memory.cmp(&a.memory)));
+    groups
+}
+
+#[test]
+fn test_parse_cgroup_path_v2() {
+    let content = "12:pids:/user.slice\n0::/system.slice/sshd.service\n";
+    assert_eq!(parse_cgroup_path(content).as_deref(), Some("/system.slice/sshd.service"));
+    assert_eq!(parse_cgroup_path("4:memory:/docker/abc\n"), None);
+}
+
+#[test]
+fn test_short_name_recognises_container_runtimes() {
+    let id = "4f9c1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f";
+    assert_eq!(
+        short_name(&format!("/system.slice/docker-{}.scope", id)),
+        "docker:4f9c1a2b3c4d"
+    );
+    assert_eq!(
+        short_name(&format!(
+            "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1234.slice/cri-containerd-{}.scope",
+            id
+        )),
+        "containerd:4f9c1a2b3c4d"
+    );
+    assert_eq!(
+        short_name(&format!("/machine.slice/libpod-{}.scope/container", id)),
+        "podman:4f9c1a2b3c4d"
+    );
+    assert_eq!(short_name(&format!("/docker/{}", id)), "docker:4f9c1a2b3c4d");
+    assert_eq!(
+        short_name(&format!("/kubepods/burstable/pod1234/{}", id)),
+        "k8s:4f9c1a2b3c4d"
+    );
+}
+
+#[test]
+fn test_short_name_systemd_units() {
+    assert_eq!(short_name("/system.slice/nginx.service"), "nginx.service");
+    assert_eq!(short_name("/user.slice/user-1000.slice/session-2.scope"), "session-2.scope");
+    assert_eq!(short_name("/"), "/");
+}
+
+#[test]
+fn test_group_by_cgroup_aggregates() {
+    let mut a = ProcessInfo::new(1, "nginx".into(), 0, 1000);
+    a.cpu_percent = 1.5;
+    a.cgroup = Some("/system.slice/nginx.service".into());
+    let mut b = ProcessInfo::new(2, "nginx".into(), 0, 2000);
+    b.cpu_percent = 2.0;
+    b.cgroup = Some("/system.slice/nginx.service".into());
+    let mut c = ProcessInfo::new(3, "sshd".into(), 0, 500);
+    c.cgroup = Some("/system.slice/sshd.service".into());
+
+    let groups = group_by_cgroup(&[a, b, c], |_| CgroupStats {
+        pids_current: Some(7),
+        ..Default::default()
+    });
+
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].label, "nginx.service");
+    assert_eq!(groups[0].process_count, 2);
+    assert_eq!(groups[0].memory, 3000);
+    assert_eq!(groups[0].cpu_percent, 3.5);
+    assert_eq!(groups[0].stats.pids_current, Some(7));
+}
+
+#[test]
+fn test_parse_cpu_stat_usage() {
+    let content = "usage_usec 123456\nuser_usec 100000\nsystem_usec 23456\n";
+    assert_eq!(parse_cpu_stat_usage(content), Some(123456));
+}
@@ -0,0 +1,220 @@
+use crate::cgroup;
+use crate::namespaces;
+use crate::process::ProcessInfo;
+use crate::suspicious;
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Column {
+    Pid,
+    Name,
+    State,
+    Memory,
+    CpuTime,
+    CpuPercent,
+    ReadRate,
+    WriteRate,
+    Cgroup,
+    Namespaces,
+    NsPid,
+    Flags,
+    OomScore,
+    OomScoreAdj,
+}
+
+impl Column {
+    pub const ALL: [Column; 14] = [
+        Column::Pid,
+        Column::Name,
+        Column::State,
+        Column::Memory,
+        Column::CpuTime,
+        Column::CpuPercent,
+        Column::ReadRate,
+        Column::WriteRate,
+        Column::Cgroup,
+        Column::Namespaces,
+        Column::NsPid,
+        Column::Flags,
+        Column::OomScore,
+        Column::OomScoreAdj,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Pid => "PID",
+            Column::Name => "Name",
+            Column::State => "S",
+            Column::Memory => "Memory(KB)",
+            Column::CpuTime => "CPU-Time",
+            Column::CpuPercent => "CPU%",
+            Column::ReadRate => "Read/s",
+            Column::WriteRate => "Write/s",
+            Column::Cgroup => "Cgroup",
+            Column::Namespaces => "NS",
+            Column::NsPid => "NS-PID",
+            Column::Flags => "Flags",
+            Column::OomScore => "OOM",
+            Column::OomScoreAdj => "OOM-Adj",
+        }
+    }
+
+    // Name used by the HTTP API and the command line
+    pub fn key(&self) -> &'static str {
+        match self {
+            Column::Pid => "pid",
+            Column::Name => "name",
+            Column::State => "state",
+            Column::Memory => "memory",
+            Column::CpuTime => "cpu_time",
+            Column::CpuPercent => "cpu_percent",
+            Column::ReadRate => "read_rate",
+            Column::WriteRate => "write_rate",
+            Column::Cgroup => "cgroup",
+            Column::Namespaces => "namespaces",
+            Column::NsPid => "ns_pid",
+            Column::Flags => "flags",
+            Column::OomScore => "oom_score",
+            Column::OomScoreAdj => "oom_score_adj",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.key() == key)
+    }
+
+    pub fn width(&self) -> u16 {
+        match self {
+            Column::Pid | Column::NsPid => 9,
+            Column::State => 2,
+            Column::Namespaces | Column::Flags => 18,
+            Column::Name | Column::Cgroup => 25,
+            Column::Memory => 12,
+            Column::CpuTime | Column::CpuPercent | Column::ReadRate | Column::WriteRate => 10,
+            Column::OomScore | Column::OomScoreAdj => 8,
+        }
+    }
+
+    pub fn cell(&self, proc: &ProcessInfo) -> String {
+        match self {
+            Column::Pid => proc.pid.to_string(),
+            Column::Name => proc.name.clone(),
+            Column::State => proc.stat.state.to_string(),
+            Column::Memory => (proc.memory / 1024).to_string(),
+            Column::CpuTime => proc.cpu_time.to_string(),
+            Column::CpuPercent => format!("{:.1}", proc.cpu_percent),
+            Column::ReadRate => format_rate(proc.read_rate),
+            Column::WriteRate => format_rate(proc.write_rate),
+            Column::Cgroup => proc.cgroup.as_deref().map(cgroup::short_name).unwrap_or_else(|| "-".into()),
+            Column::Namespaces => match &proc.namespaces {
+                Some(_) => namespaces::isolation_label(&proc.isolated_namespaces),
+                None => "-".into(),
+            },
+            Column::NsPid => proc.container_pid().map(|p| p.to_string()).unwrap_or_else(|| "-".into()),
+            Column::Flags => suspicious::flag_codes(&proc.flags),
+            Column::OomScore => proc.oom_score.map(|s| s.to_string()).unwrap_or_else(|| "-".into()),
+            Column::OomScoreAdj => proc.oom_score_adj.map(|s| s.to_string()).unwrap_or_else(|| "-".into()),
+        }
+    }
+
+    // Unformatted value for machine readable output: bytes, full cgroup paths, null when unknown
+    pub fn value(&self, proc: &ProcessInfo) -> Value {
+        match self {
+            Column::Pid => json!(proc.pid),
+            Column::Name => json!(proc.name),
+            Column::State => json!(proc.stat.state.to_string()),
+            Column::Memory => json!(proc.memory),
+            Column::CpuTime => json!(proc.cpu_time),
+            Column::CpuPercent => json!((proc.cpu_percent * 10.0).round() / 10.0),
+            Column::ReadRate => json!(proc.read_rate.map(f64::round)),
+            Column::WriteRate => json!(proc.write_rate.map(f64::round)),
+            Column::Cgroup => json!(proc.cgroup),
+            Column::Namespaces => json!(proc.namespaces.as_ref().map(|_| &proc.isolated_namespaces)),
+            Column::NsPid => json!(proc.container_pid()),
+            Column::Flags => json!(proc.flags.iter().map(|f| f.code()).collect::<Vec<_>>()),
+            Column::OomScore => json!(proc.oom_score),
+            Column::OomScoreAdj => json!(proc.oom_score_adj),
+        }
+    }
+
+    pub fn compare(&self, a: &ProcessInfo, b: &ProcessInfo) -> Ordering {
+        match self {
+            Column::Pid => a.pid.cmp(&b.pid),
+            Column::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            Column::State => a.stat.state.cmp(&b.stat.state),
+            Column::Memory => a.memory.cmp(&b.memory),
+            Column::CpuTime => a.cpu_time.cmp(&b.cpu_time),
+            Column::CpuPercent => a.cpu_percent.total_cmp(&b.cpu_percent),
+            // Unknown rates sort below every known rate
+            Column::ReadRate => compare_rate(a.read_rate, b.read_rate),
+            Column::WriteRate => compare_rate(a.write_rate, b.write_rate),
+            Column::Cgroup => a.cgroup.cmp(&b.cgroup),
+            Column::Namespaces => a.isolated_namespaces.len().cmp(&b.isolated_namespaces.len()),
+            Column::NsPid => a.container_pid().cmp(&b.container_pid()),
+            Column::Flags => a.flags.len().cmp(&b.flags.len()),
+            Column::OomScore => a.oom_score.cmp(&b.oom_score),
+            Column::OomScoreAdj => a.oom_score_adj.cmp(&b.oom_score_adj),
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|c| c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+fn compare_rate(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0}{}", value, UNITS[unit])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+pub fn format_rate(rate: Option<f64>) -> String {
+    match rate {
+        Some(rate) => format!("{}/s", format_bytes(rate)),
+        None => "-".into(),
+    }
+}
+
+#[test]
+fn test_format_bytes_units() {
+    assert_eq!(format_bytes(512.0), "512B");
+    assert_eq!(format_bytes(2048.0), "2.0K");
+    assert_eq!(format_bytes(5.5 * 1024.0 * 1024.0), "5.5M");
+}
+
+#[test]
+fn test_unknown_rate_is_placeholder_and_sorts_last() {
+    let mut known = ProcessInfo::new(1, "a".into(), 0, 0);
+    known.read_rate = Some(0.0);
+    let unknown = ProcessInfo::new(2, "b".into(), 0, 0);
+
+    assert_eq!(Column::ReadRate.cell(&unknown), "-");
+    assert_eq!(Column::ReadRate.cell(&known), "0B/s");
+    assert_eq!(Column::ReadRate.compare(&unknown, &known), Ordering::Less);
+}
+
+#[test]
+fn test_column_keys_round_trip() {
+    for column in Column::ALL {
+        assert_eq!(Column::from_key(column.key()), Some(column));
+    }
+    assert_eq!(Column::from_key("bogus"), None);
+}
@@ -0,0 +1,352 @@
+use crate::kill;
+use crate::launcher;
+use crate::process;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Process control over HTTP, only available when a token file was given
+pub struct Control {
+    token: String,
+    audit: Mutex<File>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SignalSpec {
+    Number(i32),
+    Name(String), // TERM or SIGTERM
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignalRequest {
+    pub signal: SignalSpec,
+    pub starttime: Option<u64>, // refuses to act if the pid has been reused
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReniceRequest {
+    pub nice: i32,
+    pub starttime: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AffinityRequest {
+    pub cpus: String, // taskset -c syntax, e.g. 0,2-3
+    pub starttime: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Signal(i32),
+    Renice(i32),
+    Affinity(Vec<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlError {
+    BadRequest(String),
+    NotFound,
+    Conflict(String), // the pid now belongs to a different process
+    Denied(String),
+    Failed(String),
+}
+
+// One line of the audit log, written for every attempted action including refused ones
+#[derive(Debug, Serialize)]
+pub struct AuditEntry<'a> {
+    pub time: String,
+    pub client: &'a str,
+    pub user_agent: Option<&'a str>,
+    pub authorized: bool,
+    pub action: &'a str,
+    pub pid: Option<u32>,
+    pub starttime: Option<u64>,
+    pub params: &'a str,
+    pub result: Result<(), String>,
+}
+
+// A token readable by anyone else on the host is as good as no token
+pub fn load_token(path: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if metadata.mode() & 0o077 != 0 {
+        return Err(format!(
+            "{}: permissions are {:o}, the token file must be 0600",
+            path.display(),
+            metadata.mode() & 0o777
+        ));
+    }
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(format!("{}: not owned by the current user", path.display()));
+    }
+    let token = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let token = token.trim();
+    if token.len() < 16 {
+        return Err(format!("{}: the token must be at least 16 characters", path.display()));
+    }
+    Ok(token.to_owned())
+}
+
+pub fn default_audit_log() -> PathBuf {
+    let state_dir = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    state_dir.join("task-manager").join("audit.log")
+}
+
+// Compares every byte so the response time does not reveal how much of a guess was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Howard Hinnant's days-to-civil conversion, to avoid a date crate for one timestamp
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+pub fn parse_signal(spec: &SignalSpec) -> Result<i32, String> {
+    match spec {
+        SignalSpec::Number(n) if (1..=64).contains(n) => Ok(*n),
+        SignalSpec::Number(n) => Err(format!("invalid signal {}", n)),
+        SignalSpec::Name(name) => kill::signal_number(name).ok_or_else(|| format!("unknown signal '{}'", name)),
+    }
+}
+
+impl Action {
+    pub fn params(&self) -> String {
+        match self {
+            Action::Signal(signal) => format!("{} ({})", kill::signal_name(*signal), signal),
+            Action::Renice(nice) => nice.to_string(),
+            Action::Affinity(cpus) => cpus.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(","),
+        }
+    }
+}
+
+// The body of POST /processes/{pid}/{action}, with the expected start time if given
+pub fn parse_action(action: &str, body: &str) -> Result<(Action, Option<u64>), String> {
+    let invalid = |e: serde_json::Error| format!("invalid request body: {}", e);
+    match action {
+        "signal" => {
+            let request: SignalRequest = serde_json::from_str(body).map_err(invalid)?;
+            Ok((Action::Signal(parse_signal(&request.signal)?), request.starttime))
+        }
+        "renice" => {
+            let request: ReniceRequest = serde_json::from_str(body).map_err(invalid)?;
+            if !(-20..=19).contains(&request.nice) {
+                return Err(format!("nice must be between -20 and 19, not {}", request.nice));
+            }
+            Ok((Action::Renice(request.nice), request.starttime))
+        }
+        "affinity" => {
+            let request: AffinityRequest = serde_json::from_str(body).map_err(invalid)?;
+            Ok((Action::Affinity(launcher::parse_cpu_list(&request.cpus)?), request.starttime))
+        }
+        _ => Err(format!("unknown action '{}'", action)),
+    }
+}
+
+fn os_error(e: io::Error) -> ControlError {
+    match e.raw_os_error() {
+        Some(libc::ESRCH) => ControlError::NotFound,
+        Some(libc::EPERM) | Some(libc::EACCES) => ControlError::Denied(e.to_string()),
+        Some(libc::EINVAL) => ControlError::BadRequest(e.to_string()),
+        _ => ControlError::Failed(e.to_string()),
+    }
+}
+
+fn last_os_result(ret: libc::c_int) -> Result<(), ControlError> {
+    if ret != 0 {
+        return Err(os_error(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+// renice and affinity apply to the main thread, like renice(1) and taskset(1) without -a
+pub fn perform(pid: u32, action: &Action) -> Result<(), ControlError> {
+    match action {
+        Action::Signal(signal) => last_os_result(unsafe { libc::kill(pid as libc::pid_t, *signal) }),
+        Action::Renice(nice) => last_os_result(unsafe { libc::setpriority(libc::PRIO_PROCESS, pid, *nice) }),
+        Action::Affinity(cpus) => {
+            let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            for cpu in cpus {
+                if *cpu >= libc::CPU_SETSIZE as usize {
+                    return Err(ControlError::BadRequest(format!("CPU {} is out of range", cpu)));
+                }
+                unsafe { libc::CPU_SET(*cpu, &mut set) };
+            }
+            last_os_result(unsafe {
+                libc::sched_setaffinity(pid as libc::pid_t, std::mem::size_of::<libc::cpu_set_t>(), &set)
+            })
+        }
+    }
+}
+
+// The start time is read right before acting, so a pid reused since the client looked is refused
+pub fn perform_checked(pid: u32, expected_starttime: Option<u64>, action: &Action) -> (Option<u64>, Result<(), ControlError>) {
+    let Some(stat) = process::read_stat(pid) else {
+        return (None, Err(ControlError::NotFound));
+    };
+    if let Some(expected) = expected_starttime
+        && expected != stat.starttime
+    {
+        let message = format!("process {} has start time {}, not {}", pid, stat.starttime, expected);
+        return (Some(stat.starttime), Err(ControlError::Conflict(message)));
+    }
+    (Some(stat.starttime), perform(pid, action))
+}
+
+impl ControlError {
+    pub fn message(&self) -> String {
+        match self {
+            ControlError::NotFound => "no such process".into(),
+            ControlError::BadRequest(m)
+            | ControlError::Conflict(m)
+            | ControlError::Denied(m)
+            | ControlError::Failed(m) => m.clone(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            ControlError::BadRequest(_) => 400,
+            ControlError::Denied(_) => 403,
+            ControlError::NotFound => 404,
+            ControlError::Conflict(_) => 409,
+            ControlError::Failed(_) => 500,
+        }
+    }
+}
+
+impl Control {
+    pub fn new(token: String, audit: File) -> Self {
+        Control {
+            token,
+            audit: Mutex::new(audit),
+        }
+    }
+
+    // The audit log is created 0600 as well, it records who did what
+    pub fn load(token_file: &Path, audit_log: &Path) -> Result<Self, String> {
+        let token = load_token(token_file)?;
+        if let Some(dir) = audit_log.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let audit = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(audit_log)
+            .map_err(|e| format!("{}: {}", audit_log.display(), e))?;
+        Ok(Control::new(token, audit))
+    }
+
+    // Takes the raw Authorization header value
+    pub fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        authorization
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()))
+    }
+
+    pub fn audit(&self, entry: &AuditEntry) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        let mut file = self.audit.lock().unwrap();
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+pub fn now_utc() -> String {
+    format_utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0))
+}
+
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("task-manager-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_load_token_requires_private_file() {
+    use std::os::unix::fs::PermissionsExt;
+    let path = temp_path("token");
+    fs::write(&path, "0123456789abcdef0123\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    assert!(load_token(&path).unwrap_err().contains("0600"));
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(load_token(&path).unwrap(), "0123456789abcdef0123");
+    fs::write(&path, "short").unwrap();
+    assert!(load_token(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_is_authorized() {
+    let control = Control::new("0123456789abcdef".into(), File::open("/dev/null").unwrap());
+    assert!(control.is_authorized(Some("Bearer 0123456789abcdef")));
+    assert!(!control.is_authorized(Some("Bearer 0123456789abcdeX")));
+    assert!(!control.is_authorized(Some("Basic 0123456789abcdef")));
+    assert!(!control.is_authorized(None));
+}
+
+#[test]
+fn test_format_utc() {
+    assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_utc(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_utc(1700000000), "2023-11-14T22:13:20Z");
+}
+
+#[test]
+fn test_parse_action() {
+    assert_eq!(parse_action("signal", r#"{"signal": "TERM"}"#).unwrap(), (Action::Signal(libc::SIGTERM), None));
+    assert_eq!(
+        parse_action("signal", r#"{"signal": 9, "starttime": 42}"#).unwrap(),
+        (Action::Signal(9), Some(42))
+    );
+    assert_eq!(parse_action("renice", r#"{"nice": 10}"#).unwrap().0, Action::Renice(10));
+    assert_eq!(parse_action("affinity", r#"{"cpus": "0,2-3"}"#).unwrap().0, Action::Affinity(vec![0, 2, 3]));
+    assert!(parse_action("renice", r#"{"nice": 40}"#).is_err());
+    assert!(parse_action("signal", r#"{"signal": "SIGFOO"}"#).is_err());
+    assert!(parse_action("signal", r#"{"signal": 15, "extra": 1}"#).is_err());
+    assert!(parse_action("signal", "not json").is_err());
+}
+
+#[test]
+fn test_perform_checked_guards_pid_reuse() {
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let pid = child.id();
+    let starttime = process::read_stat(pid).unwrap().starttime;
+
+    let (_, result) = perform_checked(pid, Some(starttime + 1), &Action::Signal(libc::SIGTERM));
+    assert!(matches!(result, Err(ControlError::Conflict(_))));
+    assert_eq!(perform_checked(pid, None, &Action::Renice(5)), (Some(starttime), Ok(())));
+    assert_eq!(perform_checked(pid, Some(starttime), &Action::Signal(libc::SIGTERM)).1, Ok(()));
+    child.wait().unwrap();
+    assert_eq!(perform_checked(pid, None, &Action::Signal(libc::SIGTERM)).1, Err(ControlError::NotFound));
+}
@@ -1,5 +1,4 @@
 use crate::process::ProcessInfo;
-use num_cpus;
 use std::collections::HashMap;
 use std::time::Instant;
 
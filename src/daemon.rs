@@ -0,0 +1,163 @@
+use crate::app::App;
+use crate::control::{self, Control};
+#[cfg(test)]
+use crate::control::temp_path;
+use crate::kill;
+use crate::stream::SharedStreamHub;
+use crate::web_tui::SharedFrameHub;
+use crate::webserver::{self, Listen, ServerOptions};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Set from the signal handler, 0 until SIGTERM or SIGINT arrives
+static SHUTDOWN_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn request_shutdown(signal: libc::c_int) {
+    SHUTDOWN_SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+pub fn install_signal_handlers() {
+    for signal in [libc::SIGTERM, libc::SIGINT] {
+        unsafe { libc::signal(signal, request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+    }
+}
+
+pub fn shutdown_signal() -> Option<i32> {
+    match SHUTDOWN_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+// Timestamped lines on stderr, where journald picks them up, or appended to a file
+pub struct Log {
+    file: Option<Mutex<File>>,
+}
+
+impl Log {
+    pub fn open(path: Option<&Path>) -> Result<Self, String> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?,
+            )),
+            None => None,
+        };
+        Ok(Log { file })
+    }
+
+    pub fn line(&self, message: &str) {
+        let line = format!("{} {}\n", control::now_utc(), message);
+        // Nowhere left to report a failed log write
+        let _ = match &self.file {
+            Some(file) => file.lock().unwrap().write_all(line.as_bytes()),
+            None => io::stderr().write_all(line.as_bytes()),
+        };
+    }
+}
+
+pub fn parse_pidfile(content: &str) -> Option<u32> {
+    content.trim().parse().ok()
+}
+
+// Holds an flock on the pidfile for as long as it lives, so a stale file left by a crash
+// or a reused pid cannot block startup and two instances cannot both take it
+pub struct Pidfile {
+    path: PathBuf,
+    _file: File, // open only to keep the lock
+}
+
+impl Pidfile {
+    pub fn acquire(path: &Path) -> Result<Self, String> {
+        let error = |e: io::Error| format!("{}: {}", path.display(), e);
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(error)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let lock_error = io::Error::last_os_error();
+            if lock_error.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(error(lock_error));
+            }
+            let mut content = String::new();
+            let _ = file.read_to_string(&mut content);
+            return Err(match parse_pidfile(&content) {
+                Some(pid) => format!("{}: already running as pid {}", path.display(), pid),
+                None => format!("{}: already locked by another instance", path.display()),
+            });
+        }
+        file.set_len(0).map_err(error)?;
+        file.rewind().map_err(error)?;
+        file.write_all(format!("{}\n", std::process::id()).as_bytes()).map_err(error)?;
+        Ok(Pidfile { path: path.to_owned(), _file: file })
+    }
+}
+
+// Unlinked while still locked, the lock goes when the file is closed right after
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Collector and web server without a terminal, until SIGTERM or SIGINT
+pub fn run(app: Arc<Mutex<App>>, hub: SharedStreamHub, options: &ServerOptions, control: Option<Control>) -> Result<(), String> {
+    let log = Log::open(options.log_file.as_deref())?;
+    install_signal_handlers();
+    // Taken before binding, which would otherwise unlink the Unix socket of a running instance
+    let pidfile = options.pidfile.as_deref().map(Pidfile::acquire).transpose()?;
+    // Serving HTTP is the point of running headless, so a failed bind is fatal here
+    let listening = webserver::start_http_server(app, hub, SharedFrameHub::default(), None, options, control)?
+        .ok_or("headless mode needs the web server, drop --no-http")?;
+    log.line(&format!("started as pid {}, web server listening on {}", std::process::id(), listening));
+
+    let signal = loop {
+        if let Some(signal) = shutdown_signal() {
+            break signal;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    log.line(&format!("received {}, shutting down", kill::signal_name(signal)));
+    if let Listen::Unix { path, .. } = &options.listen {
+        let _ = fs::remove_file(path);
+    }
+    drop(pidfile);
+    Ok(())
+}
+
+#[test]
+fn test_pidfile_replaces_stale_and_refuses_locked() {
+    let path = temp_path("pidfile");
+    fs::write(&path, "999999999\n").unwrap();
+    let pidfile = Pidfile::acquire(&path).unwrap();
+    assert_eq!(parse_pidfile(&fs::read_to_string(&path).unwrap()), Some(std::process::id()));
+
+    // flock locks belong to the open file, so a second open conflicts even in this process
+    let error = Pidfile::acquire(&path).err().unwrap();
+    assert!(error.contains(&format!("already running as pid {}", std::process::id())), "{}", error);
+
+    drop(pidfile);
+    assert!(!path.exists());
+    drop(Pidfile::acquire(&path).unwrap());
+}
+
+#[test]
+fn test_log_appends_timestamped_lines() {
+    let path = temp_path("daemon.log");
+    let _ = fs::remove_file(&path);
+    let log = Log::open(Some(&path)).unwrap();
+    log.line("first");
+    Log::open(Some(&path)).unwrap().line("second");
+    let content = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("Z first"), "{}", lines[0]);
+    assert!(lines[1].ends_with(" second"));
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,313 @@
+use crate::environ::{self, EnvDiff, EnvVar};
+use crate::exe_hash::{self, FileHash, SharedHashCache};
+use crate::limits::{self, ResourceLimit};
+use crate::security::{self, SecurityInfo};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DetailTab {
+    Environment,
+    Security,
+    Limits,
+    Hashes,
+}
+
+impl DetailTab {
+    pub const ALL: [DetailTab; 4] = [
+        DetailTab::Environment,
+        DetailTab::Security,
+        DetailTab::Limits,
+        DetailTab::Hashes,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            DetailTab::Environment => "Environment",
+            DetailTab::Security => "Security",
+            DetailTab::Limits => "Limits",
+            DetailTab::Hashes => "Hashes",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        Self::ALL.iter().position(|t| t == self).unwrap_or(0)
+    }
+
+    pub fn next(&self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn prev(&self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+// One line of the Security tab, the renderer styles it and line_count counts it
+pub enum SecurityRow {
+    Heading(&'static str),
+    Capability(&'static str, u64),
+    Field(&'static str, String),
+    Blank,
+}
+
+pub fn security_rows(info: &SecurityInfo) -> Vec<SecurityRow> {
+    let unknown = || "unknown (permission denied?)".to_string();
+    let yes_no = |value: Option<bool>| match value {
+        Some(true) => "yes".to_string(),
+        Some(false) => "no".to_string(),
+        None => unknown(),
+    };
+    let seccomp = match info.seccomp {
+        Some(mode) => match info.seccomp_filters {
+            Some(filters) => format!("{} ({} filters)", security::seccomp_mode_name(mode), filters),
+            None => security::seccomp_mode_name(mode).to_string(),
+        },
+        None => unknown(),
+    };
+    let caps = &info.capabilities;
+    vec![
+        SecurityRow::Heading("Capabilities"),
+        SecurityRow::Capability("Inheritable", caps.inheritable),
+        SecurityRow::Capability("Permitted", caps.permitted),
+        SecurityRow::Capability("Effective", caps.effective),
+        SecurityRow::Capability("Bounding", caps.bounding),
+        SecurityRow::Capability("Ambient", caps.ambient),
+        SecurityRow::Blank,
+        SecurityRow::Heading("Confinement"),
+        SecurityRow::Field("Seccomp", seccomp),
+        SecurityRow::Field("NoNewPrivs", yes_no(info.no_new_privs)),
+        SecurityRow::Field("LSM label", info.lsm_label.clone().unwrap_or_else(|| "none".into())),
+        SecurityRow::Field("setuid exe", yes_no(info.exe_setuid)),
+        SecurityRow::Field("setgid exe", yes_no(info.exe_setgid)),
+    ]
+}
+
+pub struct EnvDiffView {
+    pub other_pid: u32,
+    pub diff: Result<Vec<EnvDiff>, String>,
+}
+
+// Everything shown in the detail tabs of a single process, loaded when the view is opened
+pub struct ProcessDetails {
+    pub pid: u32,
+    pub name: String,
+    pub tab: DetailTab,
+    pub scroll: usize,
+    pub search: String,
+    pub searching: bool,
+    pub environ: Result<Vec<EnvVar>, String>,
+    pub env_diff: Option<EnvDiffView>,
+    pub security: Result<SecurityInfo, String>,
+    pub limits: Result<Vec<ResourceLimit>, String>, // on the Limits tab, scroll is the selected row
+    pub limit_edit: Option<String>,
+    pub limit_message: Option<Result<String, String>>,
+    pub hashes: Option<Result<Vec<FileHash>, String>>,
+    pub hashes_pending: Option<Receiver<Result<Vec<FileHash>, String>>>,
+}
+
+impl ProcessDetails {
+    pub fn load(pid: u32, name: String) -> Self {
+        Self {
+            pid,
+            name,
+            tab: DetailTab::Environment,
+            scroll: 0,
+            search: String::new(),
+            searching: false,
+            environ: environ::read_environ(pid).map_err(|e| e.to_string()),
+            env_diff: None,
+            security: security::read_security_info(pid).map_err(|e| e.to_string()),
+            limits: limits::read_limits(pid).map_err(|e| e.to_string()),
+            limit_edit: None,
+            limit_message: None,
+            hashes: None,
+            hashes_pending: None,
+        }
+    }
+
+    pub fn selected_limit(&self) -> Option<&ResourceLimit> {
+        self.limits.as_ref().ok()?.get(self.scroll)
+    }
+
+    pub fn start_limit_edit(&mut self) {
+        if let Some(limit) = self.selected_limit() {
+            self.limit_edit = Some(limits::format_limit(limit.soft));
+            self.limit_message = None;
+        }
+    }
+
+    pub fn apply_limit_edit(&mut self) {
+        let Some(input) = self.limit_edit.take() else {
+            return;
+        };
+        let Some(limit) = self.selected_limit() else {
+            return;
+        };
+        let name = limit.name.clone();
+        let result = match (limit.resource, limits::parse_limit_value(&input)) {
+            (None, _) => Err(format!("{} cannot be changed", name)),
+            (_, Err(e)) => Err(e),
+            (Some(resource), Ok(value)) => limits::set_soft_limit(self.pid, resource, value)
+                .map(|_| format!("{} soft limit set to {}", name, input.trim()))
+                .map_err(|e| format!("{}: {}", name, e)),
+        };
+        self.limit_message = Some(result);
+        self.limits = limits::read_limits(self.pid).map_err(|e| e.to_string());
+    }
+
+    // Hashing every mapped library can take seconds, so it runs in the background
+    pub fn start_hashing(&mut self, cache: SharedHashCache, with_packages: bool) {
+        let (tx, rx) = mpsc::channel();
+        let pid = self.pid;
+        thread::spawn(move || {
+            let hashes = exe_hash::hash_process_files(pid, &cache, with_packages).map_err(|e| e.to_string());
+            let _ = tx.send(hashes);
+        });
+        self.hashes_pending = Some(rx);
+    }
+
+    pub fn poll_hashes(&mut self) {
+        if let Some(rx) = &self.hashes_pending
+            && let Ok(hashes) = rx.try_recv()
+        {
+            self.hashes = Some(hashes);
+            self.hashes_pending = None;
+        }
+    }
+
+    pub fn toggle_env_diff(&mut self, other_pid: Option<u32>) {
+        if self.env_diff.is_some() {
+            self.env_diff = None;
+            self.clamp_scroll();
+            return;
+        }
+        let Some(other_pid) = other_pid else {
+            return;
+        };
+        let diff = match (&self.environ, environ::read_environ(other_pid)) {
+            (Ok(own), Ok(other)) => Ok(environ::diff_environ(own, &other)),
+            (Err(e), _) => Err(format!("PID {}: {}", self.pid, e)),
+            (_, Err(e)) => Err(format!("PID {}: {}", other_pid, e)),
+        };
+        self.env_diff = Some(EnvDiffView { other_pid, diff });
+        self.scroll = 0;
+    }
+
+    pub fn visible_environ(&self) -> Vec<&EnvVar> {
+        match &self.environ {
+            Ok(vars) => vars.iter().filter(|v| v.matches(&self.search)).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn visible_env_diff(&self) -> Vec<&EnvDiff> {
+        let query = self.search.to_lowercase();
+        match self.env_diff.as_ref().map(|d| &d.diff) {
+            Some(Ok(diff)) => diff
+                .iter()
+                .filter(|d| d.key().to_lowercase().contains(&query))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // Rows the current tab draws, scrolling stops at the last one
+    pub fn line_count(&self) -> usize {
+        match self.tab {
+            DetailTab::Environment if self.env_diff.is_some() => self.visible_env_diff().len(),
+            DetailTab::Environment => self.visible_environ().len(),
+            DetailTab::Security => self.security.as_ref().map_or(0, |info| security_rows(info).len()),
+            DetailTab::Limits => self.limits.as_ref().map_or(0, Vec::len),
+            DetailTab::Hashes => match &self.hashes {
+                Some(Ok(hashes)) => hashes.len(),
+                _ => 0,
+            },
+        }
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.scroll + 1 < self.line_count() {
+            self.scroll += 1;
+        }
+    }
+
+    // Keeps scroll on a drawn row after the search or the diff toggle shrinks the list
+    pub fn clamp_scroll(&mut self) {
+        self.scroll = self.scroll.min(self.line_count().saturating_sub(1));
+    }
+
+    pub fn push_search(&mut self, c: char) {
+        self.search.push(c);
+        self.clamp_scroll();
+    }
+
+    pub fn pop_search(&mut self) {
+        self.search.pop();
+        self.clamp_scroll();
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn switch_tab(&mut self, tab: DetailTab, hash_cache: SharedHashCache) {
+        self.tab = tab;
+        self.scroll = 0;
+        if tab == DetailTab::Hashes && self.hashes.is_none() && self.hashes_pending.is_none() {
+            self.start_hashing(hash_cache, false);
+        }
+    }
+}
+
+#[test]
+fn test_detail_tab_cycles() {
+    let tab = DetailTab::Environment;
+    assert_eq!(tab.next(), DetailTab::Security);
+    assert_eq!(tab.prev(), DetailTab::Hashes);
+    assert_eq!(tab.next().next().next().next(), DetailTab::Environment);
+}
+
+#[test]
+fn test_visible_environ_applies_search() {
+    let mut details = ProcessDetails::load(std::process::id(), "self".into());
+    details.environ = Ok(environ::parse_environ(b"HOME=/root\0LD_PRELOAD=/x.so\0PATH=/bin\0"));
+    details.search = "ld_".into();
+    let visible = details.visible_environ();
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].key, "LD_PRELOAD");
+}
+
+#[test]
+fn test_scroll_stops_at_last_line() {
+    let mut details = ProcessDetails::load(std::process::id(), "self".into());
+    details.environ = Ok(environ::parse_environ(b"A=1\0B=2\0C=3\0"));
+    for _ in 0..10 {
+        details.scroll_down();
+    }
+    assert_eq!(details.scroll, 2);
+    details.push_search('b');
+    assert_eq!(details.line_count(), 1);
+    assert_eq!(details.scroll, 0);
+    details.pop_search();
+    assert_eq!(details.line_count(), 3);
+
+    details.switch_tab(DetailTab::Security, SharedHashCache::default());
+    for _ in 0..20 {
+        details.scroll_down();
+    }
+    let rows = security_rows(details.security.as_ref().unwrap()).len();
+    assert_eq!(details.scroll, rows - 1);
+}
+
+#[test]
+fn test_limit_edit_rejects_invalid_value() {
+    let mut details = ProcessDetails::load(std::process::id(), "self".into());
+    details.switch_tab(DetailTab::Limits, SharedHashCache::default());
+    details.start_limit_edit();
+    assert!(details.limit_edit.is_some());
+    details.limit_edit = Some("lots".into());
+    details.apply_limit_edit();
+    assert!(matches!(details.limit_message, Some(Err(_))));
+    assert!(details.limit_edit.is_none());
+}
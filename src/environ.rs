@@ -0,0 +1,140 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnvVar {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvDiff {
+    OnlyLeft(EnvVar),
+    OnlyRight(EnvVar),
+    Changed {
+        key: String,
+        left: String,
+        right: String,
+    },
+}
+
+impl EnvVar {
+    // Loader variables can inject code, proxy variables can redirect traffic
+    pub fn is_security_relevant(&self) -> bool {
+        is_security_relevant_key(&self.key)
+    }
+
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.key.to_lowercase().contains(&query) || self.value.to_lowercase().contains(&query)
+    }
+}
+
+impl EnvDiff {
+    pub fn key(&self) -> &str {
+        match self {
+            EnvDiff::OnlyLeft(var) | EnvDiff::OnlyRight(var) => &var.key,
+            EnvDiff::Changed { key, .. } => key,
+        }
+    }
+}
+
+pub fn is_security_relevant_key(key: &str) -> bool {
+    let upper = key.to_uppercase();
+    upper.starts_with("LD_") || upper.ends_with("_PROXY")
+}
+
+pub fn read_environ(pid: u32) -> io::Result<Vec<EnvVar>> {
+    let raw = fs::read(format!("/proc/{}/environ", pid))?;
+    Ok(parse_environ(&raw))
+}
+
+// environ is a list of NUL terminated KEY=VALUE entries
+pub fn parse_environ(raw: &[u8]) -> Vec<EnvVar> {
+    let mut vars: Vec<EnvVar> = raw
+        .split(|b| *b == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            match entry.split_once('=') {
+                Some((key, value)) => EnvVar {
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                },
+                None => EnvVar {
+                    key: entry.into_owned(),
+                    value: String::new(),
+                },
+            }
+        })
+        .collect();
+    vars.sort_by(|a, b| a.key.cmp(&b.key));
+    vars
+}
+
+// Only differences are returned, sorted by key
+pub fn diff_environ(left: &[EnvVar], right: &[EnvVar]) -> Vec<EnvDiff> {
+    let left_map: BTreeMap<&str, &EnvVar> = left.iter().map(|v| (v.key.as_str(), v)).collect();
+    let right_map: BTreeMap<&str, &EnvVar> = right.iter().map(|v| (v.key.as_str(), v)).collect();
+
+    let mut keys: Vec<&str> = left_map.keys().chain(right_map.keys()).copied().collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| match (left_map.get(key), right_map.get(key)) {
+            (Some(l), Some(r)) if l.value != r.value => Some(EnvDiff::Changed {
+                key: key.to_owned(),
+                left: l.value.clone(),
+                right: r.value.clone(),
+            }),
+            (Some(l), None) => Some(EnvDiff::OnlyLeft((*l).clone())),
+            (None, Some(r)) => Some(EnvDiff::OnlyRight((*r).clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn var(key: &str, value: &str) -> EnvVar {
+    EnvVar {
+        key: key.into(),
+        value: value.into(),
+    }
+}
+
+#[test]
+fn test_parse_environ_sorts_and_splits_on_first_equals() {
+    let vars = parse_environ(b"PATH=/usr/bin\0A=b=c\0EMPTY=\0");
+    assert_eq!(vars, vec![var("A", "b=c"), var("EMPTY", ""), var("PATH", "/usr/bin")]);
+}
+
+#[test]
+fn test_security_relevant_variables() {
+    assert!(var("LD_PRELOAD", "/tmp/x.so").is_security_relevant());
+    assert!(var("LD_LIBRARY_PATH", "/opt").is_security_relevant());
+    assert!(var("https_proxy", "http://proxy:3128").is_security_relevant());
+    assert!(!var("HOME", "/root").is_security_relevant());
+}
+
+#[test]
+fn test_diff_environ_reports_only_differences() {
+    let left = vec![var("A", "1"), var("B", "2"), var("C", "3")];
+    let right = vec![var("A", "1"), var("B", "changed"), var("D", "4")];
+
+    let diff = diff_environ(&left, &right);
+    assert_eq!(
+        diff,
+        vec![
+            EnvDiff::Changed {
+                key: "B".into(),
+                left: "2".into(),
+                right: "changed".into()
+            },
+            EnvDiff::OnlyLeft(var("C", "3")),
+            EnvDiff::OnlyRight(var("D", "4")),
+        ]
+    );
+}
@@ -0,0 +1,282 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+
+// Identifies file contents without reading them; a replaced binary gets a new inode or mtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct FileKey {
+    pub dev: u64,
+    pub ino: u64,
+    pub mtime: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileHash {
+    pub path: String,
+    pub sha256: Result<String, String>,
+    pub package: Option<Result<String, String>>, // None if not looked up
+}
+
+#[derive(Default)]
+pub struct HashCache {
+    pub entries: HashMap<FileKey, String>,
+}
+
+pub type SharedHashCache = Arc<Mutex<HashCache>>;
+
+impl FileKey {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            mtime: metadata.mtime(),
+        })
+    }
+}
+
+pub fn sha256_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub fn sha256_cached(path: &Path, cache: &SharedHashCache) -> io::Result<String> {
+    let key = FileKey::of(path)?;
+    if let Some(hash) = cache.lock().unwrap().entries.get(&key) {
+        return Ok(hash.clone());
+    }
+    // Hash outside the lock, large libraries take a moment
+    let hash = sha256_reader(File::open(path)?)?;
+    cache.lock().unwrap().entries.insert(key, hash.clone());
+    Ok(hash)
+}
+
+// A file backed mapping from /proc/[pid]/maps; path keeps the " (deleted)" marker
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappedFile {
+    pub range: String, // start-end, the entry's name under /proc/[pid]/map_files
+    pub inode: u64,
+    pub path: String,
+}
+
+// The path is everything after the fifth field, so names with spaces stay whole
+fn maps_path(line: &str) -> Option<&str> {
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        rest = &rest[rest.find(char::is_whitespace)?..];
+    }
+    Some(rest.trim_start())
+}
+
+// Unique file backed shared objects from /proc/[pid]/maps
+pub fn parse_mapped_libraries(maps: &str) -> Vec<MappedFile> {
+    let mut libraries: Vec<MappedFile> = maps
+        .lines()
+        .filter_map(|line| {
+            let path = maps_path(line)?;
+            let mut fields = line.split_whitespace();
+            let range = fields.next()?.to_owned();
+            let inode = fields.nth(3)?.parse().ok()?;
+            let is_library = path.starts_with('/') && path.contains(".so");
+            is_library.then(|| MappedFile { range, inode, path: path.to_owned() })
+        })
+        .collect();
+    libraries.sort_by(|a, b| a.path.cmp(&b.path));
+    libraries.dedup_by(|a, b| a.path == b.path);
+    libraries
+}
+
+// map_files opens the file that is actually mapped, even if it was deleted or replaced on disk,
+// but needs CAP_SYS_ADMIN. Without it the file on disk is only hashed if it is still the same inode.
+fn hash_mapping(pid: u32, library: &MappedFile, cache: &SharedHashCache) -> Result<String, String> {
+    let mapped = format!("/proc/{}/map_files/{}", pid, library.range);
+    if let Ok(hash) = sha256_cached(Path::new(&mapped), cache) {
+        return Ok(hash);
+    }
+    if library.path.ends_with(" (deleted)") {
+        return Err("deleted after it was loaded, reading the mapping needs CAP_SYS_ADMIN".into());
+    }
+    let path = Path::new(&library.path);
+    match FileKey::of(path) {
+        Ok(key) if key.ino != library.inode => Err("replaced on disk after it was loaded".into()),
+        Ok(_) => sha256_cached(path, cache).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// /proc/[pid]/exe still opens the original file if it was deleted or replaced on disk
+pub fn hash_process_files(pid: u32, cache: &SharedHashCache, with_packages: bool) -> io::Result<Vec<FileHash>> {
+    let exe_link = format!("/proc/{}/exe", pid);
+    let exe_path = fs::read_link(&exe_link)?.to_string_lossy().into_owned();
+    let mut hashes = vec![FileHash {
+        sha256: sha256_cached(Path::new(&exe_link), cache).map_err(|e| e.to_string()),
+        package: with_packages.then(|| find_package(&exe_path)),
+        path: exe_path,
+    }];
+
+    let maps = fs::read_to_string(format!("/proc/{}/maps", pid)).unwrap_or_default();
+    for library in parse_mapped_libraries(&maps) {
+        hashes.push(FileHash {
+            sha256: hash_mapping(pid, &library, cache),
+            package: with_packages.then(|| find_package(&library.path)),
+            path: library.path,
+        });
+    }
+    Ok(hashes)
+}
+
+// Every installed package lists its files in <name>.list, one path per line
+pub fn parse_dpkg_list(package: &str, content: &str, index: &mut HashMap<String, String>) {
+    for path in content.lines().filter(|l| !l.is_empty()) {
+        index.entry(path.to_owned()).or_insert_with(|| package.to_owned());
+    }
+}
+
+// Rebuilt when packages are installed, upgraded or removed, which replaces .list files
+// and so changes the directory's mtime
+type DpkgIndex = Arc<HashMap<String, String>>;
+
+fn dpkg_index() -> DpkgIndex {
+    static INDEX: Mutex<Option<(SystemTime, DpkgIndex)>> = Mutex::new(None);
+    let modified = fs::metadata(DPKG_INFO_DIR).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut cached = INDEX.lock().unwrap();
+    if let Some((built, index)) = cached.as_ref()
+        && *built == modified
+    {
+        return Arc::clone(index);
+    }
+    let mut index = HashMap::new();
+    for entry in fs::read_dir(DPKG_INFO_DIR).into_iter().flatten().flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(package) = file_name.strip_suffix(".list")
+            && let Ok(content) = fs::read_to_string(entry.path())
+        {
+            parse_dpkg_list(package, &content, &mut index);
+        }
+    }
+    let index = Arc::new(index);
+    *cached = Some((modified, Arc::clone(&index)));
+    index
+}
+
+// With merged /usr, /usr/bin/ls may be registered as /bin/ls and the other way around
+pub fn path_candidates(path: &str) -> Vec<String> {
+    let path = path.trim_end_matches(" (deleted)");
+    let mut candidates = vec![path.to_owned()];
+    if let Some(stripped) = path.strip_prefix("/usr") {
+        candidates.push(stripped.to_owned());
+    } else {
+        candidates.push(format!("/usr{}", path));
+    }
+    candidates
+}
+
+pub fn find_package(path: &str) -> Result<String, String> {
+    let candidates = path_candidates(path);
+    if Path::new(DPKG_INFO_DIR).is_dir() {
+        let index = dpkg_index();
+        return candidates
+            .iter()
+            .find_map(|c| index.get(c).cloned())
+            .ok_or_else(|| "not owned by any dpkg package".into());
+    }
+    // The rpm database is a sqlite/BerkeleyDB file, so ask rpm itself to read it
+    if Path::new("/var/lib/rpm").is_dir() {
+        for candidate in &candidates {
+            if let Ok(output) = Command::new("rpm").args(["-qf", candidate]).output()
+                && output.status.success()
+            {
+                return Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned());
+            }
+        }
+        return Err("not owned by any rpm package".into());
+    }
+    Err("no dpkg or rpm database found".into())
+}
+
+#[test]
+fn test_sha256_reader() {
+    assert_eq!(
+        sha256_reader(&b"abc"[..]).unwrap(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn test_parse_mapped_libraries_dedups() {
+    let maps = "55d0-55d1 r-xp 00000000 08:01 1 /usr/bin/bash\n\
+                7f00-7f01 r--p 00000000 08:01 2 /usr/lib/x86_64-linux-gnu/libc.so.6\n\
+                7f01-7f02 r-xp 00001000 08:01 2 /usr/lib/x86_64-linux-gnu/libc.so.6\n\
+                7f03-7f04 rw-p 00000000 00:00 0 \n\
+                7ffd-7ffe rw-p 00000000 00:00 0 [stack]\n";
+    let libraries = parse_mapped_libraries(maps);
+    assert_eq!(libraries.len(), 1);
+    assert_eq!(libraries[0].path, "/usr/lib/x86_64-linux-gnu/libc.so.6");
+    assert_eq!((libraries[0].range.as_str(), libraries[0].inode), ("7f00-7f01", 2));
+}
+
+#[test]
+fn test_parse_mapped_libraries_keeps_spaces_and_deleted() {
+    let maps = "7f00-7f01 r-xp 00000000 08:01 12345                      /opt/my app/libx.so (deleted)\n";
+    let libraries = parse_mapped_libraries(maps);
+    assert_eq!(libraries[0].path, "/opt/my app/libx.so (deleted)");
+    assert_eq!(libraries[0].inode, 12345);
+}
+
+#[test]
+fn test_hash_deleted_mapping() {
+    let path = crate::control::temp_path("libgone.so");
+    fs::write(&path, b"not really a library").unwrap();
+    let file = File::open(&path).unwrap();
+    use std::os::unix::io::AsRawFd;
+    let mapping = unsafe { libc::mmap(std::ptr::null_mut(), 20, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0) };
+    assert_ne!(mapping, libc::MAP_FAILED);
+    fs::remove_file(&path).unwrap();
+
+    let hashes = hash_process_files(std::process::id(), &SharedHashCache::default(), false).unwrap();
+    unsafe { libc::munmap(mapping, 20) };
+    let deleted = format!("{} (deleted)", path.display());
+    let entry = hashes.iter().find(|h| h.path == deleted).unwrap();
+    // Without CAP_SYS_ADMIN the mapping cannot be read, but it must never be taken from disk
+    match &entry.sha256 {
+        Ok(hash) => assert_eq!(*hash, sha256_reader(&b"not really a library"[..]).unwrap()),
+        Err(e) => assert!(e.contains("deleted"), "{}", e),
+    }
+}
+
+#[test]
+fn test_parse_dpkg_list_and_usrmerge_candidates() {
+    let mut index = HashMap::new();
+    parse_dpkg_list("coreutils", "/.\n/bin\n/bin/ls\n", &mut index);
+    assert_eq!(index.get("/bin/ls").map(String::as_str), Some("coreutils"));
+    assert_eq!(path_candidates("/usr/bin/ls"), vec!["/usr/bin/ls", "/bin/ls"]);
+    assert_eq!(path_candidates("/bin/ls (deleted)"), vec!["/bin/ls", "/usr/bin/ls"]);
+}
+
+#[test]
+fn test_hash_cache_reuses_entries() {
+    let cache: SharedHashCache = Arc::default();
+    let exe = Path::new("/proc/self/exe");
+    let first = sha256_cached(exe, &cache).unwrap();
+    assert_eq!(cache.lock().unwrap().entries.len(), 1);
+    assert_eq!(sha256_cached(exe, &cache).unwrap(), first);
+    assert_eq!(cache.lock().unwrap().entries.len(), 1);
+}
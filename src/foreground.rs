@@ -0,0 +1,103 @@
+use crate::process::{self, Stat};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq)]
+pub enum Foreground {
+    NoTty,
+    OtherTerminal { tty: String, is_foreground: bool },
+    SameTerminal, // the viewer can hand its own terminal over, like fg in a shell
+}
+
+// tty_nr packs the device number: major in bits 8-15, minor in bits 0-7 and 20-31
+pub fn tty_name(tty_nr: i32) -> String {
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+    match major {
+        136..=143 => format!("/dev/pts/{}", (major - 136) * 256 + minor),
+        4 if minor < 64 => format!("/dev/tty{}", minor),
+        4 => format!("/dev/ttyS{}", minor - 64),
+        _ => format!("tty {}:{}", major, minor),
+    }
+}
+
+pub fn plan(target: &Stat, own: &Stat) -> Foreground {
+    if target.tty_nr == 0 {
+        Foreground::NoTty
+    } else if target.tty_nr == own.tty_nr && target.session == own.session {
+        Foreground::SameTerminal
+    } else {
+        Foreground::OtherTerminal {
+            tty: tty_name(target.tty_nr),
+            is_foreground: target.pgrp == target.tpgid,
+        }
+    }
+}
+
+// Jobs are stopped and continued as a whole process group
+pub fn continue_group(pgrp: i32) -> io::Result<()> {
+    if unsafe { libc::kill(-pgrp, libc::SIGCONT) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Gives the terminal to the job and blocks until it exits or stops again, then takes it back.
+// SIGTTOU is ignored meanwhile, tcsetpgrp from a background group would stop the viewer.
+pub fn hand_over_terminal(pid: u32, pgrp: i32) -> io::Result<()> {
+    let own_pgrp = unsafe { libc::getpgrp() };
+    let previous = unsafe { libc::signal(libc::SIGTTOU, libc::SIG_IGN) };
+    let result = (|| {
+        if unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, pgrp) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        continue_group(pgrp)?;
+        loop {
+            thread::sleep(Duration::from_millis(100));
+            let still_running = process::read_stat(pid).is_some_and(|stat| !matches!(stat.state, 'T' | 't' | 'Z'));
+            let still_foreground = unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } == pgrp;
+            if !still_running || !still_foreground {
+                return Ok(());
+            }
+        }
+    })();
+    unsafe {
+        libc::tcsetpgrp(libc::STDIN_FILENO, own_pgrp);
+        libc::signal(libc::SIGTTOU, previous);
+    }
+    result
+}
+
+#[cfg(test)]
+fn stat(tty_nr: i32, session: i32, pgrp: i32, tpgid: i32) -> Stat {
+    Stat {
+        tty_nr,
+        session,
+        pgrp,
+        tpgid,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_tty_name() {
+    assert_eq!(tty_name(34816), "/dev/pts/0");
+    assert_eq!(tty_name(34819), "/dev/pts/3");
+    assert_eq!(tty_name(1025), "/dev/tty1");
+    assert_eq!(tty_name(1088), "/dev/ttyS0");
+}
+
+#[test]
+fn test_plan() {
+    let own = stat(34816, 100, 200, 200);
+    assert_eq!(plan(&stat(0, 1, 1, -1), &own), Foreground::NoTty);
+    assert_eq!(plan(&stat(34816, 100, 150, 200), &own), Foreground::SameTerminal);
+    assert_eq!(
+        plan(&stat(34819, 300, 300, 300), &own),
+        Foreground::OtherTerminal {
+            tty: "/dev/pts/3".into(),
+            is_foreground: true
+        }
+    );
+}
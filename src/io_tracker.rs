@@ -0,0 +1,140 @@
+use crate::process::ProcessInfo;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct IoStats {
+    pub rchar: u64,
+    pub wchar: u64,
+    pub syscr: u64,
+    pub syscw: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub cancelled_write_bytes: u64,
+}
+
+impl IoStats {
+    // Only readable for own processes unless running as root
+    pub fn read(pid: u32) -> io::Result<Self> {
+        let content = fs::read_to_string(format!("/proc/{}/io", pid))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut stats = Self::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().parse::<u64>().unwrap_or(0);
+            match key {
+                "rchar" => stats.rchar = value,
+                "wchar" => stats.wchar = value,
+                "syscr" => stats.syscr = value,
+                "syscw" => stats.syscw = value,
+                "read_bytes" => stats.read_bytes = value,
+                "write_bytes" => stats.write_bytes = value,
+                "cancelled_write_bytes" => stats.cancelled_write_bytes = value,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+// Storage level throughput, like iotop cancelled writes (truncated dirty pages) are subtracted
+pub struct IoTracker {
+    pub last_proc_io: HashMap<u32, (IoStats, Instant)>,
+}
+
+impl IoTracker {
+    pub fn new() -> Self {
+        Self {
+            last_proc_io: HashMap::new(),
+        }
+    }
+
+    pub fn update_process_io(&mut self, processes: &mut [ProcessInfo]) {
+        let now = Instant::now();
+        let mut seen = HashMap::with_capacity(processes.len());
+
+        for proc in processes.iter_mut() {
+            let Some(io) = proc.io else {
+                proc.read_rate = None;
+                proc.write_rate = None;
+                continue;
+            };
+
+            let (read_rate, write_rate) = match self.last_proc_io.get(&proc.pid) {
+                Some((previous, last_check)) => {
+                    let elapsed = now.duration_since(*last_check).as_secs_f64();
+                    if elapsed > 0.0 {
+                        let written = io.write_bytes.saturating_sub(io.cancelled_write_bytes);
+                        let previous_written =
+                            previous.write_bytes.saturating_sub(previous.cancelled_write_bytes);
+                        (
+                            io.read_bytes.saturating_sub(previous.read_bytes) as f64 / elapsed,
+                            written.saturating_sub(previous_written) as f64 / elapsed,
+                        )
+                    } else {
+                        (0.0, 0.0)
+                    }
+                }
+                None => (0.0, 0.0),
+            };
+
+            proc.read_rate = Some(read_rate);
+            proc.write_rate = Some(write_rate);
+            seen.insert(proc.pid, (io, now));
+        }
+
+        self.last_proc_io = seen;
+    }
+}
+
+#[test]
+fn test_parse_io_stats() {
+    let content = "rchar: 323934931\nwchar: 323929600\nsyscr: 632687\nsyscw: 632675\nread_bytes: 4096\nwrite_bytes: 323932160\ncancelled_write_bytes: 0\n";
+    let stats = IoStats::parse(content);
+    assert_eq!(stats.rchar, 323934931);
+    assert_eq!(stats.syscw, 632675);
+    assert_eq!(stats.read_bytes, 4096);
+    assert_eq!(stats.write_bytes, 323932160);
+}
+
+#[test]
+fn test_update_process_io_calculates_rates() {
+    let mut tracker = IoTracker::new();
+    let earlier = Instant::now() - std::time::Duration::from_secs(2);
+    let previous = IoStats {
+        read_bytes: 1000,
+        ..Default::default()
+    };
+    tracker.last_proc_io.insert(1, (previous, earlier));
+
+    let mut processes = vec![ProcessInfo::new(1, "reader".into(), 0, 0)];
+    processes[0].io = Some(IoStats {
+        read_bytes: 5000,
+        ..Default::default()
+    });
+
+    tracker.update_process_io(&mut processes);
+
+    let read_rate = processes[0].read_rate.unwrap();
+    assert!(read_rate > 1000.0 && read_rate <= 2000.0);
+    assert_eq!(processes[0].write_rate, Some(0.0));
+}
+
+#[test]
+fn test_update_process_io_unreadable_stays_unknown() {
+    let mut tracker = IoTracker::new();
+    let mut processes = vec![ProcessInfo::new(2, "other_user".into(), 0, 0)];
+
+    tracker.update_process_io(&mut processes);
+
+    assert_eq!(processes[0].read_rate, None);
+    assert!(!tracker.last_proc_io.contains_key(&2));
+}
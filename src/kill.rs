@@ -0,0 +1,203 @@
+use crate::process::{self, ProcessInfo};
+use crate::suspend;
+use serde::Serialize;
+
+pub const SIGNALS: [(i32, &str); 4] = [
+    (libc::SIGTERM, "SIGTERM"),
+    (libc::SIGKILL, "SIGKILL"),
+    (libc::SIGINT, "SIGINT"),
+    (libc::SIGHUP, "SIGHUP"),
+];
+
+// Names accepted from outside, e.g. by the HTTP control endpoints
+const NAMED_SIGNALS: [(i32, &str); 14] = [
+    (libc::SIGHUP, "SIGHUP"),
+    (libc::SIGINT, "SIGINT"),
+    (libc::SIGQUIT, "SIGQUIT"),
+    (libc::SIGABRT, "SIGABRT"),
+    (libc::SIGKILL, "SIGKILL"),
+    (libc::SIGUSR1, "SIGUSR1"),
+    (libc::SIGSEGV, "SIGSEGV"),
+    (libc::SIGUSR2, "SIGUSR2"),
+    (libc::SIGPIPE, "SIGPIPE"),
+    (libc::SIGALRM, "SIGALRM"),
+    (libc::SIGTERM, "SIGTERM"),
+    (libc::SIGCONT, "SIGCONT"),
+    (libc::SIGSTOP, "SIGSTOP"),
+    (libc::SIGTSTP, "SIGTSTP"),
+];
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TreeOrder {
+    ChildrenFirst, // parents cannot respawn children that are already gone
+    ParentFirst,   // children are not reparented to init while the parent still runs
+}
+
+impl TreeOrder {
+    pub fn toggle(&self) -> Self {
+        match self {
+            TreeOrder::ChildrenFirst => TreeOrder::ParentFirst,
+            TreeOrder::ParentFirst => TreeOrder::ChildrenFirst,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TreeOrder::ChildrenFirst => "children first",
+            TreeOrder::ParentFirst => "parent first",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KillTarget {
+    pub pid: u32,
+    pub name: String,
+    pub starttime: u64, // a pid reused while the preview was open is not signalled
+}
+
+impl KillTarget {
+    pub fn of(process: &ProcessInfo) -> Self {
+        KillTarget {
+            pid: process.pid,
+            name: process.name.clone(),
+            starttime: process.stat.starttime,
+        }
+    }
+}
+
+// Processes about to be signalled, shown for confirmation first
+#[derive(Debug, Clone, PartialEq)]
+pub struct KillPreview {
+    pub description: String,
+    pub root: Option<u32>, // set for a process tree, whose order can still be changed
+    pub targets: Vec<KillTarget>,
+    pub signal: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignalOutcome {
+    pub pid: u32,
+    pub name: String,
+    pub result: Result<(), String>,
+}
+
+pub fn signal_name(signal: i32) -> &'static str {
+    NAMED_SIGNALS
+        .iter()
+        .find(|(s, _)| *s == signal)
+        .map(|(_, name)| *name)
+        .unwrap_or("signal")
+}
+
+// TERM, SIGTERM and sigterm all name the same signal
+pub fn signal_number(name: &str) -> Option<i32> {
+    let upper = name.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    NAMED_SIGNALS
+        .iter()
+        .find(|(_, n)| n[3..] == *name)
+        .map(|(signal, _)| *signal)
+}
+
+pub fn next_signal(signal: i32) -> i32 {
+    let index = SIGNALS.iter().position(|(s, _)| *s == signal).unwrap_or(0);
+    SIGNALS[(index + 1) % SIGNALS.len()].0
+}
+
+pub fn tree_targets(processes: &[ProcessInfo], pid: u32, order: TreeOrder) -> Vec<KillTarget> {
+    let mut pids = vec![pid];
+    pids.extend(process::descendants(processes, pid));
+    if order == TreeOrder::ChildrenFirst {
+        pids.reverse();
+    }
+    let own_pid = std::process::id();
+    pids.into_iter()
+        .filter(|pid| *pid != own_pid)
+        .filter_map(|pid| processes.iter().find(|p| p.pid == pid).map(KillTarget::of))
+        .collect()
+}
+
+// Like pkill; neither this nor a tree ever includes the viewer itself
+pub fn matching_targets(processes: &[ProcessInfo]) -> Vec<KillTarget> {
+    let own_pid = std::process::id();
+    processes.iter().filter(|p| p.pid != own_pid).map(KillTarget::of).collect()
+}
+
+fn signal_target(target: &KillTarget, signal: i32) -> Result<(), String> {
+    let stat = process::read_stat(target.pid).ok_or("process has exited")?;
+    if stat.starttime != target.starttime {
+        return Err("process has exited and its pid was reused".into());
+    }
+    suspend::send_signal(target.pid, signal).map_err(|e| e.to_string())
+}
+
+pub fn signal_all(targets: &[KillTarget], signal: i32) -> Vec<SignalOutcome> {
+    targets
+        .iter()
+        .map(|target| SignalOutcome {
+            pid: target.pid,
+            name: target.name.clone(),
+            result: signal_target(target, signal),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn tree() -> Vec<ProcessInfo> {
+    let mut procs = vec![
+        ProcessInfo::new(10, "make".into(), 0, 0),
+        ProcessInfo::new(11, "cc".into(), 0, 0),
+        ProcessInfo::new(12, "as".into(), 0, 0),
+        ProcessInfo::new(13, "cc".into(), 0, 0),
+    ];
+    procs[1].stat.ppid = 10;
+    procs[2].stat.ppid = 11;
+    procs[3].stat.ppid = 10;
+    procs
+}
+
+#[test]
+fn test_tree_targets_order() {
+    let pids = |order| -> Vec<u32> { tree_targets(&tree(), 10, order).into_iter().map(|t| t.pid).collect() };
+    assert_eq!(pids(TreeOrder::ParentFirst), vec![10, 11, 13, 12]);
+    assert_eq!(pids(TreeOrder::ChildrenFirst), vec![12, 13, 11, 10]);
+}
+
+#[test]
+fn test_matching_targets_skips_self() {
+    let mut procs = tree();
+    procs.push(ProcessInfo::new(std::process::id(), "task-manager".into(), 0, 0));
+    assert_eq!(matching_targets(&procs).len(), 4);
+}
+
+#[test]
+fn test_signal_all_reports_failures() {
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let stat = process::read_stat(child.id()).unwrap();
+    let target = |pid, starttime| KillTarget { pid, name: "sleep".into(), starttime };
+    let targets = vec![
+        target(child.id(), stat.starttime + 1),
+        target(child.id(), stat.starttime),
+        target(u32::MAX / 2, 0),
+    ];
+    let outcomes = signal_all(&targets, libc::SIGTERM);
+    assert_eq!(outcomes[0].result, Err("process has exited and its pid was reused".into()));
+    assert_eq!(outcomes[1].result, Ok(()));
+    assert!(outcomes[2].result.is_err());
+    child.wait().unwrap();
+}
+
+#[test]
+fn test_next_signal_cycles() {
+    assert_eq!(signal_name(next_signal(libc::SIGTERM)), "SIGKILL");
+    assert_eq!(next_signal(libc::SIGHUP), libc::SIGTERM);
+}
+
+#[test]
+fn test_signal_number() {
+    assert_eq!(signal_number("TERM"), Some(libc::SIGTERM));
+    assert_eq!(signal_number("sigusr1"), Some(libc::SIGUSR1));
+    assert_eq!(signal_number("SIGBOGUS"), None);
+    assert_eq!(signal_name(libc::SIGCONT), "SIGCONT");
+}
@@ -0,0 +1,335 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// What the run prompt accepts: [--cwd=DIR] [--nice=N] [--cpus=LIST] [KEY=VALUE ...] program [args ...]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaunchSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub nice: Option<i32>,
+    pub cpus: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputLine {
+    pub stream: Stream,
+    pub text: String,
+}
+
+// Only the newest lines are kept, so a chatty command cannot grow the viewer without bound
+pub const MAX_OUTPUT_LINES: usize = 10_000;
+
+// Longer lines are split, so output without newlines cannot grow one line without bound
+pub const MAX_LINE_BYTES: usize = 4096;
+
+pub type SharedOutput = Arc<Mutex<VecDeque<OutputLine>>>;
+
+pub struct RunningCommand {
+    pub pid: u32,
+    pub command_line: String,
+    pub output: SharedOutput,
+    pub status: Option<ExitStatus>,
+    child: Child,
+    capture_threads: Vec<JoinHandle<()>>,
+}
+
+impl RunningCommand {
+    // Also reaps the child, so finished commands do not linger as zombies
+    pub fn poll(&mut self) {
+        if self.status.is_none() {
+            self.status = self.child.try_wait().ok().flatten();
+        }
+        self.capture_threads.retain(|handle| !handle.is_finished());
+    }
+
+    pub fn status_label(&self) -> String {
+        match self.status {
+            None => "running".into(),
+            Some(status) => match status.code() {
+                Some(code) => format!("exited with status {}", code),
+                None => format!("killed by {}", status),
+            },
+        }
+    }
+
+    // Blocks until both pipes are closed and every line is in output
+    #[cfg(test)]
+    fn wait_for_output(&mut self) {
+        for handle in self.capture_threads.drain(..) {
+            handle.join().unwrap();
+        }
+    }
+}
+
+// Splits like a shell: whitespace separates words, quotes group them, backslash escapes
+pub fn tokenize(input: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                word.push(chars.next().ok_or("trailing backslash")?);
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".into());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+// "0,2-3" -> [0, 2, 3], the format of taskset -c and Cpus_allowed_list.
+// CPUs past CPU_SETSIZE cannot be put in a cpu_set_t, so they are refused here.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, String> {
+    let mut cpus = vec![];
+    for part in list.split(',').filter(|p| !p.is_empty()) {
+        let invalid = || format!("invalid CPU list '{}'", list);
+        let (from, to): (usize, usize) = match part.split_once('-') {
+            Some((from, to)) => (from.parse().map_err(|_| invalid())?, to.parse().map_err(|_| invalid())?),
+            None => {
+                let cpu = part.parse().map_err(|_| invalid())?;
+                (cpu, cpu)
+            }
+        };
+        if from > to {
+            return Err(invalid());
+        }
+        if to >= libc::CPU_SETSIZE as usize {
+            return Err(format!("CPU {} is out of range, the limit is {}", to, libc::CPU_SETSIZE - 1));
+        }
+        cpus.extend(from..=to);
+    }
+    if cpus.is_empty() {
+        return Err("empty CPU list".into());
+    }
+    Ok(cpus)
+}
+
+fn is_env_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((key, _)) => !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        None => false,
+    }
+}
+
+pub fn parse_command_line(input: &str) -> Result<LaunchSpec, String> {
+    let mut spec = LaunchSpec::default();
+    let mut words = tokenize(input)?.into_iter();
+    for word in words.by_ref() {
+        if let Some(cwd) = word.strip_prefix("--cwd=") {
+            spec.cwd = Some(cwd.to_owned());
+        } else if let Some(nice) = word.strip_prefix("--nice=") {
+            spec.nice = Some(nice.parse().map_err(|_| format!("invalid nice level '{}'", nice))?);
+        } else if let Some(cpus) = word.strip_prefix("--cpus=") {
+            spec.cpus = Some(parse_cpu_list(cpus)?);
+        } else if is_env_assignment(&word) {
+            let (key, value) = word.split_once('=').unwrap_or_default();
+            spec.env.push((key.to_owned(), value.to_owned()));
+        } else {
+            spec.program = word;
+            break;
+        }
+    }
+    if spec.program.is_empty() {
+        return Err("no program given".into());
+    }
+    spec.args = words.collect();
+    Ok(spec)
+}
+
+fn push_line(output: &mut VecDeque<OutputLine>, line: OutputLine) {
+    if output.len() == MAX_OUTPUT_LINES {
+        output.pop_front();
+    }
+    output.push_back(line);
+}
+
+// Reads until EOF whatever arrives: a stopped reader would leave the child blocked on a full pipe.
+// Invalid UTF-8 is replaced, and an overlong line is cut into pieces of MAX_LINE_BYTES.
+fn capture(reader: impl Read + Send + 'static, stream: Stream, output: SharedOutput) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader).take(MAX_LINE_BYTES as u64);
+        let mut bytes = vec![];
+        loop {
+            bytes.clear();
+            reader.set_limit(MAX_LINE_BYTES as u64);
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = bytes.strip_suffix(b"\n").unwrap_or(&bytes);
+                    let text = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned();
+                    push_line(&mut output.lock().unwrap(), OutputLine { stream, text });
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+pub fn spawn(spec: &LaunchSpec, command_line: &str) -> io::Result<RunningCommand> {
+    let mut command = Command::new(&spec.program);
+    command
+        .args(&spec.args)
+        .envs(spec.env.iter().cloned())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = &spec.cwd {
+        command.current_dir(cwd);
+    }
+    let nice = spec.nice;
+    let cpus = spec.cpus.clone();
+    // Runs in the forked child before exec, so only the new process is affected
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(nice) = nice
+                && libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            if let Some(cpus) = &cpus {
+                let mut set: libc::cpu_set_t = std::mem::zeroed();
+                // parse_cpu_list already refuses these, but CPU_SET would index past the set
+                for cpu in cpus.iter().filter(|cpu| **cpu < libc::CPU_SETSIZE as usize) {
+                    libc::CPU_SET(*cpu, &mut set);
+                }
+                if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = command.spawn()?;
+    let output = SharedOutput::default();
+    let mut capture_threads = vec![];
+    if let Some(stdout) = child.stdout.take() {
+        capture_threads.push(capture(stdout, Stream::Stdout, output.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        capture_threads.push(capture(stderr, Stream::Stderr, output.clone()));
+    }
+    Ok(RunningCommand {
+        pid: child.id(),
+        command_line: command_line.to_owned(),
+        output,
+        status: None,
+        child,
+        capture_threads,
+    })
+}
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(
+        tokenize(r#"echo "hello world" 'a b' c\ d"#).unwrap(),
+        vec!["echo", "hello world", "a b", "c d"]
+    );
+    assert_eq!(tokenize("  ").unwrap(), Vec::<String>::new());
+    assert_eq!(tokenize("printf ''").unwrap(), vec!["printf", ""]);
+    assert!(tokenize("echo 'open").is_err());
+}
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("0,2-3").unwrap(), vec![0, 2, 3]);
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("x").is_err());
+    assert!(parse_cpu_list("0-18446744073709551615").is_err());
+    assert!(parse_cpu_list("4000000000").is_err());
+    assert_eq!(parse_cpu_list("1023").unwrap(), vec![1023]);
+}
+
+#[test]
+fn test_parse_command_line() {
+    let spec = parse_command_line("--cwd=/tmp --nice=5 --cpus=0 RUST_LOG=debug cargo build --release").unwrap();
+    assert_eq!(spec.cwd.as_deref(), Some("/tmp"));
+    assert_eq!(spec.nice, Some(5));
+    assert_eq!(spec.cpus, Some(vec![0]));
+    assert_eq!(spec.env, vec![("RUST_LOG".to_string(), "debug".to_string())]);
+    assert_eq!(spec.program, "cargo");
+    assert_eq!(spec.args, vec!["build", "--release"]);
+    assert!(parse_command_line("FOO=bar").is_err());
+}
+
+#[test]
+fn test_spawn_applies_settings_and_captures_output() {
+    let line = "--cwd=/ --nice=5 --cpus=0 GREETING=hi sh -c 'echo $GREETING; pwd; nice; grep Cpus_allowed_list /proc/self/status; echo oops >&2'";
+    let spec = parse_command_line(line).unwrap();
+    let mut running = spawn(&spec, line).unwrap();
+    while running.status.is_none() {
+        running.poll();
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(running.status_label(), "exited with status 0");
+    running.wait_for_output();
+    let output = running.output.lock().unwrap();
+    let stdout: Vec<&str> = output
+        .iter()
+        .filter(|l| l.stream == Stream::Stdout)
+        .map(|l| l.text.as_str())
+        .collect();
+    assert_eq!(stdout[..3], ["hi", "/", "5"]);
+    assert!(stdout[3].ends_with("\t0"));
+    assert!(output.contains(&OutputLine { stream: Stream::Stderr, text: "oops".into() }));
+}
+
+#[test]
+fn test_capture_survives_binary_and_long_lines() {
+    let mut input = b"ok\n\xff\xfe bytes\r\n".to_vec();
+    input.extend(std::iter::repeat_n(b'x', MAX_LINE_BYTES + 10));
+    let output = SharedOutput::default();
+    capture(io::Cursor::new(input), Stream::Stdout, output.clone()).join().unwrap();
+    let lines: Vec<String> = output.lock().unwrap().iter().map(|l| l.text.clone()).collect();
+    assert_eq!(lines[..2], ["ok", "\u{fffd}\u{fffd} bytes"]);
+    assert_eq!(lines[2].len(), MAX_LINE_BYTES);
+    assert_eq!(lines[3], "x".repeat(10));
+}
+
+#[test]
+fn test_output_keeps_newest_lines() {
+    let mut output = VecDeque::new();
+    for i in 0..MAX_OUTPUT_LINES + 5 {
+        push_line(&mut output, OutputLine { stream: Stream::Stdout, text: i.to_string() });
+    }
+    assert_eq!(output.len(), MAX_OUTPUT_LINES);
+    assert_eq!(output.front().unwrap().text, "5");
+    assert_eq!(output.back().unwrap().text, (MAX_OUTPUT_LINES + 4).to_string());
+}
@@ -0,0 +1,222 @@
+use crate::process::Status;
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+// An int as in POSIX; glibc declares prlimit with its own enum type, see set_soft_limit
+pub type Resource = libc::c_int;
+
+// Row names in /proc/[pid]/limits, in kernel order
+const RESOURCES: [(&str, Resource); 16] = [
+    ("Max cpu time", libc::RLIMIT_CPU as Resource),
+    ("Max file size", libc::RLIMIT_FSIZE as Resource),
+    ("Max data size", libc::RLIMIT_DATA as Resource),
+    ("Max stack size", libc::RLIMIT_STACK as Resource),
+    ("Max core file size", libc::RLIMIT_CORE as Resource),
+    ("Max resident set", libc::RLIMIT_RSS as Resource),
+    ("Max processes", libc::RLIMIT_NPROC as Resource),
+    ("Max open files", libc::RLIMIT_NOFILE as Resource),
+    ("Max locked memory", libc::RLIMIT_MEMLOCK as Resource),
+    ("Max address space", libc::RLIMIT_AS as Resource),
+    ("Max file locks", libc::RLIMIT_LOCKS as Resource),
+    ("Max pending signals", libc::RLIMIT_SIGPENDING as Resource),
+    ("Max msgqueue size", libc::RLIMIT_MSGQUEUE as Resource),
+    ("Max nice priority", libc::RLIMIT_NICE as Resource),
+    ("Max realtime priority", libc::RLIMIT_RTPRIO as Resource),
+    ("Max realtime timeout", libc::RLIMIT_RTTIME as Resource),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResourceLimit {
+    pub name: String,
+    #[serde(skip)]
+    pub resource: Option<Resource>,
+    pub soft: Option<u64>, // None is unlimited
+    pub hard: Option<u64>,
+    pub units: String,
+    pub usage: Option<u64>, // current consumption in the same units, where the kernel exposes it
+}
+
+impl ResourceLimit {
+    pub fn usage_percent(&self) -> Option<f64> {
+        match (self.usage, self.soft) {
+            (Some(usage), Some(soft)) if soft > 0 => Some(usage as f64 * 100.0 / soft as f64),
+            _ => None,
+        }
+    }
+}
+
+fn resource_for(name: &str) -> Option<Resource> {
+    RESOURCES.iter().find(|(n, _)| *n == name).map(|(_, r)| *r)
+}
+
+fn parse_value(value: &str) -> Option<u64> {
+    value.parse().ok()
+}
+
+// "unlimited" or a plain number, as accepted by the editor
+pub fn parse_limit_value(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("unlimited") {
+        return Ok(libc::RLIM_INFINITY);
+    }
+    input
+        .parse()
+        .map_err(|_| format!("'{}' is not a number or 'unlimited'", input))
+}
+
+pub fn format_limit(value: Option<u64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "unlimited".into())
+}
+
+// Columns are fixed width, the header tells where soft, hard and units start
+pub fn parse_limits(content: &str) -> Vec<ResourceLimit> {
+    let mut lines = content.lines();
+    let Some(header) = lines.next() else {
+        return vec![];
+    };
+    let (Some(soft_at), Some(hard_at), Some(units_at)) =
+        (header.find("Soft Limit"), header.find("Hard Limit"), header.find("Units"))
+    else {
+        return vec![];
+    };
+
+    lines
+        .filter(|line| line.len() > hard_at)
+        .map(|line| {
+            let field = |from: usize, to: usize| line.get(from..to.min(line.len())).unwrap_or("").trim();
+            let name = field(0, soft_at).to_string();
+            ResourceLimit {
+                resource: resource_for(&name),
+                soft: parse_value(field(soft_at, hard_at)),
+                hard: parse_value(field(hard_at, units_at)),
+                units: field(units_at, line.len()).to_string(),
+                usage: None,
+                name,
+            }
+        })
+        .collect()
+}
+
+// RLIMIT_NPROC counts every thread of the real user, not just this process
+fn count_user_threads(uid: u32) -> Option<u64> {
+    let mut threads = 0;
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        if !entry.file_name().to_string_lossy().chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let Ok(status) = Status::read_from(&entry.path().join("status")) else {
+            continue;
+        };
+        if status.real_uid() == Some(uid) {
+            threads += status.number("Threads").unwrap_or(1);
+        }
+    }
+    Some(threads)
+}
+
+// SigQ is "queued/limit"
+fn pending_signals(status: &Status) -> Option<u64> {
+    status.value("SigQ")?.split('/').next()?.parse().ok()
+}
+
+pub fn fill_usage(limits: &mut [ResourceLimit], status: &Status, open_fds: Option<u64>) {
+    for limit in limits.iter_mut() {
+        limit.usage = match limit.name.as_str() {
+            "Max open files" => open_fds,
+            "Max processes" => status.real_uid().and_then(count_user_threads),
+            "Max stack size" => status.bytes("VmStk"),
+            "Max address space" => status.bytes("VmSize"),
+            "Max data size" => status.bytes("VmData"),
+            "Max resident set" => status.bytes("VmRSS"),
+            "Max locked memory" => status.bytes("VmLck"),
+            "Max pending signals" => pending_signals(status),
+            _ => None,
+        };
+    }
+}
+
+pub fn read_limits(pid: u32) -> io::Result<Vec<ResourceLimit>> {
+    let mut limits = parse_limits(&fs::read_to_string(format!("/proc/{}/limits", pid))?);
+    let status = Status::read(pid).unwrap_or_default();
+    let open_fds = fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|entries| entries.count() as u64);
+    fill_usage(&mut limits, &status, open_fds);
+    Ok(limits)
+}
+
+// Changes only the soft limit, the hard limit stays as it is
+pub fn set_soft_limit(pid: u32, resource: Resource, soft: u64) -> io::Result<()> {
+    #[cfg(target_env = "gnu")]
+    let resource = resource as libc::__rlimit_resource_t;
+    let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::prlimit(pid as libc::pid_t, resource, std::ptr::null(), &mut current) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if soft > current.rlim_max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("soft limit cannot exceed the hard limit ({})", format_limit(finite(current.rlim_max))),
+        ));
+    }
+    let new = libc::rlimit { rlim_cur: soft, rlim_max: current.rlim_max };
+    if unsafe { libc::prlimit(pid as libc::pid_t, resource, &new, std::ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn finite(value: u64) -> Option<u64> {
+    (value != libc::RLIM_INFINITY).then_some(value)
+}
+
+#[cfg(test)]
+const SAMPLE_LIMITS: &str = "\
+Limit                     Soft Limit           Hard Limit           Units     \n\
+Max cpu time              unlimited            unlimited            seconds   \n\
+Max stack size            8388608              unlimited            bytes     \n\
+Max processes             63459                63459                processes \n\
+Max open files            1024                 524288               files     \n";
+
+#[test]
+fn test_parse_limits() {
+    let limits = parse_limits(SAMPLE_LIMITS);
+    assert_eq!(limits.len(), 4);
+    assert_eq!(limits[0].name, "Max cpu time");
+    assert_eq!(limits[0].soft, None);
+    assert_eq!(limits[1].soft, Some(8388608));
+    assert_eq!(limits[1].units, "bytes");
+    assert_eq!(limits[3].resource, Some(libc::RLIMIT_NOFILE as Resource));
+    assert_eq!(limits[3].hard, Some(524288));
+}
+
+#[test]
+fn test_fill_usage_from_status() {
+    let mut limits = parse_limits(SAMPLE_LIMITS);
+    let status = Status::parse("Name:\tnginx\nVmStk:\t     132 kB\nSigQ:\t2/63459\n");
+    fill_usage(&mut limits, &status, Some(768));
+    assert_eq!(limits[1].usage, Some(132 * 1024));
+    assert_eq!(limits[3].usage, Some(768));
+    assert_eq!(limits[3].usage_percent(), Some(75.0));
+    assert_eq!(limits[0].usage, None);
+}
+
+#[test]
+fn test_parse_limit_value() {
+    assert_eq!(parse_limit_value(" 4096 "), Ok(4096));
+    assert_eq!(parse_limit_value("unlimited"), Ok(libc::RLIM_INFINITY));
+    assert!(parse_limit_value("lots").is_err());
+}
+
+#[test]
+fn test_set_own_soft_limit() {
+    let own = read_limits(std::process::id()).unwrap();
+    let nofile = own.iter().find(|l| l.resource == Some(libc::RLIMIT_NOFILE as Resource)).unwrap();
+    let soft = nofile.soft.unwrap_or(libc::RLIM_INFINITY);
+    // Setting the current value is a no-op that still goes through prlimit
+    set_soft_limit(std::process::id(), libc::RLIMIT_NOFILE as Resource, soft).unwrap();
+    if let Some(hard) = nofile.hard {
+        assert!(set_soft_limit(std::process::id(), libc::RLIMIT_NOFILE as Resource, hard + 1).is_err());
+    }
+}
@@ -1,56 +1,148 @@
+mod api;
 mod app;
+mod batch;
+mod cgroup;
+mod columns;
+mod control;
 mod cpu_tracker;
+mod daemon;
+mod details;
+mod environ;
+mod exe_hash;
+mod foreground;
+mod io_tracker;
+mod kill;
+mod launcher;
+mod limits;
+mod metrics;
+mod namespaces;
+mod oom;
 mod process;
+mod profiler;
+mod psi;
+mod security;
+mod stream;
+mod suspend;
+mod suspicious;
+mod system_stats;
 mod tui;
+mod web_tui;
 mod webserver;
 
 use app::App;
-use app::AppState;
 use cpu_tracker::CpuTracker;
+use io_tracker::IoTracker;
 use std::io;
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use suspicious::SuspicionScanner;
+use system_stats::SystemTracker;
 use tui::Tui;
+use web_tui::SharedFrameHub;
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("run") {
+        return profiler::run_cli(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("batch") {
+        return batch::run_cli(&args[1..]);
+    }
+    let server_options = match webserver::parse_server_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, webserver::USAGE);
+            std::process::exit(2);
+        }
+    };
+    // Refusing to start beats silently running without the requested control endpoints
+    let control = match webserver::load_control(&server_options) {
+        Ok(control) => control,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let app_arc = Arc::new(Mutex::new(App::new()));
-    let app_for_refresh = Arc::clone(&app_arc);
-    let cpu_tracker = CpuTracker::new();
+    let stream_hub = stream::SharedStreamHub::default();
+    spawn_collector(Arc::clone(&app_arc), Arc::clone(&stream_hub));
+    if server_options.headless {
+        if let Err(e) = daemon::run(app_arc, stream_hub, &server_options, control) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
     let app_for_http = Arc::clone(&app_arc);
-    webserver::start_http_server(app_for_http);
+    let frame_hub = SharedFrameHub::default();
+    let (key_tx, key_rx) = mpsc::channel();
+    // The viewer is still useful without its web server, so a failed bind only shows in the status line
+    let http_status = webserver::start_http_server(
+        app_for_http,
+        Arc::clone(&stream_hub),
+        Arc::clone(&frame_hub),
+        Some(key_tx),
+        &server_options,
+        control,
+    );
+    app_arc.lock().unwrap().status_message = match http_status {
+        Ok(Some(listening)) => Some(Ok(format!("Web server listening on {}", listening))),
+        Ok(None) => None,
+        Err(e) => Some(Err(format!("Web server not started: {}", e))),
+    };
     let mut tui = Tui::new()?;
 
-    std::thread::spawn(move || {
-        let mut cpu_tracker = cpu_tracker;
-        loop {
-            {
-                let mut app = app_for_refresh.lock().unwrap();
-                if let Ok(_) = process::Processes::fetch_process_list() {
-                    app.reload_processes();
-                    cpu_tracker.update_process_cpu(&mut app.processes);
-                }
-            }
-            std::thread::sleep(Duration::from_millis(1000));
-        }
-    });
-
     loop {
         {
             let mut app = app_arc.lock().unwrap();
-            tui.draw(&mut app)?;
-            let result = match app.state {
-                AppState::Normal => tui.handle_input_normal(&mut app),
-                AppState::Filtering => tui.handle_input_filtering(&mut app),
-                AppState::ProcessMenu => tui.handle_input_processmenu(&mut app),
-            };
+            let buffer = tui.draw(&mut app)?;
+            frame_hub.lock().unwrap().publish(buffer);
+            // Keys from the browser never end the session, only the local terminal can quit
+            while let Ok(key) = key_rx.try_recv() {
+                let _ = tui.handle_key(&mut app, key);
+            }
+            let result = tui.handle_input(&mut app);
 
             if result.is_err() {
                 break;
             }
         }
+        if tui.hand_over_terminal(&app_arc).is_err() {
+            break;
+        }
         std::thread::sleep(Duration::from_millis(10));
     }
+    let resume_errors = app_arc.lock().unwrap().resume_all_paused();
     tui.cleanup()?;
+    for error in resume_errors {
+        eprintln!("Could not resume {}", error);
+    }
     Ok(())
 }
+
+// Refreshes App and feeds the event stream once a second, for as long as the program runs
+fn spawn_collector(app: Arc<Mutex<App>>, stream_hub: stream::SharedStreamHub) {
+    let mut cpu_tracker = CpuTracker::new();
+    let mut io_tracker = IoTracker::new();
+    let mut system_tracker = SystemTracker::new();
+    let mut suspicion_scanner = SuspicionScanner::new();
+    std::thread::spawn(move || loop {
+        let system = system_tracker.update();
+        app.lock().unwrap().system = system;
+        if let Ok(mut processes) = process::Processes::fetch_process_list() {
+            cpu_tracker.update_process_cpu(&mut processes);
+            io_tracker.update_process_io(&mut processes);
+            suspicion_scanner.update_process_flags(&mut processes);
+            {
+                let mut app = app.lock().unwrap();
+                app.set_processes(processes.clone());
+                app.refresh_cgroup_pressure();
+            }
+            stream_hub.lock().unwrap().publish(&processes);
+        }
+        std::thread::sleep(Duration::from_millis(1000));
+    });
+}
@@ -0,0 +1,350 @@
+use crate::app::App;
+use crate::process::ProcessInfo;
+use crate::psi::ResourcePressure;
+use crate::system_stats::SystemStats;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::sync::Mutex;
+
+// Prometheus text exposition format, which OpenMetrics scrapers accept as well
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+const PREFIX: &str = "task_manager";
+
+// Columns of a cpuN line in /proc/stat, in order
+const CPU_MODES: [&str; 8] = ["user", "nice", "system", "idle", "iowait", "irq", "softirq", "steal"];
+
+// Bounds the number of per-process series; the system metrics are always exported
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsOptions {
+    pub allow: Vec<String>, // process names to export, empty exports every process
+    pub top: Option<usize>, // only the busiest processes, by CPU then memory
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreTimes {
+    pub cpu: String,
+    pub ticks: Vec<u64>, // one per CPU_MODES entry the kernel reports
+}
+
+pub struct ProcessSample<'a> {
+    pub process: &'a ProcessInfo,
+    pub user: String,
+    pub open_fds: Option<u64>,
+}
+
+// Writes one metric family at a time, HELP and TYPE first
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {}_{} {}", PREFIX, name, help);
+        let _ = writeln!(self.text, "# TYPE {}_{} {}", PREFIX, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let _ = write!(self.text, "{}_{}", PREFIX, name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", format_value(value));
+    }
+}
+
+// Process names and cgroup paths are not under our control
+pub fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    match value {
+        v if v.is_nan() => "NaN".into(),
+        v if v == f64::INFINITY => "+Inf".into(),
+        v if v == f64::NEG_INFINITY => "-Inf".into(),
+        v => v.to_string(),
+    }
+}
+
+pub fn parse_core_times(stat: &str) -> Vec<CoreTimes> {
+    stat.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let cpu = fields.next()?.strip_prefix("cpu")?;
+            if cpu.is_empty() || !cpu.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let ticks = fields.take(CPU_MODES.len()).filter_map(|v| v.parse().ok()).collect();
+            Some(CoreTimes { cpu: cpu.to_owned(), ticks })
+        })
+        .collect()
+}
+
+pub fn parse_passwd(content: &str) -> HashMap<u32, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            Some((fields.get(2)?.parse().ok()?, fields.first()?.to_string()))
+        })
+        .collect()
+}
+
+// Allowlist first, then the busiest top N of what is left
+pub fn select_processes<'a>(processes: &'a [ProcessInfo], options: &MetricsOptions) -> Vec<&'a ProcessInfo> {
+    let mut selected: Vec<&ProcessInfo> = processes
+        .iter()
+        .filter(|p| options.allow.is_empty() || options.allow.contains(&p.name))
+        .collect();
+    if let Some(top) = options.top {
+        selected.sort_by(|a, b| {
+            b.cpu_percent
+                .partial_cmp(&a.cpu_percent)
+                .unwrap_or(Ordering::Equal)
+                .then(b.memory.cmp(&a.memory))
+        });
+        selected.truncate(top);
+    }
+    selected.sort_by_key(|p| p.pid);
+    selected
+}
+
+fn pressure(out: &mut Exposition, resources: [(&str, Option<ResourcePressure>); 3]) {
+    out.family(
+        "pressure_stalled_seconds_total",
+        "counter",
+        "Time tasks were stalled waiting for a resource (PSI).",
+    );
+    for (resource, pressure) in resources {
+        let Some(pressure) = pressure else { continue };
+        let lines = [("some", Some(pressure.some)), ("full", pressure.full)];
+        for (kind, line) in lines {
+            if let Some(line) = line {
+                let seconds = line.total as f64 / 1_000_000.0;
+                out.sample("pressure_stalled_seconds_total", &[("resource", resource), ("kind", kind)], seconds);
+            }
+        }
+    }
+}
+
+pub fn render(system: &SystemStats, cores: &[CoreTimes], total_processes: usize, processes: &[ProcessSample], ticks_per_second: f64) -> String {
+    let mut out = Exposition { text: String::new() };
+
+    out.family("cpu_seconds_total", "counter", "Time each CPU spent in each mode.");
+    for core in cores {
+        for (mode, ticks) in CPU_MODES.iter().zip(&core.ticks) {
+            out.sample("cpu_seconds_total", &[("cpu", &core.cpu), ("mode", mode)], *ticks as f64 / ticks_per_second);
+        }
+    }
+    out.family("cpu_busy_ratio", "gauge", "Busy share of all CPUs over the last refresh.");
+    out.sample("cpu_busy_ratio", &[], system.cpu_percent / 100.0);
+
+    if let Some(memory) = &system.memory {
+        let values = [
+            ("memory_total_bytes", "Total usable memory.", memory.total),
+            ("memory_free_bytes", "Memory not used for anything.", memory.free),
+            ("memory_available_bytes", "Memory available for new allocations without swapping.", memory.available),
+            ("memory_buffers_bytes", "Memory used by block device buffers.", memory.buffers),
+            ("memory_cached_bytes", "Memory used by the page cache.", memory.cached),
+            ("swap_total_bytes", "Total swap space.", memory.swap_total),
+            ("swap_free_bytes", "Unused swap space.", memory.swap_free),
+        ];
+        for (name, help, value) in values {
+            out.family(name, "gauge", help);
+            out.sample(name, &[], value as f64);
+        }
+    }
+    if let Some(load) = &system.load {
+        for (name, window, value) in [("load1", "1", load.one), ("load5", "5", load.five), ("load15", "15", load.fifteen)] {
+            out.family(name, "gauge", &format!("{} minute load average.", window));
+            out.sample(name, &[], value);
+        }
+    }
+    if let Some(psi) = &system.pressure {
+        pressure(&mut out, [("cpu", psi.cpu), ("memory", psi.memory), ("io", psi.io)]);
+    }
+    out.family("processes", "gauge", "Number of processes, including those not exported below.");
+    out.sample("processes", &[], total_processes as f64);
+
+    type Value = fn(&ProcessSample, f64) -> Option<f64>;
+    let families: [(&str, &str, &str, Value); 6] = [
+        ("process_cpu_seconds_total", "counter", "User and system CPU time of the process.", |s, ticks| {
+            Some(s.process.cpu_time as f64 / ticks)
+        }),
+        ("process_resident_memory_bytes", "gauge", "Resident set size of the process.", |s, _| {
+            Some(s.process.memory as f64)
+        }),
+        ("process_read_bytes_total", "counter", "Bytes the process caused to be read from storage.", |s, _| {
+            s.process.io.map(|io| io.read_bytes as f64)
+        }),
+        ("process_write_bytes_total", "counter", "Bytes the process caused to be written to storage.", |s, _| {
+            s.process.io.map(|io| io.write_bytes as f64)
+        }),
+        ("process_open_fds", "gauge", "Open file descriptors of the process.", |s, _| {
+            s.open_fds.map(|fds| fds as f64)
+        }),
+        ("process_threads", "gauge", "Threads of the process.", |s, _| s.process.threads.map(|t| t as f64)),
+    ];
+    for (name, kind, help, value) in families {
+        out.family(name, kind, help);
+        for sample in processes {
+            if let Some(value) = value(sample, ticks_per_second) {
+                let pid = sample.process.pid.to_string();
+                let cgroup = sample.process.cgroup.as_deref().unwrap_or("");
+                let labels = [("pid", pid.as_str()), ("name", &sample.process.name), ("user", &sample.user), ("cgroup", cgroup)];
+                out.sample(name, &labels, value);
+            }
+        }
+    }
+    out.text
+}
+
+// Everything that is not already in App is read at scrape time, and only for exported processes
+pub fn collect(app: &Mutex<App>, options: &MetricsOptions) -> String {
+    let (system, processes) = {
+        let app = app.lock().unwrap();
+        (app.system.clone(), app.all_processes.clone())
+    };
+    let selected = select_processes(&processes, options);
+    let users = fs::read_to_string("/etc/passwd").map(|c| parse_passwd(&c)).unwrap_or_default();
+    let samples: Vec<ProcessSample> = selected
+        .into_iter()
+        .map(|process| ProcessSample {
+            process,
+            // Numeric uid when there is no passwd entry, e.g. inside containers
+            user: process
+                .uid
+                .map(|uid| users.get(&uid).cloned().unwrap_or_else(|| uid.to_string()))
+                .unwrap_or_default(),
+            open_fds: fs::read_dir(format!("/proc/{}/fd", process.pid))
+                .ok()
+                .map(|entries| entries.count() as u64),
+        })
+        .collect();
+    let cores = fs::read_to_string("/proc/stat").map(|c| parse_core_times(&c)).unwrap_or_default();
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+    render(&system, &cores, processes.len(), &samples, ticks_per_second)
+}
+
+// prometheus-parse skips lines it cannot read, so callers compare the sample count
+#[cfg(test)]
+fn parse_exposition(text: &str) -> prometheus_parse::Scrape {
+    prometheus_parse::Scrape::parse(text.lines().map(|line| Ok(line.to_owned()))).unwrap()
+}
+
+#[cfg(test)]
+fn sample_process(pid: u32, name: &str, cpu_percent: f64, memory: u64) -> ProcessInfo {
+    let mut process = ProcessInfo::new(pid, name.into(), 250, memory);
+    process.cpu_percent = cpu_percent;
+    process
+}
+
+#[cfg(test)]
+fn sample_exposition() -> String {
+    use crate::io_tracker::IoStats;
+    use crate::psi::{Pressure, PressureLine};
+    use crate::system_stats::{LoadAverage, MemoryStats};
+    let line = PressureLine { total: 1_500_000, ..Default::default() };
+    let system = SystemStats {
+        cpu_percent: 42.0,
+        load: Some(LoadAverage { one: 0.5, five: 1.0, fifteen: 1.5, running: 1, total: 100 }),
+        memory: Some(MemoryStats { total: 1 << 30, ..Default::default() }),
+        pressure: Some(Pressure {
+            cpu: Some(ResourcePressure { some: line, full: None }),
+            memory: Some(ResourcePressure { some: line, full: Some(line) }),
+            io: None,
+        }),
+        ..Default::default()
+    };
+    let cores = parse_core_times("cpu  10 0 0 0\ncpu0 100 2 30 400 5 0 1 0 0 0\ncpu1 100 2 30 400 5 0 1 0\n");
+    let mut odd = sample_process(7, "we\"ird\\name\n", 1.0, 4096);
+    odd.cgroup = Some("/system.slice/a.service".into());
+    odd.io = Some(IoStats { read_bytes: 10, write_bytes: 20, ..Default::default() });
+    odd.threads = Some(3);
+    let plain = sample_process(8, "sh", 0.0, 0);
+    let samples = [
+        ProcessSample { process: &odd, user: "root".into(), open_fds: Some(4) },
+        ProcessSample { process: &plain, user: "1000".into(), open_fds: None },
+    ];
+    render(&system, &cores, 2, &samples, 100.0)
+}
+
+#[test]
+fn test_render_is_valid_exposition() {
+    let text = sample_exposition();
+    let scrape = parse_exposition(&text);
+    let sample_lines = text.lines().filter(|line| !line.starts_with('#')).count();
+    assert_eq!(scrape.samples.len(), sample_lines);
+    assert_eq!(sample_lines, 16 + 1 + 7 + 3 + 3 + 1 + 2 + 2 + 1 + 1 + 1 + 1);
+    let series: std::collections::HashSet<String> =
+        scrape.samples.iter().map(|s| format!("{}{{{}}}", s.metric, s.labels)).collect();
+    assert_eq!(series.len(), sample_lines);
+    for sample in &scrape.samples {
+        assert!(scrape.docs.contains_key(&sample.metric), "{} has no HELP", sample.metric);
+        match sample.value {
+            prometheus_parse::Value::Counter(_) => assert!(sample.metric.ends_with("_total"), "{}", sample.metric),
+            prometheus_parse::Value::Gauge(_) => {}
+            ref other => panic!("{} is {:?}", sample.metric, other),
+        }
+    }
+    let plain = scrape.samples.iter().find(|s| s.metric == "task_manager_process_cpu_seconds_total" && s.labels.get("pid") == Some("8"));
+    assert_eq!(plain.map(|s| (s.labels.get("user"), s.labels.get("name"))), Some((Some("1000"), Some("sh"))));
+    assert!(text.contains("task_manager_cpu_seconds_total{cpu=\"1\",mode=\"idle\"} 4\n"));
+    assert!(text.contains("task_manager_cpu_busy_ratio 0.42\n"));
+    assert!(text.contains("task_manager_pressure_stalled_seconds_total{resource=\"memory\",kind=\"full\"} 1.5\n"));
+    assert!(text.contains(
+        "task_manager_process_cpu_seconds_total{pid=\"7\",name=\"we\\\"ird\\\\name\\n\",user=\"root\",cgroup=\"/system.slice/a.service\"} 2.5\n"
+    ));
+    assert!(text.contains("task_manager_process_open_fds{pid=\"7\""));
+    assert!(!text.contains("task_manager_process_open_fds{pid=\"8\""));
+}
+
+// Needs promtool on PATH: cargo test -- --ignored
+#[test]
+#[ignore]
+fn test_render_passes_promtool() {
+    use std::io::Write as _;
+    use std::process::{Command, Stdio};
+    let mut child = Command::new("promtool")
+        .args(["check", "metrics"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("promtool is not installed");
+    child.stdin.take().unwrap().write_all(sample_exposition().as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "promtool: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_select_processes() {
+    let processes = [
+        sample_process(1, "init", 0.5, 10),
+        sample_process(2, "nginx", 5.0, 10),
+        sample_process(3, "nginx", 0.0, 30),
+        sample_process(4, "postgres", 9.0, 10),
+    ];
+    let pids = |options: &MetricsOptions| select_processes(&processes, options).iter().map(|p| p.pid).collect::<Vec<_>>();
+    assert_eq!(pids(&MetricsOptions::default()), vec![1, 2, 3, 4]);
+    assert_eq!(pids(&MetricsOptions { top: Some(2), ..Default::default() }), vec![2, 4]);
+    let allow = vec!["nginx".to_string(), "init".to_string()];
+    assert_eq!(pids(&MetricsOptions { allow: allow.clone(), top: None }), vec![1, 2, 3]);
+    assert_eq!(pids(&MetricsOptions { allow, top: Some(2) }), vec![1, 2]);
+}
+
+#[test]
+fn test_parse_passwd_and_core_times() {
+    let users = parse_passwd("root:x:0:0:root:/root:/bin/bash\n# comment\nalice:x:1000:1000::/home/alice:/bin/sh\n");
+    assert_eq!(users.get(&1000).map(String::as_str), Some("alice"));
+    assert_eq!(users.len(), 2);
+    let cores = parse_core_times("cpu  1 2 3 4\ncpu0 1 2 3 4 5 6 7 8 9 10\nintr 5\n");
+    assert_eq!(cores, vec![CoreTimes { cpu: "0".into(), ticks: vec![1, 2, 3, 4, 5, 6, 7, 8] }]);
+}
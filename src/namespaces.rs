@@ -0,0 +1,190 @@
+use crate::process::{ProcessInfo, Status};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const NAMESPACE_KINDS: [&str; 7] = ["pid", "net", "mnt", "user", "uts", "ipc", "cgroup"];
+
+// Inode numbers of /proc/[pid]/ns/*, equal inodes mean a shared namespace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Namespaces {
+    pub pid: Option<u64>,
+    pub net: Option<u64>,
+    pub mnt: Option<u64>,
+    pub user: Option<u64>,
+    pub uts: Option<u64>,
+    pub ipc: Option<u64>,
+    pub cgroup: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NamespaceGroup {
+    pub namespaces: Namespaces,
+    pub differs: Vec<&'static str>, // kinds not shared with the reference (host) process
+    pub members: Vec<(u32, String)>,
+}
+
+impl Namespaces {
+    pub fn read(pid: u32) -> Option<Self> {
+        Self::read_from(&Path::new("/proc").join(pid.to_string()).join("ns"))
+    }
+
+    pub fn read_from(ns_dir: &Path) -> Option<Self> {
+        let inode = |kind: &str| {
+            let link = fs::read_link(ns_dir.join(kind)).ok()?;
+            parse_ns_link(&link.to_string_lossy())
+        };
+        let namespaces = Self {
+            pid: inode("pid"),
+            net: inode("net"),
+            mnt: inode("mnt"),
+            user: inode("user"),
+            uts: inode("uts"),
+            ipc: inode("ipc"),
+            cgroup: inode("cgroup"),
+        };
+        (namespaces != Self::default()).then_some(namespaces)
+    }
+
+    pub fn get(&self, kind: &str) -> Option<u64> {
+        match kind {
+            "pid" => self.pid,
+            "net" => self.net,
+            "mnt" => self.mnt,
+            "user" => self.user,
+            "uts" => self.uts,
+            "ipc" => self.ipc,
+            "cgroup" => self.cgroup,
+            _ => None,
+        }
+    }
+
+    // Unknown inodes (unreadable links) are not counted as different
+    pub fn differing_kinds(&self, reference: &Namespaces) -> Vec<&'static str> {
+        NAMESPACE_KINDS
+            .iter()
+            .copied()
+            .filter(|kind| match (self.get(kind), reference.get(kind)) {
+                (Some(own), Some(other)) => own != other,
+                _ => false,
+            })
+            .collect()
+    }
+}
+
+pub fn isolation_label(differs: &[&str]) -> String {
+    if differs.is_empty() {
+        "host".into()
+    } else {
+        differs.join(",")
+    }
+}
+
+// Links look like "net:[4026531840]"
+pub fn parse_ns_link(link: &str) -> Option<u64> {
+    let (_, rest) = link.split_once(":[")?;
+    rest.strip_suffix(']')?.parse().ok()
+}
+
+// NSpid lists the pid in every nested pid namespace, outermost (host) first
+pub fn parse_nspid(status: &Status) -> Vec<u32> {
+    status
+        .value("NSpid")
+        .map(|pids| pids.split_whitespace().filter_map(|p| p.parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+// Init's namespaces are the host reference, falls back to our own if pid 1 is not readable
+pub fn reference_namespaces() -> Namespaces {
+    Namespaces::read(1)
+        .or_else(|| Namespaces::read_from(Path::new("/proc/self/ns")))
+        .unwrap_or_default()
+}
+
+pub fn group_by_namespaces(processes: &[ProcessInfo], reference: &Namespaces) -> Vec<NamespaceGroup> {
+    let mut groups: BTreeMap<&Namespaces, Vec<(u32, String)>> = BTreeMap::new();
+    for proc in processes {
+        if let Some(namespaces) = &proc.namespaces {
+            groups
+                .entry(namespaces)
+                .or_default()
+                .push((proc.pid, proc.name.clone()));
+        }
+    }
+
+    let mut groups: Vec<NamespaceGroup> = groups
+        .into_iter()
+        .map(|(namespaces, mut members)| {
+            members.sort();
+            NamespaceGroup {
+                differs: namespaces.differing_kinds(reference),
+                namespaces: namespaces.clone(),
+                members,
+            }
+        })
+        .collect();
+    // Host namespaces first, then the most isolated sets
+    groups.sort_by(|a, b| {
+        a.differs
+            .len()
+            .cmp(&b.differs.len())
+            .then(b.members.len().cmp(&a.members.len()))
+    });
+    groups
+}
+
+#[cfg(test)]
+fn namespaces(pid: u64, net: u64) -> Namespaces {
+    Namespaces {
+        pid: Some(pid),
+        net: Some(net),
+        mnt: Some(3),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_parse_ns_link() {
+    assert_eq!(parse_ns_link("net:[4026531840]"), Some(4026531840));
+    assert_eq!(parse_ns_link("garbage"), None);
+}
+
+#[test]
+fn test_parse_nspid() {
+    let status = Status::parse("Name:\tnginx\nPid:\t43210\nNSpid:\t43210\t7\nPPid:\t1\n");
+    assert_eq!(parse_nspid(&status), vec![43210, 7]);
+    assert!(parse_nspid(&Status::parse("Name:\told-kernel\n")).is_empty());
+}
+
+#[test]
+fn test_label_lists_differing_namespaces() {
+    let host = namespaces(1, 1);
+    assert_eq!(isolation_label(&namespaces(1, 1).differing_kinds(&host)), "host");
+    assert_eq!(isolation_label(&namespaces(2, 2).differing_kinds(&host)), "pid,net");
+}
+
+#[test]
+fn test_group_by_namespaces() {
+    let host = namespaces(1, 1);
+    let mut procs = vec![
+        ProcessInfo::new(1, "init".into(), 0, 0),
+        ProcessInfo::new(100, "nginx".into(), 0, 0),
+        ProcessInfo::new(101, "nginx-worker".into(), 0, 0),
+    ];
+    procs[0].namespaces = Some(host.clone());
+    procs[1].namespaces = Some(namespaces(2, 2));
+    procs[2].namespaces = Some(namespaces(2, 2));
+
+    let groups = group_by_namespaces(&procs, &host);
+    assert_eq!(groups.len(), 2);
+    assert!(groups[0].differs.is_empty());
+    assert_eq!(groups[1].differs, vec!["pid", "net"]);
+    assert_eq!(groups[1].members.len(), 2);
+}
+
+#[test]
+fn test_read_own_namespaces() {
+    let own = Namespaces::read_from(Path::new("/proc/self/ns")).unwrap();
+    assert!(own.pid.is_some());
+}
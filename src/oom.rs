@@ -0,0 +1,85 @@
+use crate::process::ProcessInfo;
+use std::fs;
+use std::io;
+
+pub const OOM_SCORE_ADJ_MIN: i32 = -1000;
+pub const OOM_SCORE_ADJ_MAX: i32 = 1000;
+
+pub fn read_oom_score(pid: u32) -> Option<u32> {
+    fs::read_to_string(format!("/proc/{}/oom_score", pid)).ok()?.trim().parse().ok()
+}
+
+pub fn read_oom_score_adj(pid: u32) -> Option<i32> {
+    fs::read_to_string(format!("/proc/{}/oom_score_adj", pid)).ok()?.trim().parse().ok()
+}
+
+pub fn parse_oom_score_adj(input: &str) -> Result<i32, String> {
+    let value: i32 = input
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", input.trim()))?;
+    if !(OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(&value) {
+        return Err(format!(
+            "oom_score_adj must be between {} and {}",
+            OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX
+        ));
+    }
+    Ok(value)
+}
+
+// Lowering the value below what the process had needs CAP_SYS_RESOURCE
+pub fn set_oom_score_adj(pid: u32, value: i32) -> io::Result<()> {
+    fs::write(format!("/proc/{}/oom_score_adj", pid), value.to_string()).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => io::Error::new(
+            e.kind(),
+            "permission denied (other users' processes and lowering the value need CAP_SYS_RESOURCE)",
+        ),
+        _ => e,
+    })
+}
+
+// The kernel kills the highest oom_score first; -1000 exempts a process completely
+pub fn victim_order(processes: &[ProcessInfo]) -> Vec<&ProcessInfo> {
+    let mut victims: Vec<&ProcessInfo> = processes
+        .iter()
+        .filter(|p| p.oom_score.is_some() && p.oom_score_adj != Some(OOM_SCORE_ADJ_MIN))
+        .collect();
+    victims.sort_by(|a, b| b.oom_score.cmp(&a.oom_score).then(b.memory.cmp(&a.memory)));
+    victims
+}
+
+#[test]
+fn test_parse_oom_score_adj() {
+    assert_eq!(parse_oom_score_adj(" -500 "), Ok(-500));
+    assert_eq!(parse_oom_score_adj("1000"), Ok(1000));
+    assert!(parse_oom_score_adj("1001").is_err());
+    assert!(parse_oom_score_adj("-1001").is_err());
+    assert!(parse_oom_score_adj("high").is_err());
+}
+
+#[test]
+fn test_victim_order() {
+    let mut procs = vec![
+        ProcessInfo::new(1, "systemd".into(), 0, 100),
+        ProcessInfo::new(2, "postgres".into(), 0, 500),
+        ProcessInfo::new(3, "chrome".into(), 0, 400),
+        ProcessInfo::new(4, "sshd".into(), 0, 50),
+    ];
+    procs[0].oom_score = Some(0);
+    procs[1].oom_score = Some(300);
+    procs[2].oom_score = Some(900);
+    procs[3].oom_score = Some(0);
+    procs[3].oom_score_adj = Some(OOM_SCORE_ADJ_MIN);
+
+    let order: Vec<u32> = victim_order(&procs).iter().map(|p| p.pid).collect();
+    assert_eq!(order, vec![3, 2, 1]);
+}
+
+#[test]
+fn test_read_own_oom_values() {
+    let pid = std::process::id();
+    assert!(read_oom_score(pid).is_some());
+    let adj = read_oom_score_adj(pid).unwrap();
+    // Writing the current value back is always allowed
+    set_oom_score_adj(pid, adj).unwrap();
+}
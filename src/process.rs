@@ -1,25 +1,62 @@
-use libc;
+use crate::cgroup;
+use crate::io_tracker::IoStats;
+use crate::namespaces::{self, Namespaces};
+use crate::oom;
+use crate::security;
+use crate::suspicious::SuspicionFlag;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
 use std::{collections::HashSet, fs};
 
+// Fields of /proc/[pid]/stat, numbered as in proc(5)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Stat {
+    pub state: char,
+    pub ppid: u32,
+    pub pgrp: i32,
+    pub session: i32,
+    pub tty_nr: i32,
+    pub tpgid: i32,
+    pub utime: u64,
+    pub stime: u64,
+    pub starttime: u64, // clock ticks after boot, tells a reused pid apart
+    pub rss_pages: u64,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ProcessInfo {
     pub pid: u32,
     pub name: String,
+    pub stat: Stat,
     pub cpu_time: u64,
     pub memory: u64,
     pub cpu_percent: f64,
+    pub io: Option<IoStats>,
+    pub read_rate: Option<f64>,  // bytes/s, None if /proc/[pid]/io is unreadable
+    pub write_rate: Option<f64>, // bytes/s, None if /proc/[pid]/io is unreadable
+    pub cgroup: Option<String>,  // cgroup v2 path, e.g. /system.slice/sshd.service
+    pub namespaces: Option<Namespaces>,
+    pub isolated_namespaces: Vec<&'static str>, // namespace kinds not shared with init
+    pub ns_pids: Vec<u32>,                      // pid in each nested pid namespace, host first
+    pub cap_eff: Option<u64>,                   // effective capability mask
+    pub flags: Vec<SuspicionFlag>,
+    pub oom_score: Option<u32>,
+    pub oom_score_adj: Option<i32>,
+    pub uid: Option<u32>,     // real user id
+    pub threads: Option<u64>,
 }
 
 impl Eq for ProcessInfo {}
 
+// Identity only, so a process whose counters moved is still the same set member
 impl Hash for ProcessInfo {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.pid.hash(state);
         self.name.hash(state);
-        self.cpu_time.hash(state);
-        self.memory.hash(state);
+        self.stat.starttime.hash(state);
     }
 }
 
@@ -30,9 +67,206 @@ impl ProcessInfo {
             name,
             cpu_time,
             memory,
+            stat: Stat::default(),
             cpu_percent: 0.0,
+            io: None,
+            read_rate: None,
+            write_rate: None,
+            cgroup: None,
+            namespaces: None,
+            isolated_namespaces: vec![],
+            ns_pids: vec![],
+            cap_eff: None,
+            flags: vec![],
+            oom_score: None,
+            oom_score_adj: None,
+            uid: None,
+            threads: None,
+        }
+    }
+
+    // The pid as seen inside its own pid namespace, if that differs from the host pid
+    pub fn container_pid(&self) -> Option<u32> {
+        match self.ns_pids.as_slice() {
+            [_, .., inner] => Some(*inner),
+            _ => None,
+        }
+    }
+
+    pub fn has_elevated_capabilities(&self) -> bool {
+        self.cap_eff.unwrap_or(0) != 0
+    }
+
+    pub fn has_disk_io(&self) -> bool {
+        self.read_rate.unwrap_or(0.0) > 0.0 || self.write_rate.unwrap_or(0.0) > 0.0
+    }
+
+    pub fn has_tty(&self) -> bool {
+        self.stat.tty_nr != 0
+    }
+
+    // T is stopped by a signal, t stopped by a debugger
+    pub fn is_stopped(&self) -> bool {
+        matches!(self.stat.state, 'T' | 't')
+    }
+}
+
+// comm may contain spaces and parentheses, so fields are counted from the last ')'
+pub fn parse_stat(content: &str) -> Option<Stat> {
+    let (_, rest) = content.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // fields[0] is field 3 (state)
+    let field = |n: usize| fields.get(n - 3).copied().unwrap_or("0");
+    if fields.len() < 22 {
+        return None;
+    }
+    Some(Stat {
+        state: field(3).chars().next().unwrap_or('?'),
+        ppid: field(4).parse().ok()?,
+        pgrp: field(5).parse().ok()?,
+        session: field(6).parse().ok()?,
+        tty_nr: field(7).parse().ok()?,
+        tpgid: field(8).parse().ok()?,
+        utime: field(14).parse().ok()?,
+        stime: field(15).parse().ok()?,
+        starttime: field(22).parse().ok()?,
+        rss_pages: field(24).parse().ok()?,
+    })
+}
+
+pub fn read_stat(pid: u32) -> Option<Stat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+// /proc/[pid]/status split once into "Key:\tvalue" pairs, shared by everything reading a field of it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status(HashMap<String, String>);
+
+impl Status {
+    pub fn parse(content: &str) -> Self {
+        Status(
+            content
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.to_owned(), value.trim().to_owned()))
+                .collect(),
+        )
+    }
+
+    pub fn read(pid: u32) -> io::Result<Self> {
+        Self::read_from(Path::new(&format!("/proc/{}/status", pid)))
+    }
+
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    // The first number of the value, e.g. the real id of "Uid: 1000 1000 1000 1000"
+    pub fn number(&self, key: &str) -> Option<u64> {
+        self.value(key)?.split_whitespace().next()?.parse().ok()
+    }
+
+    // Vm* sizes are given in kB
+    pub fn bytes(&self, key: &str) -> Option<u64> {
+        Some(self.value(key)?.strip_suffix("kB")?.trim().parse::<u64>().ok()? * 1024)
+    }
+
+    pub fn real_uid(&self) -> Option<u32> {
+        self.number("Uid").map(|uid| uid as u32)
+    }
+}
+
+// Parent first, breadth first, without the root itself
+pub fn descendants(processes: &[ProcessInfo], pid: u32) -> Vec<u32> {
+    let mut found = vec![];
+    let mut queue = std::collections::VecDeque::from([pid]);
+    while let Some(parent) = queue.pop_front() {
+        for child in processes.iter().filter(|p| p.stat.ppid == parent && p.pid != parent) {
+            found.push(child.pid);
+            queue.push_back(child.pid);
+        }
+    }
+    found
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpenFile {
+    pub fd: u32,
+    pub target: String, // a path, or e.g. socket:[1234] and pipe:[5678]
+}
+
+// One line of /proc/[pid]/maps
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryMapping {
+    pub start: u64,
+    pub end: u64,
+    pub perms: String,
+    pub offset: u64,
+    pub device: String,
+    pub inode: u64,
+    pub path: Option<String>, // None for anonymous mappings
+}
+
+// Arguments are NUL separated, kernel threads have none
+pub fn read_cmdline(pid: u32) -> io::Result<Vec<String>> {
+    let raw = fs::read(format!("/proc/{}/cmdline", pid))?;
+    Ok(raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect())
+}
+
+pub fn read_link(pid: u32, name: &str) -> Option<String> {
+    fs::read_link(format!("/proc/{}/{}", pid, name))
+        .ok()
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+pub fn read_open_files(pid: u32) -> io::Result<Vec<OpenFile>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(format!("/proc/{}/fd", pid))? {
+        let entry = entry?;
+        let Ok(fd) = entry.file_name().to_string_lossy().parse() else {
+            continue;
+        };
+        // The fd may be closed between listing and reading the link
+        if let Ok(target) = fs::read_link(entry.path()) {
+            files.push(OpenFile {
+                fd,
+                target: target.to_string_lossy().into_owned(),
+            });
         }
     }
+    files.sort_by_key(|f| f.fd);
+    Ok(files)
+}
+
+pub fn parse_maps(content: &str) -> Vec<MemoryMapping> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(6, char::is_whitespace);
+            let (start, end) = fields.next()?.split_once('-')?;
+            Some(MemoryMapping {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(end, 16).ok()?,
+                perms: fields.next()?.to_owned(),
+                offset: u64::from_str_radix(fields.next()?, 16).ok()?,
+                device: fields.next()?.to_owned(),
+                inode: fields.next()?.parse().ok()?,
+                path: fields.next().map(str::trim).filter(|p| !p.is_empty()).map(str::to_owned),
+            })
+        })
+        .collect()
+}
+
+pub fn read_maps(pid: u32) -> io::Result<Vec<MemoryMapping>> {
+    Ok(parse_maps(&fs::read_to_string(format!("/proc/{}/maps", pid))?))
 }
 
 pub struct Processes {
@@ -41,11 +275,19 @@ pub struct Processes {
 
 impl PartialEq for ProcessInfo {
     fn eq(&self, other: &Self) -> bool {
-        self.pid == other.pid && self.name == other.name
+        self.pid == other.pid && self.name == other.name && self.stat.starttime == other.stat.starttime
     }
 }
 
+// Processes that appeared or went away between two lists, ordered by pid
+#[derive(Debug, Clone, Default)]
+pub struct ProcessDelta {
+    pub added: Vec<ProcessInfo>,
+    pub removed: Vec<ProcessInfo>,
+}
+
 impl Processes {
+    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
             processes: Self::fetch_process_list().unwrap_or_default(),
@@ -55,6 +297,7 @@ impl Processes {
     //first called to fill process vector
     pub fn fetch_process_list() -> std::io::Result<Vec<ProcessInfo>> {
         let mut ret: Vec<ProcessInfo> = Vec::new();
+        let host_namespaces = namespaces::reference_namespaces();
         for entry in fs::read_dir("/proc/")? {
             let dir_entry = entry?;
             let filename = dir_entry.file_name();
@@ -64,41 +307,45 @@ impl Processes {
                     .map(|s| s.trim().to_owned())
                     .unwrap_or_else(|_| "[Unknown]".into());
 
-                let mut memory = 0;
-                let mut cpu_time = 0;
-                let stat_path = dir_entry.path().join("stat");
-                let stat_content = fs::read_to_string(&stat_path).unwrap_or_default();
-                let stat_fields: Vec<&str> = stat_content.split_whitespace().collect();
-                if stat_fields.len() > 23 {
-                    let utime = stat_fields[13].parse::<u64>().unwrap_or(0);
-                    let stime = stat_fields[14].parse::<u64>().unwrap_or(0);
-                    let rss_pages = stat_fields[23].parse::<u64>().unwrap_or(0);
-                    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
-                    memory = rss_pages * page_size;
-                    cpu_time = utime + stime;
-                }
+                let stat = read_stat(pid).unwrap_or_default();
+                let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+                let memory = stat.rss_pages * page_size;
+                let cpu_time = stat.utime + stat.stime;
 
-                ret.push(ProcessInfo::new(pid, proc_name, cpu_time, memory));
+                let mut info = ProcessInfo::new(pid, proc_name, cpu_time, memory);
+                info.stat = stat;
+                info.io = IoStats::read(pid).ok();
+                info.cgroup = cgroup::read_cgroup_path(pid);
+                info.namespaces = Namespaces::read(pid);
+                if let Some(ns) = &info.namespaces {
+                    info.isolated_namespaces = ns.differing_kinds(&host_namespaces);
+                }
+                let status = Status::read_from(&dir_entry.path().join("status")).unwrap_or_default();
+                info.ns_pids = namespaces::parse_nspid(&status);
+                info.cap_eff = security::parse_capabilities(&status).map(|caps| caps.effective);
+                info.uid = status.real_uid();
+                info.threads = status.number("Threads");
+                info.oom_score = oom::read_oom_score(pid);
+                info.oom_score_adj = oom::read_oom_score_adj(pid);
+                ret.push(info);
             }
         }
         Ok(ret)
     }
 
-    pub fn update_proc(&mut self, n_proc: &Vec<ProcessInfo>) {
+    pub fn update_proc(&mut self, n_proc: &[ProcessInfo]) -> ProcessDelta {
         let old_set: HashSet<ProcessInfo> = self.processes.iter().cloned().collect();
         let new_set: HashSet<ProcessInfo> = n_proc.iter().cloned().collect();
-        let added: Vec<_> = new_set.difference(&old_set).cloned().collect();
-        let removed: Vec<_> = old_set.difference(&new_set).cloned().collect();
+        let mut added: Vec<_> = new_set.difference(&old_set).cloned().collect();
+        let mut removed: Vec<_> = old_set.difference(&new_set).cloned().collect();
+        added.sort_by_key(|p| p.pid);
+        removed.sort_by_key(|p| p.pid);
 
-        for p in &added {
-            println!("Added: {:?}", p);
-        }
-        for p in &removed {
-            println!("Removed: {:?}", p);
-        }
-        self.processes = n_proc.clone();
+        self.processes = n_proc.to_vec();
+        ProcessDelta { added, removed }
     }
 
+    #[allow(dead_code)]
     pub fn refresh_processses(&mut self) {
         match Self::fetch_process_list() {
             Ok(n_processes) => {
@@ -121,6 +368,16 @@ fn test_processinfo_new() {
     assert_eq!(proc.cpu_percent, 0.0);
 }
 
+#[test]
+fn test_parse_status() {
+    let status = Status::parse("Name:\tmy: app\nUid:\t1000\t1000\t1000\t1000\nVmStk:\t     132 kB\nThreads:\t4\n");
+    assert_eq!(status.value("Name"), Some("my: app"));
+    assert_eq!(status.real_uid(), Some(1000));
+    assert_eq!(status.bytes("VmStk"), Some(132 * 1024));
+    assert_eq!(status.number("Threads"), Some(4));
+    assert_eq!(status.number("Tgid"), None);
+}
+
 #[test]
 fn test_processinfo_equality() {
     let p1 = ProcessInfo::new(1, "bash".into(), 100, 2000);
@@ -140,7 +397,9 @@ fn test_update_proc_detects_changes() {
         ProcessInfo::new(3, "c".into(), 30, 300),
     ];
 
-    processes.update_proc(&new_list);
+    let delta = processes.update_proc(&new_list);
+    assert_eq!(delta.added.iter().map(|p| p.pid).collect::<Vec<_>>(), vec![3]);
+    assert_eq!(delta.removed.iter().map(|p| p.pid).collect::<Vec<_>>(), vec![1]);
     assert_eq!(processes.processes.len(), 2);
     assert!(processes.processes.iter().any(|p| p.pid == 3));
     assert!(!processes.processes.iter().any(|p| p.pid == 1));
@@ -151,3 +410,71 @@ fn test_refresh_processes_doesnt_panic() {
     let mut p = Processes { processes: vec![] };
     p.refresh_processses(); 
 }
+
+#[test]
+fn test_container_pid() {
+    let mut proc = ProcessInfo::new(43210, "nginx".into(), 0, 0);
+    assert_eq!(proc.container_pid(), None);
+    proc.ns_pids = vec![43210];
+    assert_eq!(proc.container_pid(), None);
+    proc.ns_pids = vec![43210, 7];
+    assert_eq!(proc.container_pid(), Some(7));
+}
+
+#[test]
+fn test_parse_stat_with_spaces_in_comm() {
+    let content = "4242 (tmux: server) T 1 4242 4242 34817 4300 4194560 100 0 0 0 25 7 0 0 20 0 1 0 123456 9000000 850 18446744073709551615";
+    let stat = parse_stat(content).unwrap();
+    assert_eq!(stat.state, 'T');
+    assert_eq!(stat.ppid, 1);
+    assert_eq!(stat.tty_nr, 34817);
+    assert_eq!(stat.tpgid, 4300);
+    assert_eq!(stat.utime, 25);
+    assert_eq!(stat.stime, 7);
+    assert_eq!(stat.starttime, 123456);
+    assert_eq!(stat.rss_pages, 850);
+}
+
+#[test]
+fn test_descendants_parent_first() {
+    let mut procs = vec![
+        ProcessInfo::new(1, "init".into(), 0, 0),
+        ProcessInfo::new(10, "sshd".into(), 0, 0),
+        ProcessInfo::new(11, "bash".into(), 0, 0),
+        ProcessInfo::new(12, "vim".into(), 0, 0),
+        ProcessInfo::new(20, "cron".into(), 0, 0),
+    ];
+    procs[1].stat.ppid = 1;
+    procs[2].stat.ppid = 10;
+    procs[3].stat.ppid = 11;
+    procs[4].stat.ppid = 1;
+    assert_eq!(descendants(&procs, 10), vec![11, 12]);
+    assert_eq!(descendants(&procs, 12), Vec::<u32>::new());
+}
+
+#[test]
+fn test_parse_maps() {
+    let maps = "55d0c8a00000-55d0c8a28000 r--p 00000000 08:01 1835013                    /usr/bin/bash\n\
+                7ffd1c5e0000-7ffd1c601000 rw-p 00000000 00:00 0                          [stack]\n\
+                7f2b4c000000-7f2b4c021000 rw-p 00000000 00:00 0 \n";
+    let mappings = parse_maps(maps);
+    assert_eq!(mappings.len(), 3);
+    assert_eq!(mappings[0].start, 0x55d0c8a00000);
+    assert_eq!(mappings[0].perms, "r--p");
+    assert_eq!(mappings[0].inode, 1835013);
+    assert_eq!(mappings[0].path.as_deref(), Some("/usr/bin/bash"));
+    assert_eq!(mappings[1].path.as_deref(), Some("[stack]"));
+    assert_eq!(mappings[2].path, None);
+}
+
+#[test]
+fn test_update_proc_ignores_counter_changes_and_sees_pid_reuse() {
+    let mut processes = Processes { processes: vec![ProcessInfo::new(1, "a".into(), 10, 100)] };
+    let delta = processes.update_proc(&[ProcessInfo::new(1, "a".into(), 99, 999)]);
+    assert!(delta.added.is_empty() && delta.removed.is_empty());
+
+    let mut reused = ProcessInfo::new(1, "a".into(), 0, 0);
+    reused.stat.starttime = 5;
+    let delta = processes.update_proc(&[reused]);
+    assert_eq!((delta.added.len(), delta.removed.len()), (1, 1));
+}
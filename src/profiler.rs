@@ -0,0 +1,420 @@
+use crate::cpu_tracker::CpuTracker;
+use crate::columns::format_bytes;
+use crate::io_tracker::IoStats;
+use crate::process::{self, ProcessInfo};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: task-manager run [--json] [--output FILE] [--interval MS] -- <command> [args ...]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOptions {
+    pub json: bool,
+    pub output: Option<PathBuf>, // None writes the report to stderr
+    pub interval: Duration,
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChildReport {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    pub user_cpu_secs: f64,
+    pub system_cpu_secs: f64,
+    pub peak_cpu_percent: f64,
+    pub peak_rss_bytes: u64,
+    pub peak_pss_bytes: Option<u64>,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+    pub seen_secs: f64, // between first and last sample that saw it
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProfileReport {
+    pub command: Vec<String>,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<i32>,
+    pub wall_time_secs: f64,
+    pub user_cpu_secs: f64,
+    pub system_cpu_secs: f64,
+    pub peak_rss_bytes: u64, // sum over the tree at its largest sample
+    pub peak_pss_bytes: Option<u64>,
+    pub peak_process_count: usize,
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+    pub samples: usize,
+    pub children: Vec<ChildReport>,
+}
+
+pub fn parse_run_args(args: &[String]) -> Result<RunOptions, String> {
+    let mut options = RunOptions {
+        json: false,
+        output: None,
+        interval: Duration::from_millis(200),
+        command: vec![],
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "-o" | "--output" => options.output = Some(args.next().ok_or("--output needs a file name")?.into()),
+            "--interval" => {
+                let ms: u64 = args
+                    .next()
+                    .and_then(|ms| ms.parse().ok())
+                    .filter(|ms| *ms > 0)
+                    .ok_or("--interval needs a positive number of milliseconds")?;
+                options.interval = Duration::from_millis(ms);
+            }
+            "--" => break,
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+    }
+    options.command = args.cloned().collect();
+    if options.command.is_empty() {
+        return Err("no command given".into());
+    }
+    Ok(options)
+}
+
+fn read_pss(pid: u32) -> Option<u64> {
+    let rollup = fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)).ok()?;
+    let kb = rollup.lines().find_map(|line| line.strip_prefix("Pss:"))?;
+    Some(kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok()? * 1024)
+}
+
+// Kernels built without CONFIG_PROC_CHILDREN have no children files
+fn read_children(pid: u32) -> Option<Vec<u32>> {
+    let mut children = vec![];
+    for task in fs::read_dir(format!("/proc/{}/task", pid)).ok()?.flatten() {
+        let list = fs::read_to_string(task.path().join("children")).ok()?;
+        children.extend(list.split_whitespace().filter_map(|pid| pid.parse::<u32>().ok()));
+    }
+    Some(children)
+}
+
+// The fallback reads only the stat file of every process
+fn children_by_parent() -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for entry in fs::read_dir("/proc").into_iter().flatten().flatten() {
+        if let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>()
+            && let Some(stat) = process::read_stat(pid)
+        {
+            children.entry(stat.ppid).or_default().push(pid);
+        }
+    }
+    children
+}
+
+// Roots first, then their descendants breadth first
+fn tree_pids(roots: &[u32]) -> Vec<u32> {
+    let mut fallback = None;
+    let mut pids = roots.to_vec();
+    let mut next = 0;
+    while let Some(&pid) = pids.get(next) {
+        next += 1;
+        let children = read_children(pid)
+            .unwrap_or_else(|| fallback.get_or_insert_with(children_by_parent).get(&pid).cloned().unwrap_or_default());
+        pids.extend(children.into_iter().filter(|child| !pids.contains(child)).collect::<Vec<_>>());
+    }
+    pids
+}
+
+fn read_tree_process(pid: u32) -> Option<ProcessInfo> {
+    let stat = process::read_stat(pid)?;
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid)).map(|s| s.trim().to_owned()).unwrap_or_default();
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    let mut info = ProcessInfo::new(pid, name, stat.utime + stat.stime, stat.rss_pages * page_size);
+    info.stat = stat;
+    info.io = IoStats::read(pid).ok();
+    Some(info)
+}
+
+// Only the command's tree is read each sample. As a subreaper the profiler inherits
+// descendants orphaned by their parent, so those stay in the tree as its own children.
+// Processes are keyed by pid and start time, a reused pid is a different child
+fn sample(
+    root: u32,
+    cpu_tracker: &mut CpuTracker,
+    children: &mut BTreeMap<(u32, u64), ChildReport>,
+    report: &mut ProfileReport,
+    elapsed: f64,
+    first_seen: &mut BTreeMap<(u32, u64), f64>,
+) {
+    let own_pid = std::process::id();
+    let mut roots = vec![root];
+    for orphan in read_children(own_pid).unwrap_or_else(|| children_by_parent().remove(&own_pid).unwrap_or_default()) {
+        if orphan != root && children.keys().any(|(pid, _)| *pid == orphan) {
+            roots.push(orphan);
+            // An orphan that exited is a zombie until its new parent reaps it
+            unsafe { libc::waitpid(orphan as libc::pid_t, std::ptr::null_mut(), libc::WNOHANG) };
+        }
+    }
+    let mut tree: Vec<_> = tree_pids(&roots).into_iter().filter_map(read_tree_process).collect();
+    cpu_tracker.update_process_cpu(&mut tree);
+
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+    let mut total_rss = 0;
+    let mut total_pss = None;
+    for proc in &tree {
+        let key = (proc.pid, proc.stat.starttime);
+        let pss = read_pss(proc.pid);
+        total_rss += proc.memory;
+        if let Some(pss) = pss {
+            total_pss = Some(total_pss.unwrap_or(0) + pss);
+        }
+        let first = *first_seen.entry(key).or_insert(elapsed);
+        let child = children.entry(key).or_default();
+        child.pid = proc.pid;
+        child.ppid = proc.stat.ppid;
+        child.name = proc.name.clone();
+        child.user_cpu_secs = proc.stat.utime as f64 / ticks;
+        child.system_cpu_secs = proc.stat.stime as f64 / ticks;
+        child.peak_cpu_percent = child.peak_cpu_percent.max(proc.cpu_percent);
+        child.peak_rss_bytes = child.peak_rss_bytes.max(proc.memory);
+        child.peak_pss_bytes = child.peak_pss_bytes.max(pss);
+        if let Some(io) = &proc.io {
+            child.read_bytes = io.read_bytes;
+            child.written_bytes = io.write_bytes;
+        }
+        child.seen_secs = elapsed - first;
+    }
+    report.samples += 1;
+    report.peak_process_count = report.peak_process_count.max(tree.len());
+    report.peak_rss_bytes = report.peak_rss_bytes.max(total_rss);
+    report.peak_pss_bytes = report.peak_pss_bytes.max(total_pss);
+}
+
+// Ctrl-C and Ctrl-\ reach the whole foreground process group; like time(1) the profiler
+// ignores them while waiting, so the command decides whether to stop and the report still comes out
+struct IgnoreInterrupts([libc::sighandler_t; 2]);
+
+impl IgnoreInterrupts {
+    const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGQUIT];
+
+    fn new() -> Self {
+        IgnoreInterrupts(Self::SIGNALS.map(|signal| unsafe { libc::signal(signal, libc::SIG_IGN) }))
+    }
+}
+
+impl Drop for IgnoreInterrupts {
+    fn drop(&mut self) {
+        for (signal, handler) in Self::SIGNALS.iter().zip(self.0) {
+            unsafe { libc::signal(*signal, handler) };
+        }
+    }
+}
+
+// Cleared again once the command is done, so later orphans go to init as usual
+struct Subreaper;
+
+impl Subreaper {
+    fn new() -> Self {
+        unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1) };
+        Subreaper
+    }
+}
+
+impl Drop for Subreaper {
+    fn drop(&mut self) {
+        unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 0) };
+    }
+}
+
+fn timeval_secs(tv: libc::timeval) -> f64 {
+    tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0
+}
+
+// wait4 is used instead of Child::wait for its rusage, which also covers
+// descendants that exited between two samples
+pub fn profile(options: &RunOptions) -> io::Result<ProfileReport> {
+    let started = Instant::now();
+    let _subreaper = Subreaper::new();
+    let child = Command::new(&options.command[0]).args(&options.command[1..]).spawn()?;
+    let root = child.id();
+    // Only after the spawn, an ignored signal would stay ignored in the command
+    let _interrupts = IgnoreInterrupts::new();
+    let mut report = ProfileReport {
+        command: options.command.clone(),
+        ..Default::default()
+    };
+    let mut cpu_tracker = CpuTracker::new();
+    let mut children = BTreeMap::new();
+    let mut first_seen = BTreeMap::new();
+
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        sample(
+            root,
+            &mut cpu_tracker,
+            &mut children,
+            &mut report,
+            started.elapsed().as_secs_f64(),
+            &mut first_seen,
+        );
+        let waited = unsafe { libc::wait4(root as libc::pid_t, &mut status, libc::WNOHANG, &mut rusage) };
+        if waited < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if waited > 0 {
+            break;
+        }
+        thread::sleep(options.interval);
+    }
+    report.wall_time_secs = started.elapsed().as_secs_f64();
+
+    if libc::WIFEXITED(status) {
+        report.exit_code = Some(libc::WEXITSTATUS(status));
+    } else if libc::WIFSIGNALED(status) {
+        report.exit_signal = Some(libc::WTERMSIG(status));
+    }
+    report.children = children.into_values().collect();
+    let sampled = |f: fn(&ChildReport) -> f64| report.children.iter().map(f).sum::<f64>();
+    report.user_cpu_secs = timeval_secs(rusage.ru_utime).max(sampled(|c| c.user_cpu_secs));
+    report.system_cpu_secs = timeval_secs(rusage.ru_stime).max(sampled(|c| c.system_cpu_secs));
+    // ru_inblock/ru_oublock count 512 byte blocks
+    let sampled_read: u64 = report.children.iter().map(|c| c.read_bytes).sum();
+    let sampled_written: u64 = report.children.iter().map(|c| c.written_bytes).sum();
+    report.read_bytes = (rusage.ru_inblock as u64 * 512).max(sampled_read);
+    report.written_bytes = (rusage.ru_oublock as u64 * 512).max(sampled_written);
+    // ru_maxrss is in KiB and only covers the largest single process
+    report.peak_rss_bytes = report.peak_rss_bytes.max(rusage.ru_maxrss as u64 * 1024);
+    Ok(report)
+}
+
+pub fn format_report(report: &ProfileReport) -> String {
+    let mut out = String::new();
+    let exit = match (report.exit_code, report.exit_signal) {
+        (Some(code), _) => format!("exit status {}", code),
+        (_, Some(signal)) => format!("killed by signal {}", signal),
+        _ => "unknown exit".into(),
+    };
+    out.push_str(&format!("Command:            {}\n", report.command.join(" ")));
+    out.push_str(&format!("Result:             {}\n", exit));
+    out.push_str(&format!("Wall time:          {:.2}s\n", report.wall_time_secs));
+    out.push_str(&format!("User CPU time:      {:.2}s\n", report.user_cpu_secs));
+    out.push_str(&format!("System CPU time:    {:.2}s\n", report.system_cpu_secs));
+    out.push_str(&format!("Peak RSS (tree):    {}\n", format_bytes(report.peak_rss_bytes as f64)));
+    out.push_str(&format!(
+        "Peak PSS (tree):    {}\n",
+        report.peak_pss_bytes.map(|b| format_bytes(b as f64)).unwrap_or_else(|| "-".into())
+    ));
+    out.push_str(&format!("Peak processes:     {}\n", report.peak_process_count));
+    out.push_str(&format!("Bytes read:         {}\n", format_bytes(report.read_bytes as f64)));
+    out.push_str(&format!("Bytes written:      {}\n", format_bytes(report.written_bytes as f64)));
+    out.push_str(&format!("Samples:            {}\n\n", report.samples));
+    out.push_str(&format!(
+        "{:>8} {:>8} {:<16} {:>8} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9} {:>7}\n",
+        "PID", "PPID", "Name", "User", "Sys", "CPU%", "RSS", "PSS", "Read", "Written", "Seen"
+    ));
+    for child in &report.children {
+        out.push_str(&format!(
+            "{:>8} {:>8} {:<16} {:>7.2}s {:>7.2}s {:>7.1} {:>9} {:>9} {:>9} {:>9} {:>6.1}s\n",
+            child.pid,
+            child.ppid,
+            child.name,
+            child.user_cpu_secs,
+            child.system_cpu_secs,
+            child.peak_cpu_percent,
+            format_bytes(child.peak_rss_bytes as f64),
+            child.peak_pss_bytes.map(|b| format_bytes(b as f64)).unwrap_or_else(|| "-".into()),
+            format_bytes(child.read_bytes as f64),
+            format_bytes(child.written_bytes as f64),
+            child.seen_secs,
+        ));
+    }
+    out
+}
+
+// The report goes to stderr like time(1), or to --output, never mixing with the command's stdout.
+// Exits with the command's status.
+pub fn run_cli(args: &[String]) -> io::Result<()> {
+    let options = match parse_run_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let report = profile(&options)?;
+    let text = if options.json {
+        format!("{}\n", serde_json::to_string_pretty(&report).map_err(io::Error::other)?)
+    } else {
+        format_report(&report)
+    };
+    match &options.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, text) {
+                eprintln!("{}: {}", path.display(), e);
+            }
+        }
+        None => {
+            let _ = io::stderr().write_all(text.as_bytes());
+        }
+    }
+    std::process::exit(report.exit_code.unwrap_or(128 + report.exit_signal.unwrap_or(0)));
+}
+
+#[cfg(test)]
+pub(crate) fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+#[test]
+fn test_parse_run_args() {
+    let options = parse_run_args(&strings(&["--json", "-o", "report.json", "--interval", "50", "--", "make", "-j4"])).unwrap();
+    assert!(options.json);
+    assert_eq!(options.output, Some(PathBuf::from("report.json")));
+    assert_eq!(options.interval, Duration::from_millis(50));
+    assert_eq!(options.command, strings(&["make", "-j4"]));
+    assert!(parse_run_args(&strings(&["--"])).is_err());
+    assert!(parse_run_args(&strings(&["--interval", "0", "--", "ls"])).is_err());
+    assert!(parse_run_args(&strings(&["--verbose", "--", "ls"])).is_err());
+    assert!(parse_run_args(&strings(&["--output"])).is_err());
+}
+
+#[test]
+fn test_profile_follows_children() {
+    let options = RunOptions {
+        json: false,
+        output: None,
+        interval: Duration::from_millis(20),
+        command: strings(&["sh", "-c", "sleep 0.3 & sleep 0.3; wait; exit 3"]),
+    };
+    let report = profile(&options).unwrap();
+    assert_eq!(report.exit_code, Some(3));
+    assert!(report.wall_time_secs >= 0.3);
+    assert!(report.peak_process_count >= 3);
+    assert!(report.children.iter().filter(|c| c.name == "sleep").count() >= 2);
+    assert!(report.peak_rss_bytes > 0);
+    assert!(format_report(&report).contains("exit status 3"));
+
+    // An interrupt aimed at the profiler is ignored until the command is done
+    let options = RunOptions {
+        command: strings(&["sh", "-c", "kill -INT $PPID; kill -QUIT $PPID; exit 4"]),
+        ..options
+    };
+    assert_eq!(profile(&options).unwrap().exit_code, Some(4));
+}
+
+#[test]
+fn test_profile_keeps_orphaned_descendants() {
+    let options = RunOptions {
+        json: false,
+        output: None,
+        interval: Duration::from_millis(20),
+        command: strings(&["sh", "-c", "(sleep 0.5 & sleep 0.1); sleep 0.4; exit 0"]),
+    };
+    let report = profile(&options).unwrap();
+    // The background sleep loses its parent after 0.1s and is inherited by the profiler
+    let orphan = report.children.iter().find(|c| c.ppid == std::process::id() && c.name == "sleep").unwrap();
+    assert!(orphan.seen_secs >= 0.25, "the orphaned sleep was seen for {}s", orphan.seen_secs);
+}
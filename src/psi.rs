@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PressureLine {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    pub total: u64, // microseconds stalled
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ResourcePressure {
+    pub some: PressureLine,
+    pub full: Option<PressureLine>, // system wide cpu has no full line on older kernels
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Pressure {
+    pub cpu: Option<ResourcePressure>,
+    pub memory: Option<ResourcePressure>,
+    pub io: Option<ResourcePressure>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+impl PressureLine {
+    // Short window above the long window means contention is building up
+    pub fn trend(&self) -> Trend {
+        if self.avg10 > self.avg60 + 1.0 {
+            Trend::Rising
+        } else if self.avg10 + 1.0 < self.avg60 {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+}
+
+impl Pressure {
+    pub fn is_available(&self) -> bool {
+        self.cpu.is_some() || self.memory.is_some() || self.io.is_some()
+    }
+
+    // Kernels without CONFIG_PSI (or booted with psi=0) have no /proc/pressure
+    pub fn read_system() -> Option<Self> {
+        let pressure = Self::read_files(Path::new("/proc/pressure"), "");
+        pressure.is_available().then_some(pressure)
+    }
+
+    // cgroup v2 exposes the same format as cpu.pressure, memory.pressure and io.pressure
+    pub fn read_cgroup(cgroup_dir: &Path) -> Option<Self> {
+        let pressure = Self::read_files(cgroup_dir, ".pressure");
+        pressure.is_available().then_some(pressure)
+    }
+
+    fn read_files(dir: &Path, suffix: &str) -> Self {
+        let read = |resource: &str| {
+            fs::read_to_string(dir.join(format!("{}{}", resource, suffix)))
+                .ok()
+                .and_then(|content| parse_pressure(&content))
+        };
+        Self {
+            cpu: read("cpu"),
+            memory: read("memory"),
+            io: read("io"),
+        }
+    }
+}
+
+pub fn parse_pressure(content: &str) -> Option<ResourcePressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next();
+        let mut parsed = PressureLine::default();
+        for field in fields {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            match key {
+                "avg10" => parsed.avg10 = value.parse().unwrap_or(0.0),
+                "avg60" => parsed.avg60 = value.parse().unwrap_or(0.0),
+                "avg300" => parsed.avg300 = value.parse().unwrap_or(0.0),
+                "total" => parsed.total = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+        match kind {
+            Some("some") => some = Some(parsed),
+            Some("full") => full = Some(parsed),
+            _ => {}
+        }
+    }
+    Some(ResourcePressure { some: some?, full })
+}
+
+#[test]
+fn test_parse_pressure_some_and_full() {
+    let content = "some avg10=1.50 avg60=0.75 avg300=0.20 total=123456\nfull avg10=0.10 avg60=0.05 avg300=0.00 total=789\n";
+    let pressure = parse_pressure(content).unwrap();
+    assert_eq!(pressure.some.avg10, 1.5);
+    assert_eq!(pressure.some.avg300, 0.2);
+    assert_eq!(pressure.some.total, 123456);
+    assert_eq!(pressure.full.unwrap().total, 789);
+}
+
+#[test]
+fn test_parse_pressure_without_full_line() {
+    let pressure = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").unwrap();
+    assert!(pressure.full.is_none());
+    assert!(parse_pressure("").is_none());
+}
+
+#[test]
+fn test_pressure_trend() {
+    let line = |avg10, avg60| PressureLine {
+        avg10,
+        avg60,
+        ..Default::default()
+    };
+    assert_eq!(line(20.0, 5.0).trend(), Trend::Rising);
+    assert_eq!(line(1.0, 10.0).trend(), Trend::Falling);
+    assert_eq!(line(2.0, 2.5).trend(), Trend::Steady);
+}
+
+#[test]
+fn test_missing_pressure_directory_is_unavailable() {
+    assert!(Pressure::read_cgroup(Path::new("/nonexistent/cgroup")).is_none());
+}
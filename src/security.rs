@@ -0,0 +1,183 @@
+use crate::process::Status;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+
+// Bit positions from linux/capability.h
+const CAPABILITY_NAMES: [&str; 41] = [
+    "chown",
+    "dac_override",
+    "dac_read_search",
+    "fowner",
+    "fsetid",
+    "kill",
+    "setgid",
+    "setuid",
+    "setpcap",
+    "linux_immutable",
+    "net_bind_service",
+    "net_broadcast",
+    "net_admin",
+    "net_raw",
+    "ipc_lock",
+    "ipc_owner",
+    "sys_module",
+    "sys_rawio",
+    "sys_chroot",
+    "sys_ptrace",
+    "sys_pacct",
+    "sys_admin",
+    "sys_boot",
+    "sys_nice",
+    "sys_resource",
+    "sys_time",
+    "sys_tty_config",
+    "mknod",
+    "lease",
+    "audit_write",
+    "audit_control",
+    "setfcap",
+    "mac_override",
+    "mac_admin",
+    "syslog",
+    "wake_alarm",
+    "block_suspend",
+    "audit_read",
+    "perfmon",
+    "bpf",
+    "checkpoint_restore",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Capabilities {
+    pub inheritable: u64,
+    pub permitted: u64,
+    pub effective: u64,
+    pub bounding: u64,
+    pub ambient: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SecurityInfo {
+    pub capabilities: Capabilities,
+    pub seccomp: Option<u8>, // 0 disabled, 1 strict, 2 filter
+    pub seccomp_filters: Option<u32>,
+    pub no_new_privs: Option<bool>,
+    pub lsm_label: Option<String>, // AppArmor profile or SELinux context
+    pub exe_setuid: Option<bool>,
+    pub exe_setgid: Option<bool>,
+}
+
+pub fn capability_name(bit: u32) -> String {
+    CAPABILITY_NAMES
+        .get(bit as usize)
+        .map(|name| format!("cap_{}", name))
+        .unwrap_or_else(|| format!("cap_{}", bit))
+}
+
+pub fn decode_capabilities(mask: u64) -> Vec<String> {
+    (0..64).filter(|bit| mask & (1 << bit) != 0).map(capability_name).collect()
+}
+
+// Full sets (root) are summarised instead of listing 41 names
+pub fn describe_capabilities(mask: u64) -> String {
+    let all_known = (1u64 << CAPABILITY_NAMES.len()) - 1;
+    match mask {
+        0 => "none".into(),
+        m if m & all_known == all_known => match decode_capabilities(m & !all_known).as_slice() {
+            [] => "all".into(),
+            extra => format!("all, {}", extra.join(", ")),
+        },
+        m => decode_capabilities(m).join(", "),
+    }
+}
+
+pub fn seccomp_mode_name(mode: u8) -> &'static str {
+    match mode {
+        0 => "disabled",
+        1 => "strict",
+        2 => "filter",
+        _ => "unknown",
+    }
+}
+
+// Capability masks are printed as hex in /proc/[pid]/status
+pub fn parse_capabilities(status: &Status) -> Option<Capabilities> {
+    let mask = |key| u64::from_str_radix(status.value(key)?, 16).ok();
+    Some(Capabilities {
+        inheritable: mask("CapInh")?,
+        permitted: mask("CapPrm")?,
+        effective: mask("CapEff")?,
+        bounding: mask("CapBnd")?,
+        ambient: mask("CapAmb").unwrap_or(0), // older kernels have no ambient set
+    })
+}
+
+pub fn parse_status(status: &Status) -> SecurityInfo {
+    SecurityInfo {
+        capabilities: parse_capabilities(status).unwrap_or_default(),
+        seccomp: status.value("Seccomp").and_then(|v| v.parse().ok()),
+        seccomp_filters: status.value("Seccomp_filters").and_then(|v| v.parse().ok()),
+        no_new_privs: status.value("NoNewPrivs").map(|v| v == "1"),
+        ..Default::default()
+    }
+}
+
+pub fn parse_lsm_label(raw: &str) -> Option<String> {
+    let label = raw.trim_end_matches(['\0', '\n']).trim();
+    (!label.is_empty()).then(|| label.to_owned())
+}
+
+pub fn read_security_info(pid: u32) -> io::Result<SecurityInfo> {
+    let mut info = parse_status(&Status::read(pid)?);
+    info.lsm_label = fs::read_to_string(format!("/proc/{}/attr/current", pid))
+        .ok()
+        .and_then(|raw| parse_lsm_label(&raw));
+    // Follows the exe link, so this needs the same permissions as reading it
+    if let Ok(metadata) = fs::metadata(format!("/proc/{}/exe", pid)) {
+        let mode = metadata.permissions().mode();
+        info.exe_setuid = Some(mode & 0o4000 != 0);
+        info.exe_setgid = Some(mode & 0o2000 != 0);
+    }
+    Ok(info)
+}
+
+#[test]
+fn test_decode_capabilities() {
+    assert!(decode_capabilities(0).is_empty());
+    assert_eq!(
+        decode_capabilities(0x0000_0000_0000_0400 | 0x0000_0000_0020_0000),
+        vec!["cap_net_bind_service", "cap_sys_admin"]
+    );
+    assert_eq!(decode_capabilities(1 << 40), vec!["cap_checkpoint_restore"]);
+    assert_eq!(decode_capabilities(1 << 45), vec!["cap_45"]);
+}
+
+#[test]
+fn test_describe_capabilities() {
+    assert_eq!(describe_capabilities(0), "none");
+    assert_eq!(describe_capabilities(0x1ff_ffff_ffff), "all");
+    assert_eq!(describe_capabilities(0x400), "cap_net_bind_service");
+}
+
+#[test]
+fn test_parse_status_security_fields() {
+    let status = "Name:\tnginx\nCapInh:\t0000000000000000\nCapPrm:\t0000000000000400\nCapEff:\t0000000000000400\nCapBnd:\t000001ffffffffff\nCapAmb:\t0000000000000000\nNoNewPrivs:\t1\nSeccomp:\t2\nSeccomp_filters:\t3\n";
+    let info = parse_status(&Status::parse(status));
+    assert_eq!(info.capabilities.effective, 0x400);
+    assert_eq!(info.capabilities.bounding, 0x1ff_ffff_ffff);
+    assert_eq!(info.seccomp, Some(2));
+    assert_eq!(info.seccomp_filters, Some(3));
+    assert_eq!(info.no_new_privs, Some(true));
+}
+
+#[test]
+fn test_parse_lsm_label() {
+    assert_eq!(parse_lsm_label("unconfined\n").as_deref(), Some("unconfined"));
+    assert_eq!(
+        parse_lsm_label("system_u:system_r:httpd_t:s0\0").as_deref(),
+        Some("system_u:system_r:httpd_t:s0")
+    );
+    assert_eq!(parse_lsm_label("\0"), None);
+}
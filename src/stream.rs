@@ -0,0 +1,180 @@
+use crate::process::{ProcessInfo, Processes};
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+
+// Deltas kept for clients resuming after a short disconnect, one per refresh tick
+const HISTORY: usize = 120;
+
+// Events queued for one client; a client that falls this far behind is dropped and
+// resumes from history when it reconnects
+pub const SUBSCRIBER_BUFFER: usize = 256;
+
+pub type SharedStreamHub = Arc<Mutex<StreamHub>>;
+
+// One Server-Sent Event, already formatted for the wire
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub seq: u64,
+    pub text: String,
+}
+
+// Turns every refresh into a numbered delta and fans it out to connected clients
+pub struct StreamHub {
+    seq: u64,
+    current: Processes,
+    values: HashMap<u32, Value>, // last serialized form of each process, to spot changes
+    history: VecDeque<Event>,
+    subscribers: Vec<SyncSender<Event>>,
+}
+
+pub fn format_event(seq: u64, kind: &str, data: &Value) -> Event {
+    Event {
+        seq,
+        text: format!("id: {}\nevent: {}\ndata: {}\n\n", seq, kind, data),
+    }
+}
+
+impl Default for StreamHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamHub {
+    pub fn new() -> Self {
+        StreamHub {
+            seq: 0,
+            current: Processes { processes: vec![] },
+            values: HashMap::new(),
+            history: VecDeque::new(),
+            subscribers: vec![],
+        }
+    }
+
+    pub fn snapshot(&self) -> Event {
+        let data = json!({ "seq": self.seq, "processes": self.current.processes });
+        format_event(self.seq, "snapshot", &data)
+    }
+
+    // Added and removed come from update_proc, changed are the remaining processes whose fields differ
+    pub fn publish(&mut self, processes: &[ProcessInfo]) {
+        let delta = self.current.update_proc(processes);
+        let values: HashMap<u32, Value> = processes
+            .iter()
+            .map(|p| (p.pid, serde_json::to_value(p).unwrap_or(Value::Null)))
+            .collect();
+        let added_pids: Vec<u32> = delta.added.iter().map(|p| p.pid).collect();
+        let mut changed: Vec<&Value> = values
+            .iter()
+            .filter(|(pid, value)| !added_pids.contains(pid) && self.values.get(pid) != Some(value))
+            .map(|(_, value)| value)
+            .collect();
+        changed.sort_by_key(|value| value["pid"].as_u64());
+
+        self.seq += 1;
+        let data = json!({
+            "seq": self.seq,
+            "added": delta.added,
+            "removed": delta.removed.iter().map(|p| p.pid).collect::<Vec<_>>(),
+            "changed": changed,
+        });
+        let event = format_event(self.seq, "delta", &data);
+        self.values = values;
+
+        self.history.push_back(event.clone());
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+        // A failed send means the client went away or stopped reading
+        self.subscribers.retain(|subscriber| subscriber.try_send(event.clone()).is_ok());
+    }
+
+    // Events to send right away, then the live feed. A client that saw `since` gets only the
+    // deltas it missed, anyone else (or anyone too far behind) starts over with a snapshot.
+    pub fn subscribe(&mut self, since: Option<u64>) -> (Vec<Event>, Receiver<Event>) {
+        let (tx, rx) = mpsc::sync_channel(SUBSCRIBER_BUFFER);
+        self.subscribers.push(tx);
+        let oldest_kept = self.history.front().map(|e| e.seq).unwrap_or(self.seq + 1);
+        let backlog = match since {
+            Some(since) if since <= self.seq && since + 1 >= oldest_kept => {
+                self.history.iter().filter(|e| e.seq > since).cloned().collect()
+            }
+            _ => vec![self.snapshot()],
+        };
+        (backlog, rx)
+    }
+}
+
+// Last-Event-ID is what EventSource sends on reconnect, ?since= is for hand written clients
+pub fn resume_point(last_event_id: Option<&str>, query: &str) -> Option<u64> {
+    let from_query = form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "since")
+        .and_then(|(_, value)| value.parse().ok());
+    last_event_id.and_then(|id| id.trim().parse().ok()).or(from_query)
+}
+
+#[cfg(test)]
+pub(crate) fn data(event: &Event) -> Value {
+    let line = event.text.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+    serde_json::from_str(line).unwrap()
+}
+
+#[test]
+fn test_publish_deltas() {
+    let mut hub = StreamHub::new();
+    let (backlog, rx) = hub.subscribe(None);
+    assert_eq!(backlog[0].seq, 0);
+    assert!(backlog[0].text.starts_with("id: 0\nevent: snapshot\n"));
+
+    hub.publish(&[ProcessInfo::new(1, "init".into(), 0, 100), ProcessInfo::new(2, "sh".into(), 0, 100)]);
+    let first = rx.try_recv().unwrap();
+    assert_eq!(data(&first)["added"].as_array().unwrap().len(), 2);
+
+    hub.publish(&[ProcessInfo::new(1, "init".into(), 0, 200), ProcessInfo::new(3, "ls".into(), 0, 100)]);
+    let second = data(&rx.try_recv().unwrap());
+    assert_eq!(second["seq"], 2);
+    assert_eq!(second["added"][0]["pid"], 3);
+    assert_eq!(second["removed"], json!([2]));
+    assert_eq!(second["changed"][0]["memory"], 200);
+    assert_eq!(second["changed"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_subscribe_resumes_or_falls_back_to_snapshot() {
+    let mut hub = StreamHub::new();
+    for memory in 0..HISTORY as u64 + 5 {
+        hub.publish(&[ProcessInfo::new(1, "init".into(), 0, memory)]);
+    }
+    let seq = HISTORY as u64 + 5;
+    let (backlog, _) = hub.subscribe(Some(seq - 2));
+    assert_eq!(backlog.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![seq - 1, seq]);
+    assert!(hub.subscribe(Some(seq)).0.is_empty());
+
+    for stale in [Some(1), Some(seq + 10), None] {
+        let (backlog, _) = hub.subscribe(stale);
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(data(&backlog[0])["processes"][0]["memory"], seq - 1);
+    }
+}
+
+#[test]
+fn test_lagging_subscriber_is_dropped() {
+    let mut hub = StreamHub::new();
+    let (_, rx) = hub.subscribe(None);
+    for memory in 0..=SUBSCRIBER_BUFFER as u64 {
+        hub.publish(&[ProcessInfo::new(1, "init".into(), 0, memory)]);
+    }
+    assert!(hub.subscribers.is_empty());
+    assert_eq!(rx.try_iter().count(), SUBSCRIBER_BUFFER);
+    assert!(rx.recv().is_err());
+}
+
+#[test]
+fn test_resume_point() {
+    assert_eq!(resume_point(Some("42"), ""), Some(42));
+    assert_eq!(resume_point(None, "since=7"), Some(7));
+    assert_eq!(resume_point(Some("x"), "since=7"), Some(7));
+    assert_eq!(resume_point(None, ""), None);
+}
@@ -0,0 +1,177 @@
+use crate::cgroup;
+use crate::process::{self, ProcessInfo};
+use std::fs;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PauseMethod {
+    Signal,         // SIGSTOP, undone with SIGCONT
+    Freeze(String), // cgroup v2 path frozen through cgroup.freeze
+}
+
+// A process (or cgroup) stopped from this viewer, resumed on request or on exit
+#[derive(Debug, Clone, PartialEq)]
+pub struct PausedProcess {
+    pub pid: u32,
+    pub name: String,
+    pub starttime: u64,
+    pub method: PauseMethod,
+}
+
+pub fn send_signal(pid: u32, signal: i32) -> io::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// The cgroup only counts as the process's own if nothing outside its tree lives there
+pub fn own_cgroup(processes: &[ProcessInfo], pid: u32) -> Option<String> {
+    let path = processes.iter().find(|p| p.pid == pid)?.cgroup.clone()?;
+    if path == "/" {
+        return None;
+    }
+    let mut tree = process::descendants(processes, pid);
+    tree.push(pid);
+    let all_in_tree = processes
+        .iter()
+        .filter(|p| p.cgroup.as_deref() == Some(path.as_str()))
+        .all(|p| tree.contains(&p.pid));
+    all_in_tree.then_some(path)
+}
+
+pub fn set_frozen(cgroup_path: &str, frozen: bool) -> io::Result<()> {
+    let file = cgroup::cgroup_dir(cgroup_path).join("cgroup.freeze");
+    fs::write(file, if frozen { "1" } else { "0" })
+}
+
+// Stops parents before children so nothing new gets forked while the tree is being stopped.
+// The viewer never stops itself, nothing could resume it from a terminal in raw mode.
+pub fn stop(processes: &[ProcessInfo], pid: u32, tree: bool) -> (Vec<PausedProcess>, Vec<String>) {
+    let mut pids = vec![pid];
+    if tree {
+        pids.extend(process::descendants(processes, pid));
+    }
+    let own_pid = std::process::id();
+    pids.retain(|pid| *pid != own_pid);
+    let mut paused = vec![];
+    let mut errors = vec![];
+    for pid in pids {
+        let Some(proc) = processes.iter().find(|p| p.pid == pid) else {
+            continue;
+        };
+        match send_signal(pid, libc::SIGSTOP) {
+            Ok(()) => paused.push(PausedProcess {
+                pid,
+                name: proc.name.clone(),
+                starttime: proc.stat.starttime,
+                method: PauseMethod::Signal,
+            }),
+            Err(e) => errors.push(format!("PID {}: {}", pid, e)),
+        }
+    }
+    (paused, errors)
+}
+
+// "/a" contains "/a" and "/a/b", but not "/ab"
+pub fn cgroup_contains(ancestor: &str, path: &str) -> bool {
+    path == ancestor || path.strip_prefix(ancestor).is_some_and(|rest| rest.starts_with('/'))
+}
+
+pub fn freeze(processes: &[ProcessInfo], pid: u32) -> Result<PausedProcess, String> {
+    let proc = processes
+        .iter()
+        .find(|p| p.pid == pid)
+        .ok_or_else(|| format!("PID {} is gone", pid))?;
+    let path = own_cgroup(processes, pid)
+        .ok_or_else(|| format!("PID {} shares its cgroup with other processes", pid))?;
+    if let Some(own) = cgroup::read_cgroup_path(std::process::id())
+        && cgroup_contains(&path, &own)
+    {
+        return Err(format!("{} contains this viewer, freezing it would freeze the viewer too", path));
+    }
+    set_frozen(&path, true).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(PausedProcess {
+        pid,
+        name: proc.name.clone(),
+        starttime: proc.stat.starttime,
+        method: PauseMethod::Freeze(path),
+    })
+}
+
+// A pid with a different start time has been reused and must not get a SIGCONT
+pub fn resume(paused: &PausedProcess) -> io::Result<()> {
+    match &paused.method {
+        PauseMethod::Freeze(path) => set_frozen(path, false),
+        PauseMethod::Signal => match process::read_stat(paused.pid) {
+            Some(stat) if stat.starttime == paused.starttime => send_signal(paused.pid, libc::SIGCONT),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "process has exited")),
+        },
+    }
+}
+
+#[cfg(test)]
+fn proc_in(pid: u32, ppid: u32, cgroup: &str) -> ProcessInfo {
+    let mut proc = ProcessInfo::new(pid, format!("p{}", pid), 0, 0);
+    proc.stat.ppid = ppid;
+    proc.cgroup = Some(cgroup.into());
+    proc
+}
+
+#[test]
+fn test_own_cgroup() {
+    let procs = vec![
+        proc_in(1, 0, "/"),
+        proc_in(10, 1, "/system.slice/nginx.service"),
+        proc_in(11, 10, "/system.slice/nginx.service"),
+        proc_in(20, 1, "/user.slice"),
+        proc_in(21, 1, "/user.slice"),
+    ];
+    assert_eq!(own_cgroup(&procs, 10).as_deref(), Some("/system.slice/nginx.service"));
+    assert_eq!(own_cgroup(&procs, 11), None);
+    assert_eq!(own_cgroup(&procs, 20), None);
+    assert_eq!(own_cgroup(&procs, 1), None);
+}
+
+#[test]
+fn test_cgroup_contains() {
+    assert!(cgroup_contains("/user.slice", "/user.slice"));
+    assert!(cgroup_contains("/user.slice", "/user.slice/session-1.scope"));
+    assert!(!cgroup_contains("/user.slice", "/user.slice-other"));
+    assert!(!cgroup_contains("/user.slice/session-1.scope", "/user.slice"));
+}
+
+#[test]
+fn test_stop_skips_the_viewer() {
+    // A parent that does not exist, with the test process as its child
+    let parent = u32::MAX / 2;
+    let mut own = ProcessInfo::new(std::process::id(), "task-manager".into(), 0, 0);
+    own.stat.ppid = parent;
+    let procs = [ProcessInfo::new(parent, "shell".into(), 0, 0), own];
+    let (paused, errors) = stop(&procs, parent, true);
+    assert!(paused.is_empty());
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with(&format!("PID {}:", parent)));
+}
+
+#[test]
+fn test_stop_and_resume_child() {
+    let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+    let pid = child.id();
+    let mut proc = ProcessInfo::new(pid, "sleep".into(), 0, 0);
+    proc.stat = process::read_stat(pid).unwrap();
+
+    let (paused, errors) = stop(&[proc], pid, true);
+    assert!(errors.is_empty());
+    assert_eq!(paused.len(), 1);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(process::read_stat(pid).unwrap().state, 'T');
+
+    resume(&paused[0]).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_ne!(process::read_stat(pid).unwrap().state, 'T');
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(resume(&paused[0]).is_err());
+}
@@ -0,0 +1,367 @@
+use crate::environ;
+use crate::process::{ProcessInfo, Status};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Mappings and environment rarely change, so every process is only re-analysed about this often
+const RESCAN_INTERVAL: Duration = Duration::from_secs(10);
+const PID_PROBE_LIMIT: u32 = 4_194_304; // PID_MAX_LIMIT on 64 bit kernels
+
+const WRITABLE_EXE_DIRS: [&str; 3] = ["/tmp/", "/dev/shm/", "/var/tmp/"];
+// Interpreters run scripts whose comm is the script name, not the interpreter
+const INTERPRETERS: [&str; 8] = ["python", "perl", "ruby", "node", "bash", "sh", "java", "php"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SuspicionFlag {
+    DeletedExe,
+    MemfdExe,
+    WritableDirExe,
+    CommMismatch,
+    LdPreload,
+    RwxMapping,
+    Hidden,
+}
+
+impl SuspicionFlag {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SuspicionFlag::DeletedExe => "DEL",
+            SuspicionFlag::MemfdExe => "MEMFD",
+            SuspicionFlag::WritableDirExe => "TMP",
+            SuspicionFlag::CommMismatch => "COMM",
+            SuspicionFlag::LdPreload => "PRELOAD",
+            SuspicionFlag::RwxMapping => "RWX",
+            SuspicionFlag::Hidden => "HIDDEN",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            SuspicionFlag::DeletedExe => "executable was deleted after start",
+            SuspicionFlag::MemfdExe => "executable runs from a memfd (fileless)",
+            SuspicionFlag::WritableDirExe => "executable runs from /tmp, /var/tmp or /dev/shm",
+            SuspicionFlag::CommMismatch => "process name differs from executable name",
+            SuspicionFlag::LdPreload => "LD_PRELOAD is set",
+            SuspicionFlag::RwxMapping => "anonymous memory that is writable and executable",
+            SuspicionFlag::Hidden => "hidden from /proc listing but reachable by pid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SuspicionReport {
+    pub flagged: Vec<(u32, String, Vec<SuspicionFlag>)>,
+    pub probed_pids: u32,
+}
+
+impl SuspicionReport {
+    // The hidden pid probe walks the whole pid range, so it only runs when a report is requested
+    pub fn build(processes: &[ProcessInfo]) -> Self {
+        let proc_root = Path::new("/proc");
+        let probed_pids = read_pid_max();
+        let mut flagged: Vec<(u32, String, Vec<SuspicionFlag>)> = processes
+            .iter()
+            .filter(|p| !p.flags.is_empty())
+            .map(|p| (p.pid, p.name.clone(), p.flags.clone()))
+            .collect();
+
+        for pid in find_hidden_pids(proc_root, probed_pids, || list_pids(proc_root)) {
+            let name = fs::read_to_string(proc_root.join(pid.to_string()).join("comm"))
+                .map(|s| s.trim().to_owned())
+                .unwrap_or_else(|_| "[Unknown]".into());
+            let mut flags = vec![SuspicionFlag::Hidden];
+            flags.extend(analyze_process(proc_root, pid));
+            flagged.push((pid, name, flags));
+        }
+
+        flagged.sort_by(|a, b| b.2.len().cmp(&a.2.len()).then(a.0.cmp(&b.0)));
+        Self { flagged, probed_pids }
+    }
+}
+
+pub fn flag_codes(flags: &[SuspicionFlag]) -> String {
+    flags.iter().map(|f| f.code()).collect::<Vec<_>>().join(",")
+}
+
+pub fn exe_flags(exe: &str) -> Vec<SuspicionFlag> {
+    let mut flags = vec![];
+    if exe.starts_with("/memfd:") {
+        flags.push(SuspicionFlag::MemfdExe);
+    } else if exe.ends_with(" (deleted)") {
+        flags.push(SuspicionFlag::DeletedExe);
+    }
+    if WRITABLE_EXE_DIRS.iter().any(|dir| exe.starts_with(dir)) {
+        flags.push(SuspicionFlag::WritableDirExe);
+    }
+    flags
+}
+
+// The interpreter's own name, optionally versioned like python3.12, perl5.36 or node20, but not shellcheck or nodemon
+fn is_interpreter(basename: &str) -> bool {
+    INTERPRETERS.iter().any(|i| {
+        basename.strip_prefix(i).is_some_and(|version| {
+            version.is_empty()
+                || (version.starts_with(|c: char| c.is_ascii_digit())
+                    && version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        })
+    })
+}
+
+// comm is truncated to 15 bytes by the kernel, so only that prefix has to match
+pub fn comm_matches_exe(comm: &str, exe: &str) -> bool {
+    let exe = exe.trim_end_matches(" (deleted)");
+    let exe = exe.strip_prefix("/memfd:").unwrap_or(exe);
+    let basename = exe.rsplit('/').next().unwrap_or(exe);
+    if is_interpreter(basename) {
+        return true;
+    }
+    let prefix: String = basename.chars().take(15).collect();
+    prefix == comm || basename.starts_with(comm)
+}
+
+// Anonymous (no backing file) mappings with rwx permissions, typical for injected shellcode and JITs
+pub fn has_rwx_anonymous_mapping(maps: &str) -> bool {
+    maps.lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(perms) = fields.get(1) else {
+            return false;
+        };
+        let path = fields.get(5).copied().unwrap_or("");
+        perms.starts_with("rwx") && (path.is_empty() || path.starts_with('['))
+    })
+}
+
+pub fn analyze_process(proc_root: &Path, pid: u32) -> Vec<SuspicionFlag> {
+    let dir = proc_root.join(pid.to_string());
+    let mut flags = vec![];
+
+    // Kernel threads have no exe, unreadable links give no evidence either way
+    if let Ok(exe) = fs::read_link(dir.join("exe")) {
+        let exe = exe.to_string_lossy();
+        flags.extend(exe_flags(&exe));
+        if let Ok(comm) = fs::read_to_string(dir.join("comm"))
+            && !comm_matches_exe(comm.trim(), &exe)
+        {
+            flags.push(SuspicionFlag::CommMismatch);
+        }
+    }
+
+    if let Ok(raw) = fs::read(dir.join("environ"))
+        && environ::parse_environ(&raw).iter().any(|v| v.key == "LD_PRELOAD")
+    {
+        flags.push(SuspicionFlag::LdPreload);
+    }
+
+    if let Ok(maps) = fs::read_to_string(dir.join("maps"))
+        && has_rwx_anonymous_mapping(&maps)
+    {
+        flags.push(SuspicionFlag::RwxMapping);
+    }
+
+    flags
+}
+
+pub fn list_pids(proc_root: &Path) -> HashSet<u32> {
+    fs::read_dir(proc_root)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().to_string_lossy().parse::<u32>().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_thread_group_leader(proc_root: &Path, pid: u32) -> bool {
+    Status::read_from(&proc_root.join(pid.to_string()).join("status"))
+        .ok()
+        .and_then(|status| status.number("Tgid"))
+        == Some(pid as u64)
+}
+
+// Rootkits filter getdents on /proc, but a direct lookup of /proc/<pid> still succeeds.
+// Threads are reachable the same way without being listed, so only thread group leaders count.
+pub fn find_hidden_pids(proc_root: &Path, max_pid: u32, list: impl Fn() -> HashSet<u32>) -> Vec<u32> {
+    let listed = list();
+    let candidates: Vec<u32> = (1..=max_pid)
+        .filter(|pid| !listed.contains(pid))
+        .filter(|pid| proc_root.join(pid.to_string()).exists())
+        .filter(|pid| is_thread_group_leader(proc_root, *pid))
+        .collect();
+
+    // Processes started during the probe show up in a second listing
+    let listed_again = list();
+    candidates.into_iter().filter(|pid| !listed_again.contains(pid)).collect()
+}
+
+pub fn read_pid_max() -> u32 {
+    fs::read_to_string("/proc/sys/kernel/pid_max")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(32768)
+        .min(PID_PROBE_LIMIT)
+}
+
+// Each pid waits between half and one and a half RESCAN_INTERVAL, so the processes of one
+// refresh are not all due again in the same later tick
+fn next_scan(now: Instant, pid: u32) -> Instant {
+    let spread = pid.wrapping_mul(2_654_435_761) % 1000; // Knuth's multiplicative hash
+    now + RESCAN_INTERVAL.mul_f64(0.5 + spread as f64 / 1000.0)
+}
+
+pub struct SuspicionScanner {
+    pub next_scan: HashMap<u32, (Vec<SuspicionFlag>, Instant)>,
+}
+
+impl SuspicionScanner {
+    pub fn new() -> Self {
+        Self {
+            next_scan: HashMap::new(),
+        }
+    }
+
+    pub fn update_process_flags(&mut self, processes: &mut [ProcessInfo]) {
+        let now = Instant::now();
+        let mut seen = HashMap::with_capacity(processes.len());
+        for proc in processes.iter_mut() {
+            let entry = match self.next_scan.remove(&proc.pid) {
+                Some((flags, due)) if now < due => (flags, due),
+                _ => (analyze_process(Path::new("/proc"), proc.pid), next_scan(now, proc.pid)),
+            };
+            proc.flags = entry.0.clone();
+            seen.insert(proc.pid, entry);
+        }
+        self.next_scan = seen;
+    }
+}
+
+#[cfg(test)]
+struct FakeProc {
+    root: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl FakeProc {
+    fn new(name: &str) -> Self {
+        let root = crate::control::temp_path(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    fn add(&self, pid: u32, exe: &str, comm: &str, environ: &[u8], maps: &str) {
+        let dir = self.root.join(pid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        std::os::unix::fs::symlink(exe, dir.join("exe")).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        fs::write(dir.join("environ"), environ).unwrap();
+        fs::write(dir.join("maps"), maps).unwrap();
+        fs::write(dir.join("status"), format!("Name:\t{}\nTgid:\t{}\n", comm, pid)).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl Drop for FakeProc {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+const CLEAN_MAPS: &str = "55d0c0a00000-55d0c0a21000 r-xp 00000000 08:01 1234 /usr/bin/nginx\n7ffd5c000000-7ffd5c021000 rw-p 00000000 00:00 0 [stack]\n";
+
+#[test]
+fn test_clean_process_has_no_flags() {
+    let fake = FakeProc::new("clean");
+    fake.add(100, "/usr/sbin/nginx", "nginx", b"PATH=/usr/bin\0", CLEAN_MAPS);
+    assert!(analyze_process(&fake.root, 100).is_empty());
+}
+
+#[test]
+fn test_deleted_and_tmp_executables() {
+    let fake = FakeProc::new("deleted");
+    fake.add(101, "/tmp/.x/miner (deleted)", "miner", b"", CLEAN_MAPS);
+    fake.add(102, "/memfd:payload (deleted)", "payload", b"", CLEAN_MAPS);
+    fake.add(103, "/dev/shm/agent", "agent", b"", CLEAN_MAPS);
+
+    assert_eq!(
+        analyze_process(&fake.root, 101),
+        vec![SuspicionFlag::DeletedExe, SuspicionFlag::WritableDirExe]
+    );
+    assert_eq!(analyze_process(&fake.root, 102), vec![SuspicionFlag::MemfdExe]);
+    assert_eq!(analyze_process(&fake.root, 103), vec![SuspicionFlag::WritableDirExe]);
+}
+
+#[test]
+fn test_comm_mismatch_preload_and_rwx() {
+    let fake = FakeProc::new("masquerade");
+    let rwx = "7f0000000000-7f0000001000 rwxp 00000000 00:00 0 \n";
+    fake.add(104, "/usr/bin/xmrig", "kworker/0:1", b"LD_PRELOAD=/lib/evil.so\0", rwx);
+
+    assert_eq!(
+        analyze_process(&fake.root, 104),
+        vec![
+            SuspicionFlag::CommMismatch,
+            SuspicionFlag::LdPreload,
+            SuspicionFlag::RwxMapping
+        ]
+    );
+}
+
+#[test]
+fn test_comm_matches_truncated_and_interpreters() {
+    assert!(comm_matches_exe("gnome-terminal-", "/usr/libexec/gnome-terminal-server"));
+    assert!(comm_matches_exe("backup.py", "/usr/bin/python3.12"));
+    assert!(comm_matches_exe("app.js", "/usr/bin/node"));
+    assert!(!comm_matches_exe("sshd", "/usr/bin/nc"));
+    assert!(!comm_matches_exe("sshd", "/usr/bin/shellcheck"));
+    assert!(!comm_matches_exe("kworker", "/tmp/python-backdoor"));
+    assert!(!comm_matches_exe("cron", "/usr/bin/nodemon"));
+}
+
+#[test]
+fn test_is_interpreter_versions() {
+    for name in ["python", "python3", "python3.12", "perl5.36", "node20", "php8.2", "sh"] {
+        assert!(is_interpreter(name), "{}", name);
+    }
+    for name in ["python.", "python3-config", "perldoc", "nodejs-x", "shellcheck", "bash.bak"] {
+        assert!(!is_interpreter(name), "{}", name);
+    }
+}
+
+#[test]
+fn test_rescans_are_spread_out() {
+    let now = Instant::now();
+    let due: HashSet<Instant> = (1000..1100).map(|pid| next_scan(now, pid)).collect();
+    assert!(due.len() > 50);
+    for at in due {
+        let wait = at - now;
+        assert!(wait >= RESCAN_INTERVAL / 2 && wait < RESCAN_INTERVAL * 3 / 2, "{:?}", wait);
+    }
+}
+
+#[test]
+fn test_rwx_file_backed_mapping_is_not_flagged() {
+    let maps = "7f0000000000-7f0000001000 rwxp 00000000 08:01 99 /usr/lib/libfoo.so\n";
+    assert!(!has_rwx_anonymous_mapping(maps));
+    assert!(has_rwx_anonymous_mapping("7f0-7f1 rwxp 00000000 00:00 0 [heap]\n"));
+}
+
+#[test]
+fn test_find_hidden_pids() {
+    let fake = FakeProc::new("hidden");
+    fake.add(1, "/sbin/init", "init", b"", CLEAN_MAPS);
+    fake.add(2, "/usr/bin/bash", "bash", b"", CLEAN_MAPS);
+    fake.add(666, "/usr/bin/rootkit", "rootkit", b"", CLEAN_MAPS);
+    // A thread of pid 2, reachable but never listed
+    fake.add(3, "/usr/bin/bash", "bash", b"", CLEAN_MAPS);
+    fs::write(fake.root.join("3").join("status"), "Name:\tbash\nTgid:\t2\n").unwrap();
+
+    assert!(find_hidden_pids(&fake.root, 1000, || list_pids(&fake.root)).is_empty());
+    // Simulate a getdents hook hiding 666 and the thread
+    let filtered = || HashSet::from([1, 2]);
+    assert_eq!(find_hidden_pids(&fake.root, 1000, filtered), vec![666]);
+}
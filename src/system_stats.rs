@@ -0,0 +1,423 @@
+use crate::psi::Pressure;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+const SECTOR_SIZE: u64 = 512; // diskstats always counts 512 byte sectors
+const HISTORY_LEN: usize = 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiskCounters {
+    pub reads: u64,
+    pub sectors_read: u64,
+    pub writes: u64,
+    pub sectors_written: u64,
+    pub io_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_drops: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_drops: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiskStats {
+    pub name: String,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+    pub iops: f64,
+    pub utilization: f64, // percent of time the device was busy
+    #[serde(skip)]
+    pub history: Vec<u64>, // read+write bytes/s, oldest first
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetStats {
+    pub name: String,
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub rx_packets_per_sec: f64,
+    pub tx_packets_per_sec: f64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_drops: u64,
+    pub tx_drops: u64,
+    #[serde(skip)]
+    pub history: Vec<u64>, // rx+tx bytes/s, oldest first
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SystemStats {
+    pub cpu_percent: f64, // busy share of all CPUs since the previous update
+    pub load: Option<LoadAverage>,
+    pub memory: Option<MemoryStats>,
+    pub disks: Vec<DiskStats>,
+    pub networks: Vec<NetStats>,
+    pub pressure: Option<Pressure>, // None if the kernel has no PSI support
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuTimes {
+    pub busy: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+    pub running: u32,
+    pub total: u32, // scheduling entities (threads)
+}
+
+// Bytes, from /proc/meminfo
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MemoryStats {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+impl SystemStats {
+    pub fn total_disk_rates(&self) -> (f64, f64) {
+        self.disks.iter().fold((0.0, 0.0), |(r, w), d| {
+            (r + d.read_bytes_per_sec, w + d.write_bytes_per_sec)
+        })
+    }
+
+    pub fn total_net_rates(&self) -> (f64, f64) {
+        self.networks.iter().fold((0.0, 0.0), |(rx, tx), n| {
+            (rx + n.rx_bytes_per_sec, tx + n.tx_bytes_per_sec)
+        })
+    }
+}
+
+// Whole disks only: partitions, loop and ram devices would double count
+pub fn parse_diskstats(content: &str, is_disk: impl Fn(&str) -> bool) -> Vec<(String, DiskCounters)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 13 {
+                return None;
+            }
+            let name = fields[2];
+            if name.starts_with("loop") || name.starts_with("ram") || !is_disk(name) {
+                return None;
+            }
+            let field = |i: usize| fields[i].parse::<u64>().unwrap_or(0);
+            Some((
+                name.to_owned(),
+                DiskCounters {
+                    reads: field(3),
+                    sectors_read: field(5),
+                    writes: field(7),
+                    sectors_written: field(9),
+                    io_ms: field(12),
+                },
+            ))
+        })
+        .collect()
+}
+
+pub fn parse_net_dev(content: &str) -> Vec<(String, NetCounters)> {
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(':')?;
+            let fields: Vec<u64> = rest
+                .split_whitespace()
+                .map(|f| f.parse::<u64>().unwrap_or(0))
+                .collect();
+            if fields.len() < 12 {
+                return None;
+            }
+            Some((
+                name.trim().to_owned(),
+                NetCounters {
+                    rx_bytes: fields[0],
+                    rx_packets: fields[1],
+                    rx_errors: fields[2],
+                    rx_drops: fields[3],
+                    tx_bytes: fields[8],
+                    tx_packets: fields[9],
+                    tx_errors: fields[10],
+                    tx_drops: fields[11],
+                },
+            ))
+        })
+        .collect()
+}
+
+// The aggregate "cpu" line; idle and iowait count as not busy
+pub fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+    let values: Vec<u64> = line.split_whitespace().skip(1).filter_map(|v| v.parse().ok()).collect();
+    let total = values.iter().take(8).sum(); // guest time is already part of user
+    let idle = values.get(3)? + values.get(4).unwrap_or(&0);
+    Some(CpuTimes { busy: total - idle, total })
+}
+
+pub fn parse_loadavg(content: &str) -> Option<LoadAverage> {
+    let fields: Vec<&str> = content.split_whitespace().collect();
+    let (running, total) = fields.get(3)?.split_once('/')?;
+    Some(LoadAverage {
+        one: fields.first()?.parse().ok()?,
+        five: fields.get(1)?.parse().ok()?,
+        fifteen: fields.get(2)?.parse().ok()?,
+        running: running.parse().ok()?,
+        total: total.parse().ok()?,
+    })
+}
+
+pub fn parse_meminfo(content: &str) -> Option<MemoryStats> {
+    let value = |key: &str| -> Option<u64> {
+        let line = content.lines().find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))?;
+        Some(line.trim().trim_end_matches("kB").trim().parse::<u64>().ok()? * 1024)
+    };
+    Some(MemoryStats {
+        total: value("MemTotal")?,
+        free: value("MemFree")?,
+        available: value("MemAvailable").unwrap_or(0),
+        buffers: value("Buffers").unwrap_or(0),
+        cached: value("Cached").unwrap_or(0),
+        swap_total: value("SwapTotal").unwrap_or(0),
+        swap_free: value("SwapFree").unwrap_or(0),
+    })
+}
+
+fn is_block_device(name: &str) -> bool {
+    Path::new("/sys/block").join(name.replace('/', "!")).exists()
+}
+
+fn rate(current: u64, previous: u64, elapsed: f64) -> f64 {
+    if elapsed > 0.0 {
+        current.saturating_sub(previous) as f64 / elapsed
+    } else {
+        0.0
+    }
+}
+
+fn push_history(history: &mut VecDeque<u64>, value: f64) {
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(value as u64);
+}
+
+pub struct SystemTracker {
+    pub last_disks: HashMap<String, DiskCounters>,
+    pub last_networks: HashMap<String, NetCounters>,
+    pub disk_history: HashMap<String, VecDeque<u64>>,
+    pub net_history: HashMap<String, VecDeque<u64>>,
+    pub last_cpu: Option<CpuTimes>,
+    pub last_check: Instant,
+}
+
+impl SystemTracker {
+    pub fn new() -> Self {
+        let mut tracker = Self {
+            last_disks: HashMap::new(),
+            last_networks: HashMap::new(),
+            disk_history: HashMap::new(),
+            net_history: HashMap::new(),
+            last_cpu: None,
+            last_check: Instant::now(),
+        };
+        tracker.update();
+        tracker
+    }
+
+    pub fn update(&mut self) -> SystemStats {
+        let disks = fs::read_to_string("/proc/diskstats")
+            .map(|c| parse_diskstats(&c, is_block_device))
+            .unwrap_or_default();
+        let networks = fs::read_to_string("/proc/net/dev")
+            .map(|c| parse_net_dev(&c))
+            .unwrap_or_default();
+        let mut stats = self.update_from(disks, networks, Instant::now());
+        stats.pressure = Pressure::read_system();
+        stats.load = fs::read_to_string("/proc/loadavg").ok().and_then(|c| parse_loadavg(&c));
+        stats.memory = fs::read_to_string("/proc/meminfo").ok().and_then(|c| parse_meminfo(&c));
+        if let Some(cpu) = fs::read_to_string("/proc/stat").ok().and_then(|c| parse_cpu_times(&c)) {
+            stats.cpu_percent = self.update_cpu(cpu);
+        }
+        stats
+    }
+
+    pub fn update_cpu(&mut self, cpu: CpuTimes) -> f64 {
+        let previous = self.last_cpu.replace(cpu).unwrap_or_default();
+        let total = cpu.total.saturating_sub(previous.total);
+        if total == 0 {
+            return 0.0;
+        }
+        cpu.busy.saturating_sub(previous.busy) as f64 * 100.0 / total as f64
+    }
+
+    pub fn update_from(
+        &mut self,
+        disks: Vec<(String, DiskCounters)>,
+        networks: Vec<(String, NetCounters)>,
+        now: Instant,
+    ) -> SystemStats {
+        let elapsed = now.duration_since(self.last_check).as_secs_f64();
+        let mut stats = SystemStats::default();
+
+        for (name, counters) in &disks {
+            let previous = self.last_disks.get(name).copied().unwrap_or(*counters);
+            let read = rate(counters.sectors_read, previous.sectors_read, elapsed) * SECTOR_SIZE as f64;
+            let write = rate(counters.sectors_written, previous.sectors_written, elapsed) * SECTOR_SIZE as f64;
+            let iops = rate(counters.reads, previous.reads, elapsed) + rate(counters.writes, previous.writes, elapsed);
+            // io_ms is milliseconds spent doing I/O, so ms per second / 10 is percent
+            let utilization = (rate(counters.io_ms, previous.io_ms, elapsed) / 10.0).min(100.0);
+
+            let history = self.disk_history.entry(name.clone()).or_default();
+            push_history(history, read + write);
+
+            stats.disks.push(DiskStats {
+                name: name.clone(),
+                read_bytes_per_sec: read,
+                write_bytes_per_sec: write,
+                iops,
+                utilization,
+                history: history.iter().copied().collect(),
+            });
+        }
+
+        for (name, counters) in &networks {
+            let previous = self.last_networks.get(name).copied().unwrap_or(*counters);
+            let rx = rate(counters.rx_bytes, previous.rx_bytes, elapsed);
+            let tx = rate(counters.tx_bytes, previous.tx_bytes, elapsed);
+
+            let history = self.net_history.entry(name.clone()).or_default();
+            push_history(history, rx + tx);
+
+            stats.networks.push(NetStats {
+                name: name.clone(),
+                rx_bytes_per_sec: rx,
+                tx_bytes_per_sec: tx,
+                rx_packets_per_sec: rate(counters.rx_packets, previous.rx_packets, elapsed),
+                tx_packets_per_sec: rate(counters.tx_packets, previous.tx_packets, elapsed),
+                rx_errors: counters.rx_errors,
+                tx_errors: counters.tx_errors,
+                rx_drops: counters.rx_drops,
+                tx_drops: counters.tx_drops,
+                history: history.iter().copied().collect(),
+            });
+        }
+
+        self.disk_history.retain(|name, _| disks.iter().any(|(n, _)| n == name));
+        self.net_history.retain(|name, _| networks.iter().any(|(n, _)| n == name));
+        self.last_disks = disks.into_iter().collect();
+        self.last_networks = networks.into_iter().collect();
+        self.last_check = now;
+        stats
+    }
+}
+
+#[test]
+fn test_parse_diskstats_skips_partitions_and_loop_devices() {
+    let content = "   8       0 sda 1000 10 8000 500 2000 20 16000 900 0 1200 1400 0 0 0 0\n\
+                   8       1 sda1 900 10 7000 400 1900 20 15000 800 0 1100 1200 0 0 0 0\n\
+                   7       0 loop0 10 0 20 1 0 0 0 0 0 1 1 0 0 0 0\n";
+    let disks = parse_diskstats(content, |name| name == "sda" || name == "loop0");
+    assert_eq!(disks.len(), 1);
+    assert_eq!(disks[0].0, "sda");
+    assert_eq!(disks[0].1.sectors_read, 8000);
+    assert_eq!(disks[0].1.sectors_written, 16000);
+    assert_eq!(disks[0].1.io_ms, 1200);
+}
+
+#[test]
+fn test_parse_net_dev() {
+    let content = "Inter-|   Receive                                                |  Transmit\n \
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n \
+    lo: 1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0\n \
+  eth0: 5000      50    1    2    0     0          0         0     7000      70    3    4    0     0       0          0\n";
+    let nets = parse_net_dev(content);
+    assert_eq!(nets.len(), 2);
+    assert_eq!(nets[1].0, "eth0");
+    assert_eq!(nets[1].1.rx_bytes, 5000);
+    assert_eq!(nets[1].1.rx_drops, 2);
+    assert_eq!(nets[1].1.tx_packets, 70);
+    assert_eq!(nets[1].1.tx_errors, 3);
+}
+
+#[test]
+fn test_system_tracker_computes_rates() {
+    let start = Instant::now();
+    let mut tracker = SystemTracker {
+        last_disks: HashMap::new(),
+        last_networks: HashMap::new(),
+        disk_history: HashMap::new(),
+        net_history: HashMap::new(),
+        last_cpu: None,
+        last_check: start,
+    };
+    let disk = |sectors_read, io_ms| DiskCounters {
+        sectors_read,
+        io_ms,
+        ..Default::default()
+    };
+    let net = |rx_bytes| NetCounters {
+        rx_bytes,
+        ..Default::default()
+    };
+
+    tracker.update_from(vec![("sda".into(), disk(0, 0))], vec![("eth0".into(), net(0))], start);
+    let stats = tracker.update_from(
+        vec![("sda".into(), disk(4, 500))],
+        vec![("eth0".into(), net(2048))],
+        start + std::time::Duration::from_secs(1),
+    );
+
+    assert_eq!(stats.disks[0].read_bytes_per_sec, 2048.0);
+    assert_eq!(stats.disks[0].utilization, 50.0);
+    assert_eq!(stats.disks[0].history, vec![0, 2048]);
+    assert_eq!(stats.networks[0].rx_bytes_per_sec, 2048.0);
+}
+
+#[test]
+fn test_parse_cpu_times_and_usage() {
+    let stat = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 100 0 50 800 50 0 0 0 0 0\n";
+    let cpu = parse_cpu_times(stat).unwrap();
+    assert_eq!(cpu, CpuTimes { busy: 150, total: 1000 });
+
+    let mut tracker = SystemTracker {
+        last_disks: HashMap::new(),
+        last_networks: HashMap::new(),
+        disk_history: HashMap::new(),
+        net_history: HashMap::new(),
+        last_cpu: Some(cpu),
+        last_check: Instant::now(),
+    };
+    assert_eq!(tracker.update_cpu(CpuTimes { busy: 200, total: 1100 }), 50.0);
+}
+
+#[test]
+fn test_parse_loadavg_and_meminfo() {
+    let load = parse_loadavg("0.52 0.58 0.59 3/467 12345\n").unwrap();
+    assert_eq!(load.one, 0.52);
+    assert_eq!(load.fifteen, 0.59);
+    assert_eq!((load.running, load.total), (3, 467));
+
+    let memory = parse_meminfo("MemTotal:       16000000 kB\nMemFree:         1000000 kB\nMemAvailable:    8000000 kB\nSwapTotal:             0 kB\n").unwrap();
+    assert_eq!(memory.total, 16_000_000 * 1024);
+    assert_eq!(memory.available, 8_000_000 * 1024);
+    assert_eq!(memory.swap_free, 0);
+}
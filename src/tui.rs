@@ -1,25 +1,44 @@
 use crate::app::App;
 use crate::app::AppState;
-use std::{thread, time::Duration};
-use syscalls::*;
+use crate::app::ProcessOrder;
+use crate::cgroup;
+use crate::namespaces;
+use crate::oom;
+use crate::columns::{format_bytes, Column};
+use crate::details::{self, DetailTab, ProcessDetails, SecurityRow};
+use crate::environ::EnvDiff;
+use crate::foreground;
+use crate::kill;
+use crate::launcher::Stream;
+use crate::limits;
+use crate::security;
+use crate::suspicious;
+use crate::psi::{Pressure, PressureLine, ResourcePressure, Trend};
+use crate::system_stats::SystemStats;
+use crate::web_tui;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use crossterm::{
-    event::{self, KeyCode, KeyEventKind},
+    event::{self, KeyCode, KeyEvent, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout},
-    style::{Style, Stylize},
-    widgets::{Block, Cell, Clear, List, ListItem, Paragraph, Row, Table, TableState},
-    Terminal,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Cell, Clear, List, ListItem, Paragraph, Row, Sparkline, Table, TableState, Tabs},
+    Frame, Terminal,
 };
 use std::io::{self, stdout};
 
 pub struct Tui {
     terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
     state: TableState,
+    handover: Option<(u32, i32)>, // confirmed foreground job, handed over once App is unlocked
 }
 
 impl Tui {
@@ -33,6 +52,7 @@ impl Tui {
         Ok(Self {
             terminal,
             state: TableState::default(),
+            handover: None,
         })
     }
 
@@ -42,207 +62,1336 @@ impl Tui {
         Ok(())
     }
 
-    pub fn draw(&mut self, app: &mut App) -> io::Result<()> {
-        self.terminal.draw(|frame| {
+    // Returns what was drawn, so it can be mirrored elsewhere
+    // The returned copy is what the web view shows, with the detail tabs blanked out
+    pub fn draw(&mut self, app: &mut App) -> io::Result<Buffer> {
+        let mut private_area = None;
+        let frame = self.terminal.draw(|frame| {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
                     Constraint::Length(1),
                     Constraint::Length(1),
+                    Constraint::Length(1),
+                    Constraint::Length(system_panel_height(&app.system, app.show_system_panel)),
                     Constraint::Min(1),
                     Constraint::Length(3),
                 ])
                 .split(frame.area());
 
-                let header = Row::new(vec![
-                    Cell::from("PID"),
-                    Cell::from("Name"),
-                    Cell::from("Memory(KB)"),
-                    Cell::from("CPU-Time"),
-                    Cell::from("CPU%"),
-                ])
+                let header = Row::new(app.columns.iter().map(|column| {
+                    if *column == app.sort_column {
+                        let arrow = if app.process_order == ProcessOrder::Ascending { "▲" } else { "▼" };
+                        Cell::from(format!("{}{}", column.header(), arrow))
+                    } else {
+                        Cell::from(column.header())
+                    }
+                }))
                 .style(Style::default().bold());
 
                 let rows = app.processes.iter().enumerate().map(|(i, item)| {
-                    let memory_kb = item.memory / 1024;
                     let style = if i == app.selected_proc {
                         Style::default().fg(ratatui::style::Color::Yellow)
+                    } else if app.marked_pid == Some(item.pid) {
+                        Style::default().fg(ratatui::style::Color::Cyan)
+                    } else if item.is_stopped() || app.is_paused(item.pid) {
+                        Style::default().fg(ratatui::style::Color::LightMagenta)
                     } else {
                         Style::default()
                     };
 
-                    Row::new(vec![
-                        Cell::from(item.pid.to_string()),
-                        Cell::from(item.name.clone()),
-                        Cell::from(memory_kb.to_string()),
-                        Cell::from(item.cpu_time.to_string()),
-                        Cell::from(format!("{:.1}", item.cpu_percent)),
-                    ])
+                    Row::new(app.columns.iter().map(|column| Cell::from(column.cell(item))))
                     .style(style)
                 });
 
-            let widths =
-            [
-                Constraint::Length(9),
-                Constraint::Length(25),
-                Constraint::Length(12),
-                Constraint::Length(10),
-                Constraint::Length(10),
-            ];
+            let widths: Vec<Constraint> = app.columns.iter().map(|c| Constraint::Length(c.width())).collect();
             let table = Table::new(rows, widths)
                 .header(header)
-                .block(Block::bordered().title("Process Info").bg(ratatui::style::Color::Black).border_style(if app.state == AppState::Normal {
+                .block(Block::bordered().title(process_table_title(app)).bg(ratatui::style::Color::Black).border_style(if app.state == AppState::Normal {
                     ratatui::style::Color::LightRed
                 } else {
                     ratatui::style::Color::White
                 }))
                .column_spacing(1); // optional: space between columns
 
-            let filter_display = Paragraph::new(format!("Filter: {}", app.filter_string))
+            let filter_display = if let (AppState::ForegroundConfirm, Some((pid, pgrp))) = (&app.state, app.foreground_target) {
+                Paragraph::new(format!(
+                    "PID {} (process group {}) shares this terminal. The viewer pauses until the job exits or is stopped again (Ctrl+Z).",
+                    pid, pgrp
+                ))
+                .block(
+                    Block::bordered()
+                        .title("Bring job to the foreground? y: yes, n: no")
+                        .bg(ratatui::style::Color::Black)
+                        .border_style(ratatui::style::Color::LightRed),
+                )
+            } else if app.state == AppState::RunPrompt {
+                Paragraph::new(format!("Run: {}", app.run_input)).block(
+                    Block::bordered()
+                        .title("[--cwd=DIR] [--nice=N] [--cpus=0,2-3] [KEY=VALUE ...] program [args ...] (Enter: run, Esc: cancel):")
+                        .bg(ratatui::style::Color::Black)
+                        .border_style(ratatui::style::Color::LightRed),
+                )
+            } else if app.state == AppState::OomAdjust {
+                let pid = app.selected_process().map(|p| p.pid).unwrap_or_default();
+                Paragraph::new(format!("oom_score_adj: {}", app.oom_adj_input)).block(
+                    Block::bordered()
+                        .title(format!("New oom_score_adj for PID {} (-1000..1000, Enter: apply, Esc: cancel):", pid))
+                        .bg(ratatui::style::Color::Black)
+                        .border_style(ratatui::style::Color::LightRed),
+                )
+            } else {
+                Paragraph::new(format!("Filter: {}", app.filter_string))
                 .block(Block::bordered().title("Filter Input:").bg(ratatui::style::Color::Black).border_style(if app.state == AppState::Filtering {
                     ratatui::style::Color::LightRed
                 } else {
                     ratatui::style::Color::White
-                }));
+                }))
+            };
                 let (help_msg, mode_str) = match app.state {
                     AppState::Filtering => (
                         "Esc: stop filtering",
                         "Mode: Filtering",
                     ),
                     AppState::Normal => (
-                        "↑[k]/↓[j]: Navigate || Enter: Select || q: Quit || /: Filter || r: reload Processes || ←: sort desc. || →: sort asc.",
+                        "↑[k]/↓[j]: Navigate || Enter: Select || q: Quit || /: Filter || r: reload Processes || ←: sort desc. || →: sort asc. || s: sort column || o: iotop mode || p: system panel || g: cgroups || n: namespaces || e: elevated capabilities || a: suspicious process report || v: OOM victim order || u: resume all paused || x: kill all matching filter || c: run command || w: command output || m: mark for diff",
                         "Mode: Normal",
                     ),
                     AppState::ProcessMenu => (
-                        "↑[k]/↓[j]: Navigate || k: Kill Process || f: bring to foreground (not working) || b: back to Process List",
+                        "↑[k]/↓[j]: Navigate || k: Kill Process || K: signal process tree || i: Inspect || o: adjust oom_score_adj || s/t: stop process/tree || c/C: continue process/tree || z: freeze cgroup || f: bring to foreground (terminal jobs) || b: back to Process List",
                         "Mode: Process Menu",
                     ),
+                    AppState::Cgroups => (
+                        "↑[k]/↓[j]: Navigate || Enter: show processes of cgroup || g/b/Esc: back to Process List || q: Quit",
+                        "Mode: Cgroups",
+                    ),
+                    AppState::Namespaces => (
+                        "↑[k]/↓[j]: Navigate || Enter: show processes of namespace set || n/b/Esc: back to Process List || q: Quit",
+                        "Mode: Namespaces",
+                    ),
+                    AppState::Report => (
+                        "↑[k]/↓[j]: Navigate || Enter: inspect process || a: rescan || b/Esc: back to Process List || q: Quit",
+                        "Mode: Suspicious Process Report",
+                    ),
+                    AppState::OomAdjust => (
+                        "Enter: apply || Esc: cancel",
+                        "Mode: Adjust oom_score_adj",
+                    ),
+                    AppState::OomVictims => (
+                        "↑[k]/↓[j]: Navigate || Enter: inspect process || v/b/Esc: back to Process List || q: Quit",
+                        "Mode: OOM Victim Order",
+                    ),
+                    AppState::RunPrompt => (
+                        "Enter: run || Esc: cancel",
+                        "Mode: Run Command",
+                    ),
+                    AppState::Output => (
+                        "↑[k]/↓[j]: scroll || G: follow newest output || c: run another command || b/Esc: back to Process List || q: Quit",
+                        "Mode: Command Output",
+                    ),
+                    AppState::ForegroundConfirm => (
+                        "y/Enter: hand over the terminal || n/Esc: cancel",
+                        "Mode: Bring to Foreground",
+                    ),
+                    AppState::KillConfirm => (
+                        "y/Enter: send signal || s: change signal || o: toggle tree order || ↑[k]/↓[j]: scroll || n/Esc: cancel",
+                        "Mode: Confirm Signal",
+                    ),
+                    AppState::KillResults => (
+                        "↑[k]/↓[j]: scroll || b/Esc: back to Process List || q: Quit",
+                        "Mode: Signal Results",
+                    ),
+                    AppState::Details => (
+                        "Tab/←/→: switch tab || ↑[k]/↓[j]: scroll || /: search || d: diff with marked process || e: edit soft limit (Limits) || o: package owners (Hashes) || b/Esc: back",
+                        "Mode: Details",
+                    ),
                 };
 
                 let help_text = Paragraph::new(help_msg)
                     .style(Style::new().bg(ratatui::style::Color::Black).fg(ratatui::style::Color::White)).alignment(Alignment::Center);
 
 
-                let mode_display = Paragraph::new(mode_str)
+                let mut mode_spans = vec![Span::raw(mode_str)];
+                match &app.status_message {
+                    Some(Ok(message)) => mode_spans.push(Span::styled(format!(" | {}", message), Style::default().fg(Color::LightGreen))),
+                    Some(Err(message)) => mode_spans.push(Span::styled(format!(" | {}", message), Style::default().fg(Color::LightRed).bold())),
+                    None => {}
+                }
+                let mode_display = Paragraph::new(Line::from(mode_spans))
                     .style(Style::new().bg(ratatui::style::Color::Black).fg(ratatui::style::Color::Red)).alignment(Alignment::Center);
 
 
             frame.render_widget(&mode_display, chunks[0]);
-            frame.render_widget(&help_text, chunks[1]);
-            frame.render_stateful_widget(table, chunks[2], &mut self.state);
-            frame.render_widget(filter_display, chunks[3]);
+            frame.render_widget(pressure_header(app), chunks[1]);
+            frame.render_widget(&help_text, chunks[2]);
+            draw_system_panel(frame, &app.system, app.show_system_panel, chunks[3]);
+            match app.state {
+                AppState::Cgroups => draw_cgroup_groups(frame, app, chunks[4]),
+                AppState::Namespaces => draw_namespace_groups(frame, app, chunks[4]),
+                AppState::Report => draw_report(frame, app, chunks[4]),
+                AppState::OomVictims => draw_oom_victims(frame, app, chunks[4]),
+                AppState::KillConfirm => draw_kill_preview(frame, app, chunks[4]),
+                AppState::KillResults => draw_kill_results(frame, app, chunks[4]),
+                AppState::Output => draw_output(frame, app, chunks[4]),
+                _ => {
+                    // The selection can move without a key press, e.g. when following a launched process
+                    self.state.select(Some(app.selected_proc));
+                    frame.render_stateful_widget(table, chunks[4], &mut self.state)
+                }
+            }
+            frame.render_widget(filter_display, chunks[5]);
 
             if app.state == AppState::ProcessMenu {
+                let popup_block = Block::bordered().title("Process Actions").bg(ratatui::style::Color::Black);
+
+                let mut options = vec![
+                    ListItem::new("  [k] Kill Process"),
+                    ListItem::new("  [K] Signal process tree (with preview)"),
+                    ListItem::new("  [i] Inspect (environment, security, limits, hashes)"),
+                    ListItem::new("  [o] Adjust oom_score_adj"),
+                    ListItem::new("  [s] Stop process (SIGSTOP)"),
+                    ListItem::new("  [t] Stop process and all descendants"),
+                    ListItem::new("  [c] Continue process, [C] continue with descendants"),
+                    ListItem::new("  [z] Freeze the process's own cgroup (cgroup.freeze)"),
+                ];
+                if app.selected_process().is_some_and(|p| p.has_tty()) {
+                    options.push(ListItem::new("  [f] Bring to foreground (terminal job)"));
+                }
+                options.push(ListItem::new("  [b] Back to Process List"));
+
                 let popup_layout = Layout::default()
                     .direction(Direction::Vertical)
                     .constraints([
                         Constraint::Length(1),
                         Constraint::Length(1),
                         Constraint::Percentage(40),
-                        Constraint::Length(5),
+                        Constraint::Length(options.len() as u16 + 2),
                         Constraint::Percentage(40),
                     ])
                     .split(frame.area());
 
-                let popup_block = Block::bordered().title("Process Actions").bg(ratatui::style::Color::Black);
-
-                let options = vec![
-                    ListItem::new("  [k] Kill Process"),
-                    ListItem::new("  [f] Bring to foreground (not working)"),
-                    ListItem::new("  [b] Back to Process List"),
-                ];
-
                 let options_list = List::new(options).block(popup_block);
                 let black_bg = Block::default().style(Style::default().bg(ratatui::style::Color::Black));
 
                 frame.render_widget(Clear, frame.area());
-                frame.render_widget(mode_display, popup_layout[0]);
-                frame.render_widget(help_text, popup_layout[1]);
+                frame.render_widget(&mode_display, popup_layout[0]);
+                frame.render_widget(&help_text, popup_layout[1]);
                 frame.render_widget(&black_bg, popup_layout[2]);
                 frame.render_widget(options_list, popup_layout[3]);
                 frame.render_widget(&black_bg, popup_layout[4]);
                 }
+
+            if let Some(details) = &app.details {
+                let details_layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(1)])
+                    .split(frame.area());
+
+                frame.render_widget(Clear, frame.area());
+                frame.render_widget(mode_display, details_layout[0]);
+                frame.render_widget(help_text, details_layout[1]);
+                private_area = Some(draw_details(frame, details, app.marked_pid, details_layout[2]));
+            }
         })?;
+        let mut buffer = frame.buffer.clone();
+        if let Some(area) = private_area {
+            web_tui::mask(&mut buffer, area, "Process details are only shown in the terminal");
+        }
+        Ok(buffer)
+    }
+
+    // Views with background work (reports, hashes, launched commands) progress without key presses
+    fn poll_background(app: &mut App) {
+        match app.state {
+            AppState::Report => app.poll_report(),
+            AppState::Output => app.poll_launched(),
+            AppState::Details => {
+                if let Some(details) = app.details.as_mut() {
+                    details.poll_hashes();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Err asks the caller to quit
+    pub fn handle_input(&mut self, app: &mut App) -> Result<(), ()> {
+        Self::poll_background(app);
+        if event::poll(Duration::from_millis(50)).map_err(|_| ())?
+            && let event::Event::Key(key) = event::read().map_err(|_| ())?
+            && key.kind == KeyEventKind::Press
+        {
+            return self.handle_key(app, key);
+        }
         Ok(())
     }
 
-    pub fn handle_input_normal(&mut self, app: &mut App) -> Result<(), ()> {
-        if event::poll(Duration::from_millis(50)).map_err(|_| ())? {
-            if let event::Event::Key(key) = event::read().map_err(|_| ())? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') => return Err(()),
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            if app.processes.is_empty() != true
-                                && app.selected_proc < app.processes.len() - 1
-                            {
-                                app.selected_proc += 1;
-                                self.state.select(Some(app.selected_proc));
-                            }
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            if app.selected_proc > 0 {
-                                app.selected_proc -= 1;
-                                self.state.select(Some(app.selected_proc));
-                            }
-                        }
-                        KeyCode::Left => app.sort_descending(),
-                        KeyCode::Right => app.sort_ascending(),
-                        KeyCode::Enter => app.state = AppState::ProcessMenu,
-                        KeyCode::Char('/') => app.state = AppState::Filtering,
-                        KeyCode::Char('r') => {
-                            app.reload_processes();
-                        }
-                        _ => {}
-                    }
+    // Also used for keys forwarded from the web view
+    pub fn handle_key(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match app.state {
+            AppState::Normal => self.handle_input_normal(app, key),
+            AppState::Filtering => self.handle_input_filtering(app, key),
+            AppState::ProcessMenu => self.handle_input_processmenu(app, key),
+            AppState::Details => self.handle_input_details(app, key),
+            AppState::Cgroups => self.handle_input_cgroups(app, key),
+            AppState::Namespaces => self.handle_input_namespaces(app, key),
+            AppState::Report => self.handle_input_report(app, key),
+            AppState::OomAdjust => self.handle_input_oom_adjust(app, key),
+            AppState::OomVictims => self.handle_input_oom_victims(app, key),
+            AppState::KillConfirm => self.handle_input_kill_confirm(app, key),
+            AppState::KillResults => self.handle_input_kill_results(app, key),
+            AppState::ForegroundConfirm => self.handle_input_foreground_confirm(app, key),
+            AppState::RunPrompt => self.handle_input_run_prompt(app, key),
+            AppState::Output => self.handle_input_output(app, key),
+        }
+    }
+
+    fn handle_input_normal(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        app.status_message = None;
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j')
+                if !app.processes.is_empty() && app.selected_proc < app.processes.len() - 1 =>
+            {
+                app.selected_proc += 1;
+                app.followed_pid = None;
+                self.state.select(Some(app.selected_proc));
+            }
+            KeyCode::Up | KeyCode::Char('k') if app.selected_proc > 0 => {
+                app.selected_proc -= 1;
+                app.followed_pid = None;
+                self.state.select(Some(app.selected_proc));
+            }
+            KeyCode::Left => app.sort_descending(),
+            KeyCode::Right => app.sort_ascending(),
+            KeyCode::Enter if app.selected_process().is_some() => app.state = AppState::ProcessMenu,
+            KeyCode::Char('/') => app.state = AppState::Filtering,
+            KeyCode::Char('m') => app.toggle_mark_selected(),
+            KeyCode::Char('s') => app.cycle_sort_column(),
+            KeyCode::Char('o') => app.toggle_io_only(),
+            KeyCode::Char('p') => app.show_system_panel = !app.show_system_panel,
+            KeyCode::Char('g') => app.open_cgroup_view(),
+            KeyCode::Char('n') => app.open_namespace_view(),
+            KeyCode::Char('e') => app.toggle_elevated_only(),
+            KeyCode::Char('a') => app.open_report(),
+            KeyCode::Char('v') => app.open_oom_view(),
+            KeyCode::Char('u') => app.resume_all(),
+            KeyCode::Char('x') => app.preview_kill_matching(),
+            KeyCode::Char('c') => app.start_run_prompt(),
+            KeyCode::Char('w') => app.open_output_view(),
+            KeyCode::Char('r') => {
+                app.reload_processes();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_processmenu(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Char('k') => app.preview_kill_selected(),
+            KeyCode::Char('i') => app.open_details(),
+            KeyCode::Char('K') => app.preview_kill_tree(),
+            KeyCode::Char('o') => app.start_oom_adjust(),
+            KeyCode::Char('s') => app.stop_selected(false),
+            KeyCode::Char('t') => app.stop_selected(true),
+            KeyCode::Char('c') => app.resume_selected(false),
+            KeyCode::Char('C') => app.resume_selected(true),
+            KeyCode::Char('z') => app.freeze_selected(),
+            KeyCode::Char('f') if app.selected_process().is_some_and(|p| p.has_tty()) => app.request_foreground(),
+            KeyCode::Char('b') => {
+                app.state = AppState::Normal;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_filtering(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Esc => app.state = AppState::Normal,
+            KeyCode::Backspace => {
+                app.filter_string.pop();
+                app.apply_filter();
+            }
+            KeyCode::Char(c) => {
+                app.filter_string.push(c);
+                app.apply_filter();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_run_prompt(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Esc => app.state = AppState::Normal,
+            KeyCode::Enter => app.launch(),
+            KeyCode::Backspace => {
+                app.run_input.pop();
+            }
+            KeyCode::Char(c) => app.run_input.push(c),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_output(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        let lines = app.launched.last().map(|r| r.output.lock().unwrap().len()).unwrap_or(0);
+        let scroll = app.output_scroll.unwrap_or(lines.saturating_sub(1));
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j') => app.output_scroll = Some((scroll + 1).min(lines.saturating_sub(1))),
+            KeyCode::Up | KeyCode::Char('k') => app.output_scroll = Some(scroll.saturating_sub(1)),
+            KeyCode::Char('G') | KeyCode::End => app.output_scroll = None,
+            KeyCode::Char('c') => app.start_run_prompt(),
+            KeyCode::Esc | KeyCode::Char('b') => app.state = AppState::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_cgroups(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j') if app.selected_group + 1 < app.cgroup_groups.len() => {
+                app.selected_group += 1
+            }
+            KeyCode::Up | KeyCode::Char('k') if app.selected_group > 0 => app.selected_group -= 1,
+            KeyCode::Enter => {
+                app.filter_selected_cgroup();
+                self.state.select(Some(app.selected_proc));
+            }
+            KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('g') => app.state = AppState::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_namespaces(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j') if app.selected_group + 1 < app.namespace_groups.len() => {
+                app.selected_group += 1
+            }
+            KeyCode::Up | KeyCode::Char('k') if app.selected_group > 0 => app.selected_group -= 1,
+            KeyCode::Enter => {
+                app.filter_selected_namespaces();
+                self.state.select(Some(app.selected_proc));
+            }
+            KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('n') => app.state = AppState::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_report(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        let flagged = app.report.as_ref().map(|r| r.flagged.len()).unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j') if app.selected_group + 1 < flagged => app.selected_group += 1,
+            KeyCode::Up | KeyCode::Char('k') if app.selected_group > 0 => app.selected_group -= 1,
+            KeyCode::Char('a') => app.open_report(),
+            KeyCode::Enter => {
+                let selected = app.report.as_ref().and_then(|r| r.flagged.get(app.selected_group));
+                if let Some((pid, name, _)) = selected {
+                    app.details = Some(ProcessDetails::load(*pid, name.clone()));
+                    app.state = AppState::Details;
                 }
             }
+            KeyCode::Esc | KeyCode::Char('b') => app.state = AppState::Normal,
+            _ => {}
         }
         Ok(())
     }
 
-    // To-do: remove the sleep after killing, find a better solution
-    pub fn handle_input_processmenu(&mut self, app: &mut App) -> Result<(), ()> {
-        if event::poll(Duration::from_millis(50)).map_err(|_| ())? {
-            if let event::Event::Key(key) = event::read().map_err(|_| ())? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('k') => unsafe {
-                            syscall!(Sysno::kill, app.processes[app.selected_proc].pid, 9);
-                            app.state = AppState::Normal;
-                            thread::sleep(Duration::from_millis(100));
-                            app.reload_processes();
-                        },
-                        KeyCode::Char('b') => {
-                            app.state = AppState::Normal;
-                        }
-                        _ => {}
-                    }
+    fn handle_input_oom_adjust(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Enter => app.apply_oom_adjust(),
+            KeyCode::Esc => app.state = AppState::Normal,
+            KeyCode::Backspace => {
+                app.oom_adj_input.pop();
+            }
+            KeyCode::Char(c) if c.is_ascii_digit() || c == '-' => app.oom_adj_input.push(c),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_oom_victims(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        let victims = oom::victim_order(&app.all_processes);
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j') if app.selected_group + 1 < victims.len() => app.selected_group += 1,
+            KeyCode::Up | KeyCode::Char('k') if app.selected_group > 0 => app.selected_group -= 1,
+            KeyCode::Enter => {
+                if let Some(proc) = victims.get(app.selected_group) {
+                    app.details = Some(ProcessDetails::load(proc.pid, proc.name.clone()));
+                    app.state = AppState::Details;
                 }
             }
+            KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('v') => app.state = AppState::Normal,
+            _ => {}
         }
         Ok(())
     }
 
-    pub fn handle_input_filtering(&mut self, app: &mut App) -> Result<(), ()> {
-        if event::poll(Duration::from_millis(50)).map_err(|_| ())? {
-            if let event::Event::Key(key) = event::read().map_err(|_| ())? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Esc => app.state = AppState::Normal,
-                        KeyCode::Backspace => {
-                            app.filter_string.pop();
-                            app.apply_filter();
-                        }
-                        KeyCode::Char(c) => {
-                            app.filter_string.push(c);
-                            app.apply_filter();
-                        }
-                        _ => {}
-                    }
+    fn handle_input_foreground_confirm(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                self.handover = app.foreground_target.take();
+                app.state = AppState::Normal;
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.foreground_target = None;
+                app.state = AppState::Normal;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Leaves the alternate screen so the job can draw on the terminal, like a shell's fg.
+    // Called without the App lock held, as the job may keep the terminal for as long as it likes.
+    pub fn hand_over_terminal(&mut self, app: &Mutex<App>) -> Result<(), ()> {
+        let Some((pid, pgrp)) = self.handover.take() else {
+            return Ok(());
+        };
+        self.cleanup().map_err(|_| ())?;
+        let result = foreground::hand_over_terminal(pid, pgrp);
+        enable_raw_mode().map_err(|_| ())?;
+        execute!(stdout(), EnterAlternateScreen).map_err(|_| ())?;
+        self.terminal.clear().map_err(|_| ())?;
+        let mut app = app.lock().unwrap();
+        app.status_message = Some(
+            result
+                .map(|_| format!("PID {} left the foreground", pid))
+                .map_err(|e| format!("PID {}: cannot hand over the terminal: {}", pid, e)),
+        );
+        app.reload_processes();
+        Ok(())
+    }
+
+    fn handle_input_kill_confirm(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        let targets = app.kill_preview.as_ref().map(|p| p.targets.len()).unwrap_or(0);
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => app.confirm_kill(),
+            KeyCode::Char('s') => app.cycle_kill_signal(),
+            KeyCode::Char('o') => app.toggle_kill_order(),
+            KeyCode::Down | KeyCode::Char('j') if app.selected_group + 1 < targets => app.selected_group += 1,
+            KeyCode::Up | KeyCode::Char('k') if app.selected_group > 0 => app.selected_group -= 1,
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.kill_preview = None;
+                app.state = AppState::Normal;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_kill_results(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        match key.code {
+            KeyCode::Char('q') => return Err(()),
+            KeyCode::Down | KeyCode::Char('j') if app.selected_group + 1 < app.kill_results.len() => {
+                app.selected_group += 1
+            }
+            KeyCode::Up | KeyCode::Char('k') if app.selected_group > 0 => app.selected_group -= 1,
+            KeyCode::Esc | KeyCode::Char('b') => app.state = AppState::Normal,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_input_details(&mut self, app: &mut App, key: KeyEvent) -> Result<(), ()> {
+        let marked_pid = app.marked_pid;
+        let hash_cache = app.hash_cache.clone();
+        let Some(details) = app.details.as_mut() else {
+            app.close_details();
+            return Ok(());
+        };
+
+        if let Some(input) = details.limit_edit.as_mut() {
+            match key.code {
+                KeyCode::Esc => details.limit_edit = None,
+                KeyCode::Enter => details.apply_limit_edit(),
+                KeyCode::Backspace => {
+                    input.pop();
                 }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
             }
+            return Ok(());
+        }
+
+        if details.searching {
+            match key.code {
+                KeyCode::Esc | KeyCode::Enter => details.searching = false,
+                KeyCode::Backspace => details.pop_search(),
+                KeyCode::Char(c) => details.push_search(c),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('b') => app.close_details(),
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                details.switch_tab(details.tab.next(), hash_cache)
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                details.switch_tab(details.tab.prev(), hash_cache)
+            }
+            KeyCode::Char('o') if details.tab == DetailTab::Hashes && details.hashes_pending.is_none() => {
+                details.start_hashing(hash_cache, true)
+            }
+            KeyCode::Down | KeyCode::Char('j') => details.scroll_down(),
+            KeyCode::Up | KeyCode::Char('k') => details.scroll_up(),
+            KeyCode::Char('/') => details.searching = true,
+            KeyCode::Char('e') if details.tab == DetailTab::Limits => details.start_limit_edit(),
+            KeyCode::Char('d') if details.tab == DetailTab::Environment => {
+                details.toggle_env_diff(marked_pid.filter(|pid| *pid != details.pid))
+            }
+            _ => {}
         }
         Ok(())
     }
 }
+
+fn process_table_title(app: &App) -> String {
+    let mut title = "Process Info".to_string();
+    if app.io_only {
+        title.push_str(" (iotop: only processes with disk I/O)");
+    }
+    if app.elevated_only {
+        title.push_str(" (audit: elevated capabilities)");
+    }
+    if let Some(path) = &app.cgroup_filter {
+        title.push_str(&format!(" (cgroup {}, g: all cgroups)", cgroup::short_name(path)));
+    }
+    if app.namespace_filter.is_some() {
+        title.push_str(" (single namespace set, n: all namespaces)");
+    }
+    if !app.paused.is_empty() {
+        title.push_str(&format!(" ({} paused by me, u: resume all)", app.paused.len()));
+    }
+    title
+}
+
+fn format_optional_bytes(bytes: Option<u64>) -> String {
+    bytes.map(|b| format_bytes(b as f64)).unwrap_or_else(|| "-".into())
+}
+
+fn draw_cgroup_groups(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let header = Row::new(vec![
+        "Cgroup", "Procs", "CPU%", "RSS", "mem.current", "mem.max", "CPU time", "pids.current", "Path",
+    ])
+    .style(Style::default().bold());
+
+    // Keep the selected group visible without a TableState
+    let visible_rows = area.height.saturating_sub(3) as usize;
+    let offset = app.selected_group.saturating_sub(visible_rows.saturating_sub(1));
+
+    let rows: Vec<Row> = app
+        .cgroup_groups
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, group)| {
+            let style = if i == app.selected_group {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                group.label.clone(),
+                group.process_count.to_string(),
+                format!("{:.1}", group.cpu_percent),
+                format_bytes(group.memory as f64),
+                format_optional_bytes(group.stats.memory_current),
+                match (group.stats.memory_current, group.stats.memory_max) {
+                    (_, Some(max)) => format_bytes(max as f64),
+                    (Some(_), None) => "max".into(),
+                    (None, None) => "-".into(),
+                },
+                group
+                    .stats
+                    .cpu_usage_usec
+                    .map(|usec| format!("{:.1}s", usec as f64 / 1_000_000.0))
+                    .unwrap_or_else(|| "-".into()),
+                group.stats.pids_current.map(|p| p.to_string()).unwrap_or_else(|| "-".into()),
+                group.path.clone(),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(25),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(12),
+        Constraint::Length(9),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Min(10),
+    ];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::bordered()
+            .title("Cgroups")
+            .bg(Color::Black)
+            .border_style(Color::LightRed),
+    );
+    frame.render_widget(table, area);
+}
+
+fn draw_namespace_groups(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let header = Row::new(vec!["Isolated", "Procs", "pid ns", "net ns", "mnt ns", "user ns", "Members"])
+        .style(Style::default().bold());
+    let inode = |inode: Option<u64>| inode.map(|i| i.to_string()).unwrap_or_else(|| "-".into());
+
+    let visible_rows = area.height.saturating_sub(3) as usize;
+    let offset = app.selected_group.saturating_sub(visible_rows.saturating_sub(1));
+
+    let rows: Vec<Row> = app
+        .namespace_groups
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, group)| {
+            let style = if i == app.selected_group {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let members: Vec<String> = group
+                .members
+                .iter()
+                .map(|(pid, name)| format!("{} {}", pid, name))
+                .collect();
+            Row::new(vec![
+                namespaces::isolation_label(&group.differs),
+                group.members.len().to_string(),
+                inode(group.namespaces.pid),
+                inode(group.namespaces.net),
+                inode(group.namespaces.mnt),
+                inode(group.namespaces.user),
+                members.join(", "),
+            ])
+            .style(style)
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(22),
+        Constraint::Length(6),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Min(10),
+    ];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::bordered()
+            .title("Namespace sets")
+            .bg(Color::Black)
+            .border_style(Color::LightRed),
+    );
+    frame.render_widget(table, area);
+}
+
+fn draw_report(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let block = Block::bordered().bg(Color::Black).border_style(Color::LightRed);
+    let Some(report) = &app.report else {
+        let block = block.title("Suspicious processes");
+        frame.render_widget(Paragraph::new("Scanning processes and probing for hidden pids...").block(block), area);
+        return;
+    };
+    let block = block.title(format!(
+        "Suspicious processes ({} flagged, {} pids probed)",
+        report.flagged.len(),
+        report.probed_pids
+    ));
+    if report.flagged.is_empty() {
+        frame.render_widget(Paragraph::new("Nothing suspicious found.").block(block), area);
+        return;
+    }
+
+    let header = Row::new(vec!["PID", "Name", "Flags", "Findings"]).style(Style::default().bold());
+    let visible_rows = area.height.saturating_sub(3) as usize;
+    let offset = app.selected_group.saturating_sub(visible_rows.saturating_sub(1));
+    let rows: Vec<Row> = report
+        .flagged
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, (pid, name, flags))| {
+            let style = if i == app.selected_group {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let findings: Vec<&str> = flags.iter().map(|f| f.description()).collect();
+            Row::new(vec![
+                pid.to_string(),
+                name.clone(),
+                suspicious::flag_codes(flags),
+                findings.join("; "),
+            ])
+            .style(style)
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(9),
+        Constraint::Length(20),
+        Constraint::Length(22),
+        Constraint::Min(10),
+    ];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+// Shows the most recently launched command; output_scroll is the last visible line
+fn draw_output(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let Some(running) = app.launched.last() else {
+        return;
+    };
+    let block = Block::bordered()
+        .title(format!(
+            "Output of PID {} `{}` ({}{})",
+            running.pid,
+            running.command_line,
+            running.status_label(),
+            if app.output_scroll.is_none() { ", following" } else { "" }
+        ))
+        .bg(Color::Black)
+        .border_style(Color::LightRed);
+    let output = running.output.lock().unwrap();
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let last = app.output_scroll.unwrap_or(output.len().saturating_sub(1)).min(output.len().saturating_sub(1));
+    let first = (last + 1).saturating_sub(visible_rows);
+    let lines: Vec<Line> = output
+        .iter()
+        .skip(first)
+        .take(visible_rows)
+        .map(|line| match line.stream {
+            Stream::Stdout => Line::raw(line.text.clone()),
+            Stream::Stderr => Line::styled(line.text.clone(), Style::default().fg(Color::LightRed)),
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn draw_kill_preview(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let Some(preview) = &app.kill_preview else {
+        return;
+    };
+    let order = match preview.root {
+        Some(_) => format!(", {}", app.kill_order.label()),
+        None => String::new(),
+    };
+    let block = Block::bordered()
+        .title(format!(
+            "Send {} to {} ({} processes{})? y: confirm, n: cancel",
+            kill::signal_name(preview.signal),
+            preview.description,
+            preview.targets.len(),
+            order
+        ))
+        .bg(Color::Black)
+        .border_style(Color::LightRed);
+    let visible_rows = area.height.saturating_sub(3) as usize;
+    let offset = app.selected_group.saturating_sub(visible_rows.saturating_sub(1));
+    let rows: Vec<Row> = preview
+        .targets
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, target)| {
+            let style = if i == app.selected_group {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![(i + 1).to_string(), target.pid.to_string(), target.name.clone()]).style(style)
+        })
+        .collect();
+    let header = Row::new(vec!["#", "PID", "Name"]).style(Style::default().bold());
+    let widths = [Constraint::Length(6), Constraint::Length(9), Constraint::Min(10)];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+fn draw_kill_results(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let failed = app.kill_results.iter().filter(|o| o.result.is_err()).count();
+    let block = Block::bordered()
+        .title(format!(
+            "Signal results ({} succeeded, {} failed)",
+            app.kill_results.len() - failed,
+            failed
+        ))
+        .bg(Color::Black)
+        .border_style(Color::LightRed);
+    let visible_rows = area.height.saturating_sub(3) as usize;
+    let offset = app.selected_group.saturating_sub(visible_rows.saturating_sub(1));
+    let rows: Vec<Row> = app
+        .kill_results
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, outcome)| {
+            let (result, color) = match &outcome.result {
+                Ok(()) => ("ok".to_string(), Color::LightGreen),
+                Err(e) => (e.clone(), Color::LightRed),
+            };
+            let style = if i == app.selected_group {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default().fg(color)
+            };
+            Row::new(vec![outcome.pid.to_string(), outcome.name.clone(), result]).style(style)
+        })
+        .collect();
+    let header = Row::new(vec!["PID", "Name", "Result"]).style(Style::default().bold());
+    let widths = [Constraint::Length(9), Constraint::Length(25), Constraint::Min(10)];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+fn draw_oom_victims(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let victims = oom::victim_order(&app.all_processes);
+    let exempt = app
+        .all_processes
+        .iter()
+        .filter(|p| p.oom_score_adj == Some(oom::OOM_SCORE_ADJ_MIN))
+        .count();
+    let block = Block::bordered()
+        .title(format!(
+            "OOM killer victim order (highest oom_score first, {} exempt with oom_score_adj -1000)",
+            exempt
+        ))
+        .bg(Color::Black)
+        .border_style(Color::LightRed);
+
+    let header = Row::new(vec!["#", "PID", "Name", "OOM", "OOM-Adj", "Memory"]).style(Style::default().bold());
+    let visible_rows = area.height.saturating_sub(3) as usize;
+    let offset = app.selected_group.saturating_sub(visible_rows.saturating_sub(1));
+    let rows: Vec<Row> = victims
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, proc)| {
+            let style = if i == app.selected_group {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                (i + 1).to_string(),
+                proc.pid.to_string(),
+                proc.name.clone(),
+                Column::OomScore.cell(proc),
+                Column::OomScoreAdj.cell(proc),
+                format_bytes(proc.memory as f64),
+            ])
+            .style(style)
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Length(9),
+        Constraint::Length(25),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Min(10),
+    ];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+fn pressure_spans(label: &str, pressure: Option<&ResourcePressure>) -> Vec<Span<'static>> {
+    let styled = |line: &PressureLine| {
+        let color = match line.trend() {
+            Trend::Rising => Color::LightRed,
+            Trend::Falling => Color::LightGreen,
+            Trend::Steady => Color::White,
+        };
+        Span::styled(
+            format!("{:.1}/{:.1}/{:.1}", line.avg10, line.avg60, line.avg300),
+            Style::default().fg(color),
+        )
+    };
+
+    let mut spans = vec![Span::raw(format!(" {} ", label))];
+    match pressure {
+        Some(p) => {
+            spans.push(styled(&p.some));
+            if let Some(full) = &p.full {
+                spans.push(Span::raw(" full "));
+                spans.push(styled(full));
+            }
+        }
+        None => spans.push(Span::raw("-")),
+    }
+    spans
+}
+
+fn pressure_resource_spans(pressure: &Pressure) -> Vec<Span<'static>> {
+    let mut spans = pressure_spans("CPU", pressure.cpu.as_ref());
+    spans.extend(pressure_spans("MEM", pressure.memory.as_ref()));
+    spans.extend(pressure_spans("IO", pressure.io.as_ref()));
+    spans
+}
+
+// some/full avg10/avg60/avg300, red when the 10s average is above the 60s average
+fn pressure_header(app: &App) -> Paragraph<'static> {
+    let mut spans = vec![Span::styled("PSI avg10/60/300:", Style::default().bold())];
+    match &app.system.pressure {
+        Some(pressure) => spans.extend(pressure_resource_spans(pressure)),
+        None => spans.push(Span::raw(" PSI unavailable")),
+    }
+    if let Some((path, pressure)) = &app.cgroup_pressure {
+        spans.push(Span::styled(format!(" || cgroup {}:", path), Style::default().bold()));
+        spans.extend(pressure_resource_spans(pressure));
+    }
+    Paragraph::new(Line::from(spans)).style(Style::new().bg(Color::Black).fg(Color::White))
+}
+
+const SYSTEM_PANEL_MAX_ROWS: usize = 8;
+
+fn system_panel_height(system: &SystemStats, expanded: bool) -> u16 {
+    if !expanded {
+        return 1;
+    }
+    let rows = system.disks.len().max(system.networks.len()).clamp(1, SYSTEM_PANEL_MAX_ROWS);
+    rows as u16 + 2
+}
+
+fn draw_system_panel(frame: &mut Frame, system: &SystemStats, expanded: bool, area: ratatui::layout::Rect) {
+    if !expanded {
+        let (read, write) = system.total_disk_rates();
+        let (rx, tx) = system.total_net_rates();
+        let summary = format!(
+            "Disk R {}/s W {}/s || Net RX {}/s TX {}/s || p: expand",
+            format_bytes(read),
+            format_bytes(write),
+            format_bytes(rx),
+            format_bytes(tx)
+        );
+        frame.render_widget(
+            Paragraph::new(summary).style(Style::new().bg(Color::Black).fg(Color::White)),
+            area,
+        );
+        return;
+    }
+
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    let disk_lines: Vec<(String, &[u64])> = system
+        .disks
+        .iter()
+        .map(|d| {
+            (
+                format!(
+                    "{:<8} R {:>8}/s W {:>8}/s {:>6.0} IOPS {:>5.1}%",
+                    d.name,
+                    format_bytes(d.read_bytes_per_sec),
+                    format_bytes(d.write_bytes_per_sec),
+                    d.iops,
+                    d.utilization
+                ),
+                d.history.as_slice(),
+            )
+        })
+        .collect();
+    let net_lines: Vec<(String, &[u64])> = system
+        .networks
+        .iter()
+        .map(|n| {
+            (
+                format!(
+                    "{:<8} RX {:>8}/s {:>5.0}p/s TX {:>8}/s {:>5.0}p/s err {} drop {}",
+                    n.name,
+                    format_bytes(n.rx_bytes_per_sec),
+                    n.rx_packets_per_sec,
+                    format_bytes(n.tx_bytes_per_sec),
+                    n.tx_packets_per_sec,
+                    n.rx_errors + n.tx_errors,
+                    n.rx_drops + n.tx_drops
+                ),
+                n.history.as_slice(),
+            )
+        })
+        .collect();
+
+    draw_throughput_block(frame, "Disks (p: collapse)", &disk_lines, halves[0]);
+    draw_throughput_block(frame, "Network", &net_lines, halves[1]);
+}
+
+fn draw_throughput_block(frame: &mut Frame, title: &str, lines: &[(String, &[u64])], area: ratatui::layout::Rect) {
+    let block = Block::bordered().title(title.to_string()).bg(Color::Black);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(1); lines.len().min(SYSTEM_PANEL_MAX_ROWS)])
+        .split(inner);
+
+    for ((text, history), row) in lines.iter().zip(rows.iter()) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(1), Constraint::Length(20)])
+            .split(*row);
+        frame.render_widget(Paragraph::new(text.as_str()), columns[0]);
+        let visible = &history[history.len().saturating_sub(columns[1].width as usize)..];
+        frame.render_widget(
+            Sparkline::default().data(visible).style(Style::default().fg(Color::LightGreen)),
+            columns[1],
+        );
+    }
+}
+
+// Returns the area below the tab titles, which may show secrets such as environment variables
+fn draw_details(frame: &mut Frame, details: &ProcessDetails, marked_pid: Option<u32>, area: ratatui::layout::Rect) -> ratatui::layout::Rect {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1), Constraint::Length(3)])
+        .split(area);
+
+    let titles: Vec<&str> = DetailTab::ALL.iter().map(|t| t.title()).collect();
+    let tabs = Tabs::new(titles)
+        .select(details.tab.index())
+        .highlight_style(Style::default().fg(Color::Yellow).bold())
+        .block(
+            Block::bordered()
+                .title(format!("PID {} ({})", details.pid, details.name))
+                .bg(Color::Black),
+        );
+    frame.render_widget(tabs, chunks[0]);
+
+    match details.tab {
+        DetailTab::Environment => draw_environment(frame, details, marked_pid, chunks[1]),
+        DetailTab::Security => draw_security(frame, details, chunks[1]),
+        DetailTab::Limits => draw_limits(frame, details, chunks[1]),
+        DetailTab::Hashes => draw_hashes(frame, details, chunks[1]),
+    }
+
+    if let (Some(input), Some(limit)) = (&details.limit_edit, details.selected_limit()) {
+        let edit = Paragraph::new(format!("New soft limit: {}", input)).block(
+            Block::bordered()
+                .title(format!("{} (number or 'unlimited', Enter: apply, Esc: cancel):", limit.name))
+                .bg(Color::Black)
+                .border_style(Color::LightRed),
+        );
+        frame.render_widget(edit, chunks[2]);
+        return chunks[1].union(chunks[2]);
+    }
+    let search = Paragraph::new(format!("Search: {}", details.search)).block(
+        Block::bordered()
+            .title("Search Input:")
+            .bg(Color::Black)
+            .border_style(if details.searching { Color::LightRed } else { Color::White }),
+    );
+    frame.render_widget(search, chunks[2]);
+    chunks[1].union(chunks[2])
+}
+
+fn draw_environment(frame: &mut Frame, details: &ProcessDetails, marked_pid: Option<u32>, area: ratatui::layout::Rect) {
+    let block = Block::bordered().bg(Color::Black);
+
+    if let Some(diff_view) = &details.env_diff {
+        let block = block.title(format!(
+            "Environment diff: PID {} vs PID {}",
+            details.pid, diff_view.other_pid
+        ));
+        if let Err(e) = &diff_view.diff {
+            frame.render_widget(Paragraph::new(format!("Cannot diff: {}", e)).block(block), area);
+            return;
+        }
+
+        let rows: Vec<Row> = details
+            .visible_env_diff()
+            .into_iter()
+            .skip(details.scroll)
+            .map(|d| match d {
+                EnvDiff::OnlyLeft(var) => Row::new(vec![var.key.clone(), var.value.clone(), "<unset>".into()])
+                    .style(Style::default().fg(Color::LightRed)),
+                EnvDiff::OnlyRight(var) => Row::new(vec![var.key.clone(), "<unset>".into(), var.value.clone()])
+                    .style(Style::default().fg(Color::LightGreen)),
+                EnvDiff::Changed { key, left, right } => {
+                    Row::new(vec![key.clone(), left.clone(), right.clone()]).style(Style::default().fg(Color::Yellow))
+                }
+            })
+            .collect();
+        let header = Row::new(vec![
+            "Variable".to_string(),
+            format!("PID {}", details.pid),
+            format!("PID {}", diff_view.other_pid),
+        ])
+        .style(Style::default().bold());
+        let widths = [Constraint::Percentage(30), Constraint::Percentage(35), Constraint::Percentage(35)];
+        frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+        return;
+    }
+
+    let block = block.title(match marked_pid {
+        Some(pid) if pid != details.pid => format!("Environment (d: diff with marked PID {})", pid),
+        _ => "Environment (mark a second process with m to diff)".to_string(),
+    });
+    if let Err(e) = &details.environ {
+        frame.render_widget(Paragraph::new(format!("Cannot read environment: {}", e)).block(block), area);
+        return;
+    }
+
+    let rows: Vec<Row> = details
+        .visible_environ()
+        .into_iter()
+        .skip(details.scroll)
+        .map(|var| {
+            if var.is_security_relevant() {
+                Row::new(vec!["!".to_string(), var.key.clone(), var.value.clone()])
+                    .style(Style::default().fg(Color::LightRed))
+            } else {
+                Row::new(vec![String::new(), var.key.clone(), var.value.clone()])
+            }
+        })
+        .collect();
+    let header = Row::new(vec!["", "Variable", "Value"]).style(Style::default().bold());
+    let widths = [Constraint::Length(1), Constraint::Percentage(30), Constraint::Percentage(70)];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+fn draw_security(frame: &mut Frame, details: &ProcessDetails, area: ratatui::layout::Rect) {
+    let block = Block::bordered().title("Security posture").bg(Color::Black);
+    let info = match &details.security {
+        Ok(info) => info,
+        Err(e) => {
+            frame.render_widget(Paragraph::new(format!("Cannot read status: {}", e)).block(block), area);
+            return;
+        }
+    };
+
+    let lines: Vec<Line> = details::security_rows(info)
+        .into_iter()
+        .map(|row| match row {
+            SecurityRow::Heading(title) => Line::from(Span::styled(title, Style::default().fg(Color::Yellow).bold())),
+            SecurityRow::Capability(label, mask) => {
+                let style = if label == "Effective" && mask != 0 {
+                    Style::default().fg(Color::LightRed)
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    Span::styled(format!("{:<12}", label), Style::default().bold()),
+                    Span::styled(format!("{:016x}  {}", mask, security::describe_capabilities(mask)), style),
+                ])
+            }
+            SecurityRow::Field(label, value) => {
+                Line::from(vec![Span::styled(format!("{:<16}", label), Style::default().bold()), Span::raw(value)])
+            }
+            SecurityRow::Blank => Line::from(""),
+        })
+        .collect();
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(ratatui::widgets::Wrap { trim: false })
+        .scroll((details.scroll as u16, 0));
+    frame.render_widget(paragraph, area);
+}
+
+fn draw_limits(frame: &mut Frame, details: &ProcessDetails, area: ratatui::layout::Rect) {
+    let block = Block::bordered().bg(Color::Black);
+    let block = match &details.limit_message {
+        Some(Ok(message)) => block.title(Span::styled(message.clone(), Style::default().fg(Color::LightGreen))),
+        Some(Err(message)) => block.title(Span::styled(message.clone(), Style::default().fg(Color::LightRed))),
+        None => block.title("Resource limits (e: edit soft limit of selected row)"),
+    };
+    let limits = match &details.limits {
+        Ok(limits) => limits,
+        Err(e) => {
+            frame.render_widget(Paragraph::new(format!("Cannot read limits: {}", e)).block(block), area);
+            return;
+        }
+    };
+
+    let format_amount = |value: Option<u64>, units: &str| match value {
+        Some(v) if units == "bytes" => format_bytes(v as f64),
+        v => limits::format_limit(v),
+    };
+    let rows: Vec<Row> = limits
+        .iter()
+        .enumerate()
+        .map(|(i, limit)| {
+            let usage = match (limit.usage, limit.usage_percent()) {
+                (Some(usage), Some(percent)) => format!("{} ({:.0}%)", format_amount(Some(usage), &limit.units), percent),
+                (Some(usage), None) => format_amount(Some(usage), &limit.units),
+                (None, _) => "-".into(),
+            };
+            let style = if i == details.scroll {
+                Style::default().fg(Color::Yellow)
+            } else if limit.usage_percent().is_some_and(|p| p >= 80.0) {
+                Style::default().fg(Color::LightRed)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                limit.name.clone(),
+                format_amount(limit.soft, &limit.units),
+                format_amount(limit.hard, &limit.units),
+                limit.units.clone(),
+                usage,
+            ])
+            .style(style)
+        })
+        .collect();
+    let header = Row::new(vec!["Limit", "Soft", "Hard", "Units", "Usage"]).style(Style::default().bold());
+    let widths = [
+        Constraint::Length(24),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Min(10),
+    ];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+fn draw_hashes(frame: &mut Frame, details: &ProcessDetails, area: ratatui::layout::Rect) {
+    let title = if details.hashes_pending.is_some() {
+        "SHA-256 of executable and mapped libraries (hashing...)"
+    } else {
+        "SHA-256 of executable and mapped libraries (o: look up owning package)"
+    };
+    let block = Block::bordered().title(title).bg(Color::Black);
+    let hashes = match &details.hashes {
+        Some(Ok(hashes)) => hashes,
+        Some(Err(e)) => {
+            frame.render_widget(Paragraph::new(format!("Cannot read executable: {}", e)).block(block), area);
+            return;
+        }
+        None => {
+            frame.render_widget(Paragraph::new("Hashing...").block(block), area);
+            return;
+        }
+    };
+
+    let rows: Vec<Row> = hashes
+        .iter()
+        .skip(details.scroll)
+        .map(|file| {
+            let (hash, style) = match &file.sha256 {
+                Ok(hash) => (hash.clone(), Style::default()),
+                Err(e) => (e.clone(), Style::default().fg(Color::LightRed)),
+            };
+            let package = match &file.package {
+                Some(Ok(package)) => package.clone(),
+                Some(Err(e)) => e.clone(),
+                None => "-".into(),
+            };
+            Row::new(vec![file.path.clone(), hash, package]).style(style)
+        })
+        .collect();
+    let header = Row::new(vec!["File", "SHA-256", "Package"]).style(Style::default().bold());
+    let widths = [Constraint::Percentage(35), Constraint::Length(64), Constraint::Min(10)];
+    frame.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
@@ -0,0 +1,242 @@
+use crate::stream::{self, Event, format_event};
+#[cfg(test)]
+use crate::stream::data;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::buffer::{Buffer, Cell};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+
+pub type SharedFrameHub = Arc<Mutex<FrameHub>>;
+
+// The terminal frame as last drawn, fanned out to browsers as full frames and cell diffs
+#[derive(Default)]
+pub struct FrameHub {
+    seq: u64,
+    last: Option<Buffer>,
+    subscribers: Vec<SyncSender<Event>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyRequest {
+    pub key: String, // a single character or a DOM key name such as Enter or ArrowUp
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub shift: bool,
+}
+
+// The standard 16 colours as xterm draws them
+const ANSI: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5", "#7f7f7f", "#ff0000",
+    "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+// None leaves the browser's default foreground or background
+pub fn css_color(color: Color) -> Option<String> {
+    let index = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+        Color::Indexed(i) => i,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+    };
+    Some(match index {
+        0..=15 => ANSI[index as usize].to_owned(),
+        // 6x6x6 colour cube, then a grey ramp
+        16..=231 => {
+            let level = |v: u8| if v == 0 { 0 } else { v * 40 + 55 };
+            let i = index - 16;
+            format!("#{:02x}{:02x}{:02x}", level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let grey = (index - 232) * 10 + 8;
+            format!("#{:02x}{:02x}{:02x}", grey, grey, grey)
+        }
+    })
+}
+
+// [x, y, symbol, fg, bg, modifier bits], kept short since a full frame has thousands
+pub fn cell_json(x: u16, y: u16, cell: &Cell) -> Value {
+    json!([x, y, cell.symbol(), css_color(cell.fg), css_color(cell.bg), cell.modifier.bits()])
+}
+
+fn full_frame(seq: u64, buffer: &Buffer) -> Event {
+    let cells: Vec<Value> = buffer
+        .content
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            let (x, y) = buffer.pos_of(i);
+            cell_json(x, y, cell)
+        })
+        .collect();
+    let data = json!({ "width": buffer.area.width, "height": buffer.area.height, "cells": cells });
+    format_event(seq, "frame", &data)
+}
+
+// Blanks an area of a published frame, leaving a note where its first line was
+pub fn mask(buffer: &mut Buffer, area: Rect, note: &str) {
+    let area = area.intersection(buffer.area);
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            buffer[(x, y)].reset();
+        }
+    }
+    if !area.is_empty() {
+        buffer.set_stringn(area.x, area.y, note, area.width as usize, Style::default().fg(Color::DarkGray));
+    }
+}
+
+impl FrameHub {
+    // Diffs are only worked out while someone is watching
+    pub fn publish(&mut self, buffer: Buffer) {
+        if !self.subscribers.is_empty() {
+            let event = match &self.last {
+                Some(last) if last.area == buffer.area => {
+                    let cells: Vec<Value> = last.diff(&buffer).into_iter().map(|(x, y, c)| cell_json(x, y, c)).collect();
+                    (!cells.is_empty()).then(|| format_event(self.seq + 1, "diff", &json!({ "cells": cells })))
+                }
+                _ => Some(full_frame(self.seq + 1, &buffer)),
+            };
+            if let Some(event) = event {
+                self.seq += 1;
+                self.subscribers.retain(|subscriber| subscriber.try_send(event.clone()).is_ok());
+            }
+        }
+        self.last = Some(buffer);
+    }
+
+    // Every viewer starts from a full frame, diffs only make sense on top of one.
+    // A viewer that falls behind is dropped and starts over from a full frame on reconnect.
+    pub fn subscribe(&mut self) -> (Vec<Event>, Receiver<Event>) {
+        let (tx, rx) = mpsc::sync_channel(stream::SUBSCRIBER_BUFFER);
+        self.subscribers.push(tx);
+        let backlog = self.last.iter().map(|buffer| full_frame(self.seq, buffer)).collect();
+        (backlog, rx)
+    }
+}
+
+pub fn parse_key(body: &str) -> Result<KeyEvent, String> {
+    let request: KeyRequest = serde_json::from_str(body).map_err(|e| format!("invalid request body: {}", e))?;
+    let mut modifiers = KeyModifiers::NONE;
+    modifiers.set(KeyModifiers::CONTROL, request.ctrl);
+    modifiers.set(KeyModifiers::ALT, request.alt);
+    let mut chars = request.key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match request.key.as_str() {
+            "Enter" => KeyCode::Enter,
+            "Escape" | "Esc" => KeyCode::Esc,
+            "Backspace" => KeyCode::Backspace,
+            "Tab" if request.shift => KeyCode::BackTab,
+            "Tab" => KeyCode::Tab,
+            "ArrowUp" | "Up" => KeyCode::Up,
+            "ArrowDown" | "Down" => KeyCode::Down,
+            "ArrowLeft" | "Left" => KeyCode::Left,
+            "ArrowRight" | "Right" => KeyCode::Right,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Delete" => KeyCode::Delete,
+            other => return Err(format!("unsupported key '{}'", other)),
+        },
+    };
+    // Shift is already part of the character, crossterm reports upper case letters the same way
+    if request.shift && !matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+        modifiers.insert(KeyModifiers::SHIFT);
+    }
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+pub fn describe_key(key: &KeyEvent) -> String {
+    let mut description = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        description.push_str("Ctrl+");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        description.push_str("Alt+");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) {
+        description.push_str("Shift+");
+    }
+    description.push_str(&key.code.to_string());
+    description
+}
+
+#[test]
+fn test_css_color() {
+    assert_eq!(css_color(Color::Reset), None);
+    assert_eq!(css_color(Color::LightRed).as_deref(), Some("#ff0000"));
+    assert_eq!(css_color(Color::Rgb(1, 2, 255)).as_deref(), Some("#0102ff"));
+    assert_eq!(css_color(Color::Indexed(196)).as_deref(), Some("#ff0000"));
+    assert_eq!(css_color(Color::Indexed(232)).as_deref(), Some("#080808"));
+}
+
+#[test]
+fn test_frame_hub_sends_full_frame_then_diffs() {
+    let mut hub = FrameHub::default();
+    let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 2));
+    buffer.set_string(0, 0, "ab", ratatui::style::Style::default().fg(Color::Green));
+    hub.publish(buffer.clone());
+
+    let (backlog, rx) = hub.subscribe();
+    let frame = data(&backlog[0]);
+    assert_eq!((frame["width"].as_u64(), frame["height"].as_u64()), (Some(4), Some(2)));
+    assert_eq!(frame["cells"][1], json!([1, 0, "b", "#00cd00", null, 0]));
+
+    hub.publish(buffer.clone());
+    assert!(rx.try_recv().is_err());
+    buffer.set_string(1, 1, "z", ratatui::style::Style::default());
+    hub.publish(buffer);
+    let diff = rx.try_recv().unwrap();
+    assert!(diff.text.contains("event: diff"));
+    assert_eq!(data(&diff)["cells"], json!([[1, 1, "z", null, null, 0]]));
+
+    hub.publish(Buffer::empty(Rect::new(0, 0, 2, 2)));
+    assert!(rx.try_recv().unwrap().text.contains("event: frame"));
+}
+
+#[test]
+fn test_mask_blanks_area() {
+    let mut buffer = Buffer::empty(Rect::new(0, 0, 6, 3));
+    for y in 0..3 {
+        buffer.set_string(0, y, "SECRET", Style::default());
+    }
+    mask(&mut buffer, Rect::new(1, 1, 10, 2), "hidden");
+    let rows: Vec<String> = (0..3).map(|y| (0..6).map(|x| buffer[(x, y)].symbol()).collect()).collect();
+    assert_eq!(rows, ["SECRET", "Shidde", "S     "]);
+}
+
+#[test]
+fn test_parse_key() {
+    assert_eq!(parse_key(r#"{"key": "k"}"#).unwrap(), KeyEvent::new(KeyCode::Char('k'), KeyModifiers::NONE));
+    assert_eq!(parse_key(r#"{"key": "K", "shift": true}"#).unwrap().code, KeyCode::Char('K'));
+    assert_eq!(parse_key(r#"{"key": "ArrowDown"}"#).unwrap().code, KeyCode::Down);
+    assert_eq!(parse_key(r#"{"key": "Tab", "shift": true}"#).unwrap().code, KeyCode::BackTab);
+    let ctrl_c = parse_key(r#"{"key": "c", "ctrl": true}"#).unwrap();
+    assert_eq!(describe_key(&ctrl_c), "Ctrl+c");
+    assert!(parse_key(r#"{"key": "F13"}"#).is_err());
+    assert!(parse_key(r#"{"code": 1}"#).is_err());
+}
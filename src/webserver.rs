@@ -1,25 +1,497 @@
+use crate::api::{self, ApiRequest, ApiResponse};
 use crate::app::App;
+use crate::control::{self, Control};
+use crate::metrics::MetricsOptions;
+#[cfg(test)]
+use crate::profiler::strings;
+use crate::stream::{self, Event, SharedStreamHub};
+use crate::web_tui::SharedFrameHub;
+use crossterm::event::KeyEvent;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::thread;
-use tiny_http::{Response, Server};
+use std::time::Duration;
+use tiny_http::{Header, Request, Response, Server};
 
-pub fn start_http_server(app: Arc<Mutex<App>>) {
-    thread::spawn(move || {
-        let server = Server::http("0.0.0.0:4242").unwrap();
+pub const USAGE: &str = "usage: task-manager [--bind ADDR:PORT | --unix-socket PATH [--socket-mode MODE] | --no-http]
+                    [--allow-host NAME]... [--read-only] [--control-token-file FILE] [--audit-log FILE]
+                    [--metrics-allow NAME[,NAME...]] [--metrics-top N]
+                    [--headless [--pidfile FILE] [--log-file FILE]]";
 
-        for request in server.incoming_requests() {
-            if request.url() == "/processes" {
-                let app_guard = app.lock().unwrap();
-                let json = serde_json::to_string(&app_guard.processes).unwrap();
-                let response = Response::from_string(json).with_header(
-                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
-                        .unwrap(),
+// Request bodies are small JSON objects, anything larger is refused unread
+const MAX_BODY: u64 = 64 * 1024;
+
+// An SSE comment sent while nothing changes, so proxies keep the stream open and gone clients are noticed
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+// Each open event stream holds a thread, further ones are refused
+const MAX_STREAMS: usize = 32;
+
+// Everything a request may need, shared with the threads serving event streams
+struct Shared {
+    app: Arc<Mutex<App>>,
+    hub: SharedStreamHub,
+    frames: SharedFrameHub,
+    keys: Option<Sender<KeyEvent>>, // to the terminal UI, absent when nothing reads them
+    control: Option<Control>,
+    read_only: bool,
+    metrics: MetricsOptions,
+    allowed_hosts: Option<Vec<String>>, // None skips the Host check, as on a Unix socket
+    streams: Arc<AtomicUsize>,          // event streams currently open
+}
+
+// Counts an open event stream until it is dropped
+struct StreamSlot(Arc<AtomicUsize>);
+
+impl StreamSlot {
+    fn acquire(streams: &Arc<AtomicUsize>) -> Option<Self> {
+        streams
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| (open < MAX_STREAMS).then_some(open + 1))
+            .ok()
+            .map(|_| StreamSlot(Arc::clone(streams)))
+    }
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Listen {
+    Disabled,
+    Tcp(String),
+    Unix { path: PathBuf, mode: u32 }, // access is controlled by the socket file's permissions
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerOptions {
+    pub listen: Listen,
+    pub allowed_hosts: Vec<String>, // host names accepted in the Host header besides localhost and IP addresses
+    pub read_only: bool, // refuses every request that is not a GET
+    pub control_token_file: Option<PathBuf>, // enables the POST control endpoints
+    pub audit_log: Option<PathBuf>,
+    pub metrics: MetricsOptions,
+    pub headless: bool, // no terminal UI, only the collector and the web server
+    pub pidfile: Option<PathBuf>,
+    pub log_file: Option<PathBuf>, // headless log, stderr if not given
+}
+
+// Only reachable from this host unless asked otherwise
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            listen: Listen::Tcp("127.0.0.1:4242".into()),
+            allowed_hosts: vec![],
+            read_only: false,
+            control_token_file: None,
+            audit_log: None,
+            metrics: MetricsOptions::default(),
+            headless: false,
+            pidfile: None,
+            log_file: None,
+        }
+    }
+}
+
+pub fn parse_server_args(args: &[String]) -> Result<ServerOptions, String> {
+    let mut options = ServerOptions::default();
+    let mut socket_mode = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--bind" => {
+                let addr = value()?;
+                // Validated here so a typo fails at startup instead of in the status line
+                if addr.parse::<std::net::SocketAddr>().is_err() {
+                    return Err(format!("invalid bind address '{}', expected e.g. 127.0.0.1:4242", addr));
+                }
+                options.listen = Listen::Tcp(addr);
+            }
+            "--unix-socket" => {
+                options.listen = Listen::Unix {
+                    path: value()?.into(),
+                    mode: 0o600,
+                }
+            }
+            "--socket-mode" => {
+                let mode = value()?;
+                socket_mode = Some(
+                    u32::from_str_radix(&mode, 8)
+                        .ok()
+                        .filter(|m| *m <= 0o777)
+                        .ok_or_else(|| format!("invalid socket mode '{}'", mode))?,
+                );
+            }
+            "--no-http" => options.listen = Listen::Disabled,
+            "--allow-host" => options.allowed_hosts.push(value()?.to_lowercase()),
+            "--read-only" => options.read_only = true,
+            "--control-token-file" => options.control_token_file = Some(value()?.into()),
+            "--audit-log" => options.audit_log = Some(value()?.into()),
+            "--metrics-allow" => options.metrics.allow.extend(value()?.split(',').map(str::to_owned)),
+            "--metrics-top" => {
+                let top = value()?;
+                options.metrics.top = Some(
+                    top.parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid process count '{}'", top))?,
                 );
-                request.respond(response).unwrap();
-            } else {
-                let response = Response::from_string("Hello from Rust HTTP server!");
-                request.respond(response).unwrap();
             }
+            "--headless" => options.headless = true,
+            "--pidfile" => options.pidfile = Some(value()?.into()),
+            "--log-file" => options.log_file = Some(value()?.into()),
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+    }
+    match (&mut options.listen, socket_mode) {
+        (Listen::Unix { mode, .. }, Some(socket_mode)) => *mode = socket_mode,
+        (_, Some(_)) => return Err("--socket-mode needs --unix-socket".into()),
+        _ => {}
+    }
+    if options.read_only && options.control_token_file.is_some() {
+        return Err("--read-only cannot be combined with --control-token-file".into());
+    }
+    if !options.headless && (options.pidfile.is_some() || options.log_file.is_some()) {
+        return Err("--pidfile and --log-file need --headless".into());
+    }
+    if options.headless && options.listen == Listen::Disabled {
+        return Err("--headless cannot be combined with --no-http".into());
+    }
+    Ok(options)
+}
+
+// Process control stays disabled unless a token file was given
+pub fn load_control(options: &ServerOptions) -> Result<Option<Control>, String> {
+    let Some(token_file) = &options.control_token_file else {
+        return Ok(None);
+    };
+    if options.listen == Listen::Disabled {
+        return Ok(None);
+    }
+    let audit_log = options.audit_log.clone().unwrap_or_else(control::default_audit_log);
+    Control::load(token_file, &audit_log).map(Some)
+}
+
+fn to_response(api_response: ApiResponse) -> Response<std::io::Cursor<Vec<u8>>> {
+    let mut response = Response::from_string(api_response.body).with_status_code(api_response.status);
+    let headers = std::iter::once(("Content-Type".to_string(), api_response.content_type.to_string()))
+        .chain(api_response.headers);
+    for (name, value) in headers {
+        if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            response.add_header(header);
+        }
+    }
+    response
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().to_owned())
+}
+
+// Written straight to the socket: the chunked encoder tiny_http uses for bodies of unknown
+// length holds small events back until 8 KiB have piled up
+fn write_event_stream(request: Request, backlog: Vec<Event>, events: Receiver<Event>) {
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    let mut send = |text: &str| writer.write_all(text.as_bytes()).and_then(|_| writer.flush());
+    if send(head).is_err() || backlog.iter().any(|event| send(&event.text).is_err()) {
+        return;
+    }
+    loop {
+        let sent = match events.recv_timeout(KEEPALIVE) {
+            Ok(event) => send(&event.text),
+            Err(RecvTimeoutError::Timeout) => send(": keepalive\n\n"),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+fn stream_events(request: Request, shared: &Shared) {
+    let (backlog, events) = if request.url().starts_with("/tui/") {
+        shared.frames.lock().unwrap().subscribe()
+    } else {
+        let (path_query, last_event_id) = (request.url().to_owned(), header(&request, "Last-Event-ID"));
+        let query = path_query.split_once('?').map(|(_, q)| q).unwrap_or("");
+        let since = stream::resume_point(last_event_id.as_deref(), query);
+        shared.hub.lock().unwrap().subscribe(since)
+    };
+    write_event_stream(request, backlog, events);
+}
+
+fn is_event_stream(request: &Request) -> bool {
+    matches!(request.url().split('?').next(), Some("/events" | "/tui/events"))
+}
+
+// DNS rebinding needs a host name the attacker controls, so IP addresses and localhost are
+// always fine and other names only when allowed explicitly
+pub fn host_allowed(host: Option<&str>, allowed: &[String]) -> bool {
+    let Some(host) = host else {
+        return true; // HTTP/1.0 clients; browsers always send one
+    };
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or(""),
+        None => host.rsplit_once(':').map(|(name, _)| name).unwrap_or(host),
+    };
+    let name = name.to_lowercase();
+    name.parse::<std::net::IpAddr>().is_ok() || name == "localhost" || allowed.contains(&name)
+}
+
+fn serve(mut request: Request, shared: &Arc<Shared>) {
+    let is_get = request.method().as_str() == "GET";
+    if let Some(allowed) = &shared.allowed_hosts
+        && !host_allowed(header(&request, "Host").as_deref(), allowed)
+    {
+        let message = "host not allowed, add it with --allow-host";
+        let _ = request.respond(to_response(ApiResponse::error(403, message)));
+        return;
+    }
+    if is_event_stream(&request) && is_get {
+        let Some(slot) = StreamSlot::acquire(&shared.streams) else {
+            let _ = request.respond(to_response(ApiResponse::error(503, "too many event streams")));
+            return;
+        };
+        // Each stream holds its connection open, so it gets a thread of its own
+        let shared = Arc::clone(shared);
+        thread::spawn(move || {
+            let _slot = slot;
+            stream_events(request, &shared)
+        });
+        return;
+    }
+    let (app, control) = (&shared.app, shared.control.as_ref());
+    let mut body = String::new();
+    let response = match request.as_reader().take(MAX_BODY + 1).read_to_string(&mut body) {
+        _ if shared.read_only && !is_get => ApiResponse::error(403, "the server is read-only"),
+        Ok(_) if body.len() as u64 > MAX_BODY => ApiResponse::error(413, "request body too large"),
+        Err(e) => ApiResponse::error(400, &format!("unreadable request body: {}", e)),
+        Ok(_) => {
+            let api_request = ApiRequest {
+                method: request.method().as_str().to_owned(),
+                url: request.url().to_owned(),
+                body,
+                authorization: header(&request, "Authorization"),
+                user_agent: header(&request, "User-Agent"),
+                client: request
+                    .remote_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|| "unix socket".into()),
+            };
+            api::handle(&api_request, app, control, shared.keys.as_ref(), &shared.metrics)
+        }
+    };
+    // The client may already have hung up
+    let _ = request.respond(to_response(response));
+}
+
+// A leftover socket from an earlier run would make the bind fail, other files are left alone.
+// The umask makes bind create the socket with its final mode, so it is never reachable by
+// anyone else in between; binding happens at startup, before other threads create files.
+fn bind_unix(path: &Path, mode: u32) -> Result<Server, String> {
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let previous = unsafe { libc::umask(!mode as libc::mode_t & 0o777) };
+    let server = Server::http_unix(path);
+    unsafe { libc::umask(previous) };
+    server.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Binds before returning so the caller can report a taken port; Ok says where it listens
+pub fn start_http_server(
+    app: Arc<Mutex<App>>,
+    hub: SharedStreamHub,
+    frames: SharedFrameHub,
+    keys: Option<Sender<KeyEvent>>,
+    options: &ServerOptions,
+    control: Option<Control>,
+) -> Result<Option<String>, String> {
+    let (server, description) = match &options.listen {
+        Listen::Disabled => return Ok(None),
+        Listen::Tcp(addr) => (
+            Server::http(addr).map_err(|e| format!("{}: {}", addr, e))?,
+            format!("http://{}", addr),
+        ),
+        Listen::Unix { path, mode } => (bind_unix(path, *mode)?, format!("unix:{}", path.display())),
+    };
+    let shared = Arc::new(Shared {
+        app,
+        hub,
+        frames,
+        keys,
+        control,
+        read_only: options.read_only,
+        metrics: options.metrics.clone(),
+        allowed_hosts: matches!(options.listen, Listen::Tcp(_)).then(|| options.allowed_hosts.clone()),
+        streams: Arc::default(),
+    });
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            serve(request, &shared);
         }
     });
+    Ok(Some(description))
+}
+
+#[cfg(test)]
+fn send(path: &Path, request: &str) -> String {
+    let mut stream = std::os::unix::net::UnixStream::connect(path).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_unix_socket_read_only() {
+    let path = control::temp_path("http.sock");
+    let options = ServerOptions {
+        listen: Listen::Unix { path: path.clone(), mode: 0o660 },
+        read_only: true,
+        ..Default::default()
+    };
+    let app = Arc::new(Mutex::new(App::default()));
+    let description = start_http_server(app, SharedStreamHub::default(), SharedFrameHub::default(), None, &options, None).unwrap();
+    assert_eq!(description, Some(format!("unix:{}", path.display())));
+    use std::os::unix::fs::PermissionsExt;
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o660);
+
+    let get = send(&path, "GET /system HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+    assert!(get.starts_with("HTTP/1.1 200"), "{}", get);
+    let post = send(
+        &path,
+        "POST /processes/1/signal HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}",
+    );
+    assert!(post.starts_with("HTTP/1.1 403"), "{}", post);
+    assert!(post.contains("read-only"));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_event_stream_resumes() {
+    use std::io::{BufRead, BufReader};
+    let path = control::temp_path("events.sock");
+    let options = ServerOptions {
+        listen: Listen::Unix { path: path.clone(), mode: 0o600 },
+        ..Default::default()
+    };
+    let hub = SharedStreamHub::default();
+    hub.lock().unwrap().publish(&[crate::process::ProcessInfo::new(1, "init".into(), 0, 0)]);
+    start_http_server(Arc::new(Mutex::new(App::default())), Arc::clone(&hub), SharedFrameHub::default(), None, &options, None).unwrap();
+
+    let mut stream = std::os::unix::net::UnixStream::connect(&path).unwrap();
+    stream.write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\nLast-Event-ID: 0\r\n\r\n").unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut next_line = || {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line
+    };
+    assert!(next_line().starts_with("HTTP/1.1 200"));
+    while next_line() != "\r\n" {}
+    // Resuming from 0 replays delta 1 instead of a snapshot
+    assert_eq!(next_line(), "id: 1\n");
+    assert_eq!(next_line(), "event: delta\n");
+    next_line();
+    next_line();
+
+    hub.lock().unwrap().publish(&[]);
+    assert_eq!(next_line(), "id: 2\n");
+    assert_eq!(next_line(), "event: delta\n");
+    assert!(next_line().contains(r#""removed":[1]"#));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_stream_slots_are_capped() {
+    let streams = Arc::default();
+    let slots: Vec<StreamSlot> = (0..MAX_STREAMS).map_while(|_| StreamSlot::acquire(&streams)).collect();
+    assert_eq!(slots.len(), MAX_STREAMS);
+    assert!(StreamSlot::acquire(&streams).is_none());
+    drop(slots);
+    assert!(StreamSlot::acquire(&streams).is_some());
+}
+
+#[test]
+fn test_bind_failure_is_an_error() {
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let options = ServerOptions {
+        listen: Listen::Tcp(taken.local_addr().unwrap().to_string()),
+        ..Default::default()
+    };
+    let app = Arc::new(Mutex::new(App::default()));
+    assert!(start_http_server(app.clone(), SharedStreamHub::default(), SharedFrameHub::default(), None, &options, None).is_err());
+    let disabled = ServerOptions {
+        listen: Listen::Disabled,
+        ..Default::default()
+    };
+    assert_eq!(start_http_server(app, SharedStreamHub::default(), SharedFrameHub::default(), None, &disabled, None), Ok(None));
+}
+
+#[test]
+fn test_host_allowed() {
+    let allowed = vec!["monitor.example.org".to_string()];
+    for host in [None, Some("localhost:4242"), Some("127.0.0.1:4242"), Some("[::1]:4242"), Some("10.0.0.5"), Some("Monitor.example.org:80")] {
+        assert!(host_allowed(host, &allowed), "{:?}", host);
+    }
+    for host in [Some("evil.example.com:4242"), Some("localhost.evil.com"), Some("")] {
+        assert!(!host_allowed(host, &allowed), "{:?}", host);
+    }
+}
+
+#[test]
+fn test_parse_server_args() {
+    let options = parse_server_args(&strings(&["--control-token-file", "/etc/tm/token", "--audit-log", "/var/log/tm"])).unwrap();
+    assert_eq!(options.control_token_file, Some(PathBuf::from("/etc/tm/token")));
+    assert_eq!(options.audit_log, Some(PathBuf::from("/var/log/tm")));
+    assert_eq!(parse_server_args(&[]).unwrap(), ServerOptions::default());
+    assert!(parse_server_args(&strings(&["--control-token-file"])).is_err());
+    assert!(parse_server_args(&strings(&["--verbose"])).is_err());
+
+    assert_eq!(parse_server_args(&[]).unwrap().listen, Listen::Tcp("127.0.0.1:4242".into()));
+    assert_eq!(
+        parse_server_args(&strings(&["--bind", "0.0.0.0:8080"])).unwrap().listen,
+        Listen::Tcp("0.0.0.0:8080".into())
+    );
+    assert!(parse_server_args(&strings(&["--bind", "localhost"])).is_err());
+    assert_eq!(
+        parse_server_args(&strings(&["--unix-socket", "/run/tm.sock", "--socket-mode", "660"])).unwrap().listen,
+        Listen::Unix {
+            path: "/run/tm.sock".into(),
+            mode: 0o660
+        }
+    );
+    assert!(parse_server_args(&strings(&["--socket-mode", "660"])).is_err());
+    assert!(parse_server_args(&strings(&["--unix-socket", "/s", "--socket-mode", "999"])).is_err());
+    assert_eq!(parse_server_args(&strings(&["--no-http"])).unwrap().listen, Listen::Disabled);
+    assert!(parse_server_args(&strings(&["--read-only", "--control-token-file", "/t"])).is_err());
+    assert_eq!(parse_server_args(&strings(&["--allow-host", "Box.lan"])).unwrap().allowed_hosts, vec!["box.lan"]);
+
+    let metrics = parse_server_args(&strings(&["--metrics-allow", "nginx,sshd", "--metrics-allow", "init", "--metrics-top", "20"]))
+        .unwrap()
+        .metrics;
+    assert_eq!(metrics.allow, vec!["nginx", "sshd", "init"]);
+    assert_eq!(metrics.top, Some(20));
+    assert!(parse_server_args(&strings(&["--metrics-top", "0"])).is_err());
+
+    let headless = parse_server_args(&strings(&["--headless", "--pidfile", "/run/tm.pid", "--log-file", "/var/log/tm.log"])).unwrap();
+    assert!(headless.headless);
+    assert_eq!(headless.pidfile, Some(PathBuf::from("/run/tm.pid")));
+    assert_eq!(headless.log_file, Some(PathBuf::from("/var/log/tm.log")));
+    assert!(parse_server_args(&strings(&["--pidfile", "/run/tm.pid"])).is_err());
+    assert!(parse_server_args(&strings(&["--headless", "--no-http"])).is_err());
 }